@@ -1,47 +1,115 @@
-use std::sync::LazyLock;
+//! ai module
+//!
+//! 通过本地的 Ollama 服务调用大语言模型，地址和模型可以在配置文件的 `[ollama]` 中设置
+
+use std::{future::Future, result, sync::LazyLock};
 
 use ollama_rs::{
     Ollama,
     error::OllamaError,
-    generation::completion::{GenerationResponseStream, request::GenerationRequest},
+    generation::{completion::request::GenerationRequest, parameters::FormatType},
+    models::ModelOptions,
 };
-use tokio::io::{AsyncWriteExt, BufWriter};
+use thiserror::Error;
 use tokio_stream::StreamExt;
 
-const DEF_MODEL: &str = "gemma3n:e4b";
+use crate::config::config;
+
+/// 默认使用的模型
+pub const DEF_MODEL: &str = "gemma3n:e4b";
+
+#[derive(Debug, Error)]
+pub enum AiError {
+    #[error(
+        "Ollama request failed: {0}, check that `ollama serve` is running and the model is pulled"
+    )]
+    Ollama(#[from] OllamaError),
+    #[error("Invalid Ollama url: {0}")]
+    InvalidUrl(String),
+}
+
+pub type Result<T> = result::Result<T, AiError>;
 
+/// 全局的 Ollama 客户端，使用配置中的 `ollama.url`，没有配置时为 `http://localhost:11434`
 fn ollama_client() -> &'static Ollama {
-    static OLLAMA_CLI: LazyLock<Ollama> = LazyLock::new(|| Ollama::default());
+    static OLLAMA_CLI: LazyLock<Ollama> = LazyLock::new(|| match &config().ollama.url {
+        Some(url) => Ollama::try_new(url.as_str()).unwrap_or_else(|_| {
+            eprintln!("warning: invalid ollama.url {}, using the default", url);
+            Ollama::default()
+        }),
+        None => Ollama::default(),
+    });
     &OLLAMA_CLI
 }
 
-// async fn generate_content_once(question: &str) -> Result<String, OllamaError> {
-//     let cli = ollama_client();
+/// 可以根据提示生成文本的模型
+pub trait TextModel: Sync {
+    /// 用于输出的模型名称
+    fn name(&self) -> &str;
 
-//     let mut buf = vec![];
-//     let mut bw = BufWriter::new(&mut buf);
+    /// 生成 `prompt` 的回答，要求输出 JSON；每收到一段输出调用一次 `on_chunk`，返回完整的输出
+    fn generate(
+        &self,
+        system: &str,
+        prompt: &str,
+        on_chunk: &mut (dyn FnMut(&str) + Send),
+    ) -> impl Future<Output = Result<String>> + Send;
+}
 
-//     let request = GenerationRequest::new(DEF_MODEL.into(), question.to_string());
-//     let mut stream: GenerationResponseStream = cli.generate_stream(request).await?;
+/// Ollama 上的一个模型
+#[derive(Debug, Clone)]
+pub struct OllamaModel {
+    client: Ollama,
+    model: String,
+}
 
-//     while let Some(Ok(res)) = stream.next().await {
-//         for ele in res {
-//             bw.write_all(ele.response.as_bytes()).await?;
-//             bw.flush().await?;
-//         }
-//     }
+impl OllamaModel {
+    /// 使用指定的服务地址和模型
+    pub fn new(url: &str, model: impl Into<String>) -> Result<Self> {
+        let client = Ollama::try_new(url).map_err(|_| AiError::InvalidUrl(url.to_string()))?;
+        Ok(Self {
+            client,
+            model: model.into(),
+        })
+    }
 
-//     Ok(String::from_utf8_lossy(&buf).to_string())
-// }
+    /// 使用配置中的服务地址，`model` 为空时使用配置中的 `ollama.model` 或者 [`DEF_MODEL`]
+    pub fn from_config(model: Option<String>) -> Self {
+        let model = model
+            .or_else(|| config().ollama.model.clone())
+            .unwrap_or_else(|| DEF_MODEL.to_string());
+        Self {
+            client: ollama_client().clone(),
+            model,
+        }
+    }
+}
 
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
+impl TextModel for OllamaModel {
+    fn name(&self) -> &str {
+        &self.model
+    }
 
-//     #[tokio::test]
-//     async fn test_generate_content_once() {
-//         let q = "我是谁？";
-//         let res = generate_content_once(q).await.unwrap();
-//         println!("res is\n{}", res)
-//     }
-// }
+    async fn generate(
+        &self,
+        system: &str,
+        prompt: &str,
+        on_chunk: &mut (dyn FnMut(&str) + Send),
+    ) -> Result<String> {
+        // 翻译需要稳定的输出，使用较低的温度
+        let request = GenerationRequest::new(self.model.clone(), prompt)
+            .system(system)
+            .format(FormatType::Json)
+            .options(ModelOptions::default().temperature(0.2));
+        let mut stream = self.client.generate_stream(request).await?;
+
+        let mut output = String::new();
+        while let Some(chunk) = stream.next().await {
+            for response in chunk? {
+                on_chunk(&response.response);
+                output.push_str(&response.response);
+            }
+        }
+        Ok(output)
+    }
+}
@@ -0,0 +1,374 @@
+//! batch module
+//! 批量处理多个文件
+//!
+//! 输入可以是单个文件、目录（递归查找）或者 glob 模式，文件会在 tokio 上以有限的并发数处理，
+//! 单个文件失败不会中断整个批次，最后按输入顺序汇总每个文件的结果。收到 Ctrl-C 时取消整个批次
+
+use std::{
+    fmt::Display,
+    future::{self, Future},
+    path::{Path, PathBuf},
+    result,
+    sync::Arc,
+};
+
+use thiserror::Error;
+use tokio::{signal, sync::Semaphore, task::JoinSet};
+use walkdir::WalkDir;
+
+/// 常见的视频容器扩展名
+pub const VIDEO_EXTENSIONS: &[&str] = &[
+    "mkv", "mp4", "m4v", "mov", "avi", "ts", "m2ts", "webm", "wmv", "flv",
+];
+
+/// 字幕文件扩展名
+pub const SRT_EXTENSIONS: &[&str] = &["srt"];
+
+/// 因中断而没有完成的文件的错误信息
+const INTERRUPTED: &str = "interrupted";
+
+#[derive(Debug, Error)]
+pub enum BatchError {
+    #[error("No matching files found for {0}")]
+    NoInput(String),
+    #[error("Invalid glob pattern: {0}")]
+    PatternError(#[from] glob::PatternError),
+    #[error("{failed} of {total} files failed")]
+    Failed { failed: usize, total: usize },
+    #[error("Interrupted after {succeeded} of {total} files")]
+    Interrupted { succeeded: usize, total: usize },
+}
+
+pub type Result<T> = result::Result<T, BatchError>;
+
+/// 输入是否需要按照批量模式处理，即是一个目录或者 glob 模式
+pub fn is_batch_input(input: &str) -> bool {
+    Path::new(input).is_dir() || is_glob(input)
+}
+
+fn is_glob(input: &str) -> bool {
+    input.contains(['*', '?', '['])
+}
+
+/// 将输入展开为文件列表
+///
+/// * 普通文件原样返回，不检查扩展名
+/// * 目录会被递归遍历，只保留扩展名在 `extensions` 中的文件（不区分大小写）
+/// * glob 模式匹配到的文件同样按扩展名过滤
+///
+/// 结果按路径排序，没有找到任何文件时返回错误
+pub fn collect_inputs(input: &str, extensions: &[&str]) -> Result<Vec<PathBuf>> {
+    let path = Path::new(input);
+    let has_extension = |p: &Path| {
+        p.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| extensions.iter().any(|x| x.eq_ignore_ascii_case(e)))
+    };
+    let mut files: Vec<PathBuf> = if path.is_file() {
+        vec![path.to_path_buf()]
+    } else if path.is_dir() {
+        WalkDir::new(path)
+            .follow_links(true)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file() && has_extension(entry.path()))
+            .map(|entry| entry.into_path())
+            .collect()
+    } else if is_glob(input) {
+        glob::glob(input)?
+            .filter_map(|entry| entry.ok())
+            .filter(|p| p.is_file() && has_extension(p))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    if files.is_empty() {
+        return Err(BatchError::NoInput(input.to_string()));
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// 默认的并发数，为可用的 CPU 核心数
+pub fn default_jobs() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+}
+
+/// 单个文件的处理结果
+#[derive(Debug)]
+pub struct FileOutcome<T> {
+    pub path: PathBuf,
+    pub result: result::Result<T, String>,
+}
+
+/// 整个批次的处理结果，顺序与输入顺序相同
+#[derive(Debug)]
+pub struct BatchSummary<T> {
+    pub outcomes: Vec<FileOutcome<T>>,
+    /// 批次是否被 Ctrl-C 中断，此时未完成的文件都记录为失败
+    pub interrupted: bool,
+}
+
+impl<T> BatchSummary<T> {
+    pub fn failed(&self) -> usize {
+        self.outcomes.iter().filter(|o| o.result.is_err()).count()
+    }
+
+    pub fn succeeded(&self) -> usize {
+        self.outcomes.len() - self.failed()
+    }
+
+    /// 在标准错误中打印每个文件的结果以及汇总信息，不干扰标准输出中的正常输出
+    pub fn print(&self) {
+        for outcome in &self.outcomes {
+            match &outcome.result {
+                Ok(_) => eprintln!("[ok]     {}", outcome.path.display()),
+                Err(e) => eprintln!("[failed] {}: {}", outcome.path.display(), e),
+            }
+        }
+        eprintln!(
+            "{} succeeded, {} failed, {} total{}",
+            self.succeeded(),
+            self.failed(),
+            self.outcomes.len(),
+            if self.interrupted {
+                ", interrupted"
+            } else {
+                ""
+            }
+        );
+    }
+
+    /// 批次被中断时返回 `BatchError::Interrupted`，有任何文件失败时返回 `BatchError::Failed`
+    pub fn into_result(self) -> Result<()> {
+        if self.interrupted {
+            return Err(BatchError::Interrupted {
+                succeeded: self.succeeded(),
+                total: self.outcomes.len(),
+            });
+        }
+        match self.failed() {
+            0 => Ok(()),
+            failed => Err(BatchError::Failed {
+                failed,
+                total: self.outcomes.len(),
+            }),
+        }
+    }
+}
+
+/// 以最多 `jobs` 个并发任务对每个文件执行 `f`
+///
+/// 任务出错或者 panic 都只记录为该文件失败，不影响其它文件；收到 Ctrl-C 时取消正在运行的任务，
+/// 不再开始新的任务
+pub async fn run_batch<T, E, F, Fut>(files: Vec<PathBuf>, jobs: usize, f: F) -> BatchSummary<T>
+where
+    T: Send + 'static,
+    E: Display,
+    F: Fn(PathBuf) -> Fut,
+    Fut: Future<Output = result::Result<T, E>> + Send + 'static,
+{
+    let interrupt = async {
+        // 无法监听信号时只能等待批次完成
+        if signal::ctrl_c().await.is_err() {
+            future::pending::<()>().await;
+        }
+    };
+    run_batch_until(files, jobs, f, interrupt).await
+}
+
+/// 同 [`run_batch`]，`interrupt` 完成时中断批次
+async fn run_batch_until<T, E, F, Fut>(
+    files: Vec<PathBuf>,
+    jobs: usize,
+    f: F,
+    interrupt: impl Future<Output = ()>,
+) -> BatchSummary<T>
+where
+    T: Send + 'static,
+    E: Display,
+    F: Fn(PathBuf) -> Fut,
+    Fut: Future<Output = result::Result<T, E>> + Send + 'static,
+{
+    let semaphore = Arc::new(Semaphore::new(jobs.max(1)));
+    let mut set = JoinSet::new();
+    for (idx, path) in files.iter().enumerate() {
+        let fut = f(path.clone());
+        let semaphore = semaphore.clone();
+        set.spawn(async move {
+            // 中断时信号量被关闭，还在排队的任务直接结束
+            let Ok(_permit) = semaphore.acquire_owned().await else {
+                return (idx, Err(INTERRUPTED.to_string()));
+            };
+            (idx, fut.await.map_err(|e| e.to_string()))
+        });
+    }
+
+    let mut results: Vec<Option<result::Result<T, String>>> = files.iter().map(|_| None).collect();
+    let mut interrupted = false;
+    tokio::pin!(interrupt);
+    // panic 或者被取消的任务无法得知是哪个文件，没有结果的文件在最后统一标记为失败
+    loop {
+        tokio::select! {
+            joined = set.join_next() => match joined {
+                Some(Ok((idx, result))) => results[idx] = Some(result),
+                Some(Err(_)) => {}
+                None => break,
+            },
+            _ = &mut interrupt, if !interrupted => {
+                interrupted = true;
+                semaphore.close();
+                set.abort_all();
+            }
+        }
+    }
+
+    let missing = if interrupted {
+        INTERRUPTED
+    } else {
+        "task panicked"
+    };
+    BatchSummary {
+        outcomes: files
+            .into_iter()
+            .zip(results)
+            .map(|(path, result)| FileOutcome {
+                path,
+                result: result.unwrap_or_else(|| Err(missing.to_string())),
+            })
+            .collect(),
+        interrupted,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use super::*;
+
+    fn touch(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+    }
+
+    #[test]
+    fn test_collect_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        touch(&root.join("s01/e01.mkv"));
+        touch(&root.join("s01/e01.srt"));
+        touch(&root.join("s02/deep/e02.MP4"));
+        touch(&root.join("notes.txt"));
+
+        let videos = collect_inputs(root.to_str().unwrap(), VIDEO_EXTENSIONS).unwrap();
+        assert_eq!(
+            videos,
+            vec![root.join("s01/e01.mkv"), root.join("s02/deep/e02.MP4")]
+        );
+
+        let pattern = format!("{}/*/*.srt", root.display());
+        assert_eq!(
+            collect_inputs(&pattern, SRT_EXTENSIONS).unwrap(),
+            vec![root.join("s01/e01.srt")]
+        );
+        assert!(is_batch_input(&pattern));
+
+        // 单个文件不检查扩展名
+        let single = root.join("notes.txt");
+        assert_eq!(
+            collect_inputs(single.to_str().unwrap(), SRT_EXTENSIONS).unwrap(),
+            vec![single.clone()]
+        );
+        assert!(!is_batch_input(single.to_str().unwrap()));
+
+        assert!(matches!(
+            collect_inputs(root.join("missing").to_str().unwrap(), SRT_EXTENSIONS),
+            Err(BatchError::NoInput(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_run_batch_keeps_going_after_failures() {
+        let files: Vec<PathBuf> = (0..6)
+            .map(|i| PathBuf::from(format!("{}.srt", i)))
+            .collect();
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let summary = run_batch(files, 2, |path| {
+            let (running, peak) = (running.clone(), peak.clone());
+            async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                match path.to_str() {
+                    Some("3.srt") => Err("broken file"),
+                    Some("4.srt") => panic!("unexpected"),
+                    _ => Ok(path.with_extension("out")),
+                }
+            }
+        })
+        .await;
+
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+        assert_eq!(summary.succeeded(), 4);
+        assert_eq!(summary.failed(), 2);
+        // 结果保持输入顺序
+        assert_eq!(summary.outcomes[3].path, PathBuf::from("3.srt"));
+        assert_eq!(summary.outcomes[3].result, Err("broken file".to_string()));
+        assert_eq!(summary.outcomes[5].result, Ok(PathBuf::from("5.out")));
+        assert!(matches!(
+            summary.into_result(),
+            Err(BatchError::Failed {
+                failed: 2,
+                total: 6
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_run_batch_stops_on_interrupt() {
+        let files: Vec<PathBuf> = (0..6)
+            .map(|i| PathBuf::from(format!("{}.srt", i)))
+            .collect();
+        let started = Arc::new(AtomicUsize::new(0));
+
+        let summary = run_batch_until(
+            files,
+            2,
+            |path| {
+                let started = started.clone();
+                async move {
+                    started.fetch_add(1, Ordering::SeqCst);
+                    if path != Path::new("0.srt") {
+                        tokio::time::sleep(Duration::from_secs(60)).await;
+                    }
+                    Ok::<_, String>(())
+                }
+            },
+            tokio::time::sleep(Duration::from_millis(50)),
+        )
+        .await;
+
+        // 第一个文件完成后只开始了两个新的任务，中断后不再开始新的任务
+        assert_eq!(started.load(Ordering::SeqCst), 3);
+        assert!(summary.interrupted);
+        assert_eq!(summary.succeeded(), 1);
+        assert_eq!(summary.outcomes[1].result, Err(INTERRUPTED.to_string()));
+        assert!(matches!(
+            summary.into_result(),
+            Err(BatchError::Interrupted {
+                succeeded: 1,
+                total: 6
+            })
+        ));
+    }
+}
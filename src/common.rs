@@ -1,14 +1,23 @@
 use std::{
-    ffi::OsStr,
-    io,
+    collections::VecDeque,
+    ffi::{OsStr, OsString},
+    future, io,
     os::unix::fs::MetadataExt,
     path::{Path, PathBuf},
-    process::ExitStatus,
+    process::{ExitStatus, Stdio},
     result,
+    sync::Mutex,
+    time::Duration,
 };
 
+use clap::ValueEnum;
 use thiserror::Error;
-use tokio::{fs::File, io::AsyncReadExt, process::Command};
+use tokio::{
+    fs::File,
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, BufReader},
+    process::{Child, Command},
+    signal, time,
+};
 use walkdir::WalkDir;
 use which::which;
 
@@ -28,6 +37,18 @@ pub enum CommonError {
 
 type Result<T> = result::Result<T, CommonError>;
 
+/// 定义输出格式的枚举类型。
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    /// 输出为 JSON 格式
+    Json,
+    /// 输出为表格格式
+    #[clap(name = "tab")]
+    Table,
+    /// 输出为列表格式
+    List,
+}
+
 /// 根据给定的路径 `[ori_path]` 生成一个相同的路径，但是在路径的最后一个元素加上指定的分隔符 `[c]` 和后缀 `[suffix]`
 /// # Error
 /// * 如果路径没有“最后一个文件“，会返回错误
@@ -36,7 +57,7 @@ pub fn same_path_with<P: AsRef<Path>>(ori_path: P, suffix: &str, c: &str) -> Res
     let mut new_path = PathBuf::from(ori_path);
     let new_name = ori_path
         .file_stem()
-        .ok_or_else(|| CommonError::PathMissingFinalError)?
+        .ok_or(CommonError::PathMissingFinalError)?
         .to_str()
         .ok_or(CommonError::NonUtf8PathError)?;
     let new_name = format!("{}{}{}", new_name, c, suffix);
@@ -46,9 +67,14 @@ pub fn same_path_with<P: AsRef<Path>>(ori_path: P, suffix: &str, c: &str) -> Res
 }
 
 /// 查找某个命令的完整路径，如果提供了父目录 `path` ，那么会在父目录下查找，不会进入该目录的其它子目录，否则会在环境变量中查找，使用等效于Unix系统的 `which` 功能
+///
+/// 如果 `path` 本身就是一个文件，则直接把它当作命令返回
 pub fn find_command_path<P: AsRef<Path>>(path: Option<P>, command: &str) -> Option<PathBuf> {
     match path {
         Some(path) => {
+            if path.as_ref().is_file() {
+                return Some(path.as_ref().to_path_buf());
+            }
             if path.as_ref().is_dir() {
                 for entry in WalkDir::new(path).max_depth(1) {
                     let entry = entry.ok()?;
@@ -71,6 +97,10 @@ pub enum CommandError {
         status: ExitStatus,
         stderr: String,
     },
+    #[error("Command [{cmd}] timed out after {timeout:?}")]
+    Timeout { cmd: String, timeout: Duration },
+    #[error("Command [{cmd}] was interrupted")]
+    Interrupted { cmd: String },
     #[error(transparent)]
     Io(#[from] io::Error),
 }
@@ -80,27 +110,351 @@ pub async fn exec_command<S: AsRef<OsStr>>(
     cmd: impl AsRef<Path>,
     options: Option<Vec<S>>,
 ) -> Result<(String, String)> {
+    exec_command_with(&SystemRunner, cmd, options).await
+}
+
+/// 与 [`exec_command`] 相同，但是通过指定的 `runner` 执行命令
+pub async fn exec_command_with<R: ProcessRunner, S: AsRef<OsStr>>(
+    runner: &R,
+    cmd: impl AsRef<Path>,
+    options: Option<Vec<S>>,
+) -> Result<(String, String)> {
+    let args: Vec<OsString> = options
+        .into_iter()
+        .flatten()
+        .map(|o| o.as_ref().to_os_string())
+        .collect();
+    runner
+        .run(cmd.as_ref(), &args, ExecOptions::default())
+        .await
+}
+
+/// 外部命令的执行者
+///
+/// 所有调用外部程序（ffmpeg、ffprobe 等）的地方都通过它完成，测试时可以替换为 [`ReplayRunner`]，
+/// 这样不需要在机器上安装这些程序
+pub trait ProcessRunner: Sync {
+    /// 查找命令的完整路径，语义与 [`find_command_path`] 相同
+    fn locate(&self, path: Option<&Path>, command: &str) -> Option<PathBuf> {
+        find_command_path(path, command)
+    }
+
+    /// 执行命令，返回标准输出和标准错误的内容，命令失败时返回 `CommandError::CommandFailed`
+    fn run(
+        &self,
+        cmd: &Path,
+        args: &[OsString],
+        exec: ExecOptions<'_>,
+    ) -> impl Future<Output = Result<(String, String)>> + Send;
+}
+
+/// 真正创建子进程的执行者
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemRunner;
+
+impl ProcessRunner for SystemRunner {
+    async fn run(
+        &self,
+        cmd: &Path,
+        args: &[OsString],
+        exec: ExecOptions<'_>,
+    ) -> Result<(String, String)> {
+        exec_command_streaming(cmd, Some(args.iter().collect()), exec).await
+    }
+}
+
+/// [`ReplayRunner`] 记录下来的一次调用
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedCall {
+    pub cmd: PathBuf,
+    pub args: Vec<String>,
+}
+
+/// [`ReplayRunner`] 回放的一次命令结果
+#[derive(Debug, Clone)]
+pub struct ReplayResponse {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: i32,
+}
+
+/// 记录所有调用并按顺序回放预设结果的执行者，用于测试
+///
+/// 预设的结果用完后再调用会返回 `io::ErrorKind::NotFound`，就像命令不存在一样
+#[derive(Debug, Default)]
+pub struct ReplayRunner {
+    responses: Mutex<VecDeque<ReplayResponse>>,
+    calls: Mutex<Vec<RecordedCall>>,
+    missing: Vec<String>,
+}
+
+impl ReplayRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 让 `locate` 认为命令 `command` 不存在
+    pub fn with_missing(mut self, command: &str) -> Self {
+        self.missing.push(command.to_string());
+        self
+    }
+
+    /// 追加一次成功的结果
+    pub fn push_success(&self, stdout: impl Into<String>, stderr: impl Into<String>) {
+        self.push(ReplayResponse {
+            stdout: stdout.into(),
+            stderr: stderr.into(),
+            exit_code: 0,
+        });
+    }
+
+    /// 追加一次以 `exit_code` 退出的失败结果
+    pub fn push_failure(&self, exit_code: i32, stderr: impl Into<String>) {
+        self.push(ReplayResponse {
+            stdout: String::new(),
+            stderr: stderr.into(),
+            exit_code,
+        });
+    }
+
+    pub fn push(&self, response: ReplayResponse) {
+        self.responses
+            .lock()
+            .expect("replay runner lock poisoned")
+            .push_back(response);
+    }
+
+    /// 目前为止记录下的所有调用
+    pub fn calls(&self) -> Vec<RecordedCall> {
+        self.calls
+            .lock()
+            .expect("replay runner lock poisoned")
+            .clone()
+    }
+}
+
+impl ProcessRunner for ReplayRunner {
+    fn locate(&self, _path: Option<&Path>, command: &str) -> Option<PathBuf> {
+        if self.missing.iter().any(|m| m == command) {
+            None
+        } else {
+            Some(PathBuf::from(command))
+        }
+    }
+
+    async fn run(
+        &self,
+        cmd: &Path,
+        args: &[OsString],
+        mut exec: ExecOptions<'_>,
+    ) -> Result<(String, String)> {
+        self.calls
+            .lock()
+            .expect("replay runner lock poisoned")
+            .push(RecordedCall {
+                cmd: cmd.to_path_buf(),
+                args: args
+                    .iter()
+                    .map(|a| a.to_string_lossy().to_string())
+                    .collect(),
+            });
+        let response = self
+            .responses
+            .lock()
+            .expect("replay runner lock poisoned")
+            .pop_front()
+            .ok_or_else(|| {
+                CommandError::Io(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "no replay response left",
+                ))
+            })?;
+
+        if let Some(on_stdout) = exec.on_stdout.as_mut() {
+            response.stdout.lines().for_each(on_stdout);
+        }
+        if let Some(on_stderr) = exec.on_stderr.as_mut() {
+            response.stderr.lines().for_each(on_stderr);
+        }
+        if response.exit_code == 0 {
+            Ok((response.stdout, response.stderr))
+        } else {
+            Err(CommandError::CommandFailed {
+                cmd: cmd.to_string_lossy().to_string(),
+                status: exit_status(response.exit_code),
+                stderr: response.stderr,
+            }
+            .into())
+        }
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn exit_status(code: i32) -> ExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+    // wait 状态中退出码位于高 8 位
+    ExitStatus::from_raw((code & 0xff) << 8)
+}
+
+#[cfg(target_family = "windows")]
+fn exit_status(code: i32) -> ExitStatus {
+    use std::os::windows::process::ExitStatusExt;
+    ExitStatus::from_raw(code as u32)
+}
+
+/// 逐行处理命令输出的回调
+pub type LineCallback<'a> = Box<dyn FnMut(&str) + Send + 'a>;
+
+/// 流式执行命令时的设置
+#[derive(Default)]
+pub struct ExecOptions<'a> {
+    /// 每读取到一行标准输出就调用一次，参数不包含换行符
+    pub on_stdout: Option<LineCallback<'a>>,
+    /// 每读取到一行标准错误就调用一次，参数不包含换行符
+    pub on_stderr: Option<LineCallback<'a>>,
+    /// 超过这个时间命令还没有结束，就杀死子进程并返回 `CommandError::Timeout`
+    pub timeout: Option<Duration>,
+    /// 收到 Ctrl-C 时是否把 SIGINT 转发给子进程，并返回 `CommandError::Interrupted`
+    ///
+    /// ⚠️开启后 tokio 会接管当前进程的 SIGINT 处理，Ctrl-C 不会再直接结束本进程
+    pub forward_interrupt: bool,
+}
+
+/// 子进程收到 SIGINT 后，等待它自行退出的最长时间
+const INTERRUPT_GRACE: Duration = Duration::from_secs(5);
+
+/// 异步执行指定的命令，逐行读取标准输出和标准错误并交给 `exec` 中的回调处理
+///
+/// 与 [`exec_command`] 一样，命令结束后返回完整的标准输出和标准错误。
+/// 子进程设置了 kill-on-drop，调用者丢弃这个 future 时子进程也会被杀死
+pub async fn exec_command_streaming<S: AsRef<OsStr>>(
+    cmd: impl AsRef<Path>,
+    options: Option<Vec<S>>,
+    mut exec: ExecOptions<'_>,
+) -> Result<(String, String)> {
+    let cmd_name = cmd.as_ref().to_string_lossy().to_string();
     let mut command = Command::new(cmd.as_ref().as_os_str());
     if let Some(options) = options {
         command.args(options);
     }
-    let output = command.output().await.map_err(|e| CommandError::Io(e))?;
-
-    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(CommandError::Io)?;
+
+    let mut out_reader = BufReader::new(child.stdout.take().expect("stdout is piped"));
+    let mut err_reader = BufReader::new(child.stderr.take().expect("stderr is piped"));
+    let (mut out_buf, mut err_buf) = (Vec::new(), Vec::new());
+    let (mut stdout, mut stderr) = (String::new(), String::new());
+    let (mut out_done, mut err_done) = (false, false);
+
+    let timeout = exec.timeout;
+    let deadline = async {
+        match timeout {
+            Some(timeout) => time::sleep(timeout).await,
+            None => future::pending().await,
+        }
+    };
+    tokio::pin!(deadline);
+    let forward_interrupt = exec.forward_interrupt;
+    let interrupt = async {
+        if forward_interrupt && signal::ctrl_c().await.is_ok() {
+            return;
+        }
+        future::pending::<()>().await
+    };
+    tokio::pin!(interrupt);
+
+    let status = loop {
+        tokio::select! {
+            line = read_line_lossy(&mut out_reader, &mut out_buf), if !out_done => match line.map_err(CommandError::Io)? {
+                Some(line) => {
+                    if let Some(on_stdout) = exec.on_stdout.as_mut() {
+                        on_stdout(&line);
+                    }
+                    stdout.push_str(&line);
+                    stdout.push('\n');
+                }
+                None => out_done = true,
+            },
+            line = read_line_lossy(&mut err_reader, &mut err_buf), if !err_done => match line.map_err(CommandError::Io)? {
+                Some(line) => {
+                    if let Some(on_stderr) = exec.on_stderr.as_mut() {
+                        on_stderr(&line);
+                    }
+                    stderr.push_str(&line);
+                    stderr.push('\n');
+                }
+                None => err_done = true,
+            },
+            status = child.wait(), if out_done && err_done => break status.map_err(CommandError::Io)?,
+            _ = &mut deadline => {
+                child.kill().await.map_err(CommandError::Io)?;
+                return Err(CommandError::Timeout {
+                    cmd: cmd_name,
+                    timeout: timeout.unwrap_or_default(),
+                }
+                .into());
+            }
+            _ = &mut interrupt => {
+                interrupt_child(&mut child).await?;
+                return Err(CommandError::Interrupted { cmd: cmd_name }.into());
+            }
+        }
+    };
 
-    if output.status.success() {
+    if status.success() {
         Ok((stdout, stderr))
     } else {
         Err(CommandError::CommandFailed {
-            cmd: cmd.as_ref().to_string_lossy().to_string(),
-            status: output.status,
+            cmd: cmd_name,
+            status,
             stderr,
         }
         .into())
     }
 }
 
+/// 读取一行输出，去掉行尾的换行符，不是 UTF-8 的字节替换为 U+FFFD；读到末尾时返回 `None`
+///
+/// 在 `select!` 中被取消时已读取的部分保留在 `buf` 中，下次调用会接着读完这一行
+async fn read_line_lossy<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+) -> io::Result<Option<String>> {
+    if reader.read_until(b'\n', buf).await? == 0 && buf.is_empty() {
+        return Ok(None);
+    }
+    if buf.ends_with(b"\n") {
+        buf.pop();
+        if buf.ends_with(b"\r") {
+            buf.pop();
+        }
+    }
+    let line = String::from_utf8_lossy(buf).into_owned();
+    buf.clear();
+    Ok(Some(line))
+}
+
+/// 向子进程发送 SIGINT 让它有机会做收尾工作（比如 ffmpeg 写完文件尾），超时后强制杀死
+async fn interrupt_child(child: &mut Child) -> Result<()> {
+    #[cfg(target_family = "unix")]
+    if let Some(pid) = child.id() {
+        // SAFETY: 只是向一个仍由我们持有的子进程发送信号
+        unsafe {
+            libc::kill(pid as libc::pid_t, libc::SIGINT);
+        }
+        if time::timeout(INTERRUPT_GRACE, child.wait()).await.is_ok() {
+            return Ok(());
+        }
+    }
+    child.kill().await.map_err(CommandError::Io)?;
+    Ok(())
+}
+
 /// 将给定的文件列表读取到字符串中并返回
 /// # Error
 /// * 如果文件不可读或者路径不存在、类型不是文件会返回系统IO错误
@@ -112,13 +466,13 @@ pub async fn read_multiple_file_to_string<P: AsRef<Path> + Send + 'static>(
     let file_size = files.len();
     let mut handlers: Vec<_> = Vec::with_capacity(file_size);
     for (idx, file) in files.into_iter().enumerate() {
-        handlers.push(tokio::spawn(async move { read_file_to_string(idx, file) }));
+        handlers.push(tokio::spawn(read_file_to_string(idx, file)));
     }
     let mut res = vec![Default::default(); file_size];
     for handler in handlers {
         match handler.await {
             Ok(text) => {
-                let (idx, text) = text.await?;
+                let (idx, text) = text?;
                 res[idx] = text
             }
             Err(e) => panic!("Tokio: failed to execute some subtask {}", e),
@@ -180,10 +534,10 @@ mod tests {
         let result = read_multiple_file_to_string(files).await;
 
         assert!(result.is_err());
-        match result.unwrap_err() {
-            CommonError::IoError => assert!(true),
-            _ => panic!("Expected IoError"),
-        }
+        assert!(
+            matches!(result.unwrap_err(), CommonError::IoError),
+            "Expected IoError"
+        );
     }
 
     #[tokio::test]
@@ -198,14 +552,12 @@ mod tests {
         let files = vec![file1_path.clone(), directory_path.clone()];
         let result = read_multiple_file_to_string(files).await;
 
-        assert!(result.is_err());
-        if let Err(CommonError::IoError) = result {
-            // 不同的操作系统返回的错误类型可能不同
-            // Unix-like 系统通常返回 IsADirectory，Windows 可能会返回 PermissionDenied
-            assert!(true);
-        } else {
-            panic!("Expected an IoError");
-        }
+        // 不同的操作系统返回的错误类型可能不同
+        // Unix-like 系统通常返回 IsADirectory，Windows 可能会返回 PermissionDenied
+        assert!(
+            matches!(result, Err(CommonError::IoError)),
+            "Expected an IoError"
+        );
     }
 
     #[tokio::test]
@@ -270,6 +622,18 @@ mod tests {
         assert_eq!(result, None);
     }
 
+    #[test]
+    fn test_specified_path_is_command_file() {
+        // 静态编译的 ffmpeg 常常带有版本号，直接指定文件时不要求文件名与命令名相同
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let command_path = temp_dir.path().join("ffmpeg-7.1-static");
+        fs::write(&command_path, "I am a tool").expect("Failed to create command file");
+
+        let result = find_command_path(Some(&command_path), "ffmpeg");
+
+        assert_eq!(result, Some(command_path));
+    }
+
     #[test]
     fn test_specified_path_is_invalid() {
         // 创建一个不存在的路径
@@ -389,4 +753,88 @@ mod tests {
             panic!("Expected an Io error, but got a different error.");
         }
     }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn test_exec_command_streaming_callbacks() {
+        let mut out_lines = Vec::new();
+        let mut err_lines = Vec::new();
+        let exec = ExecOptions {
+            on_stdout: Some(Box::new(|line| out_lines.push(line.to_string()))),
+            on_stderr: Some(Box::new(|line| err_lines.push(line.to_string()))),
+            ..Default::default()
+        };
+        let result = exec_command_streaming(
+            "sh",
+            Some(vec!["-c", "echo one; echo err >&2; echo two"]),
+            exec,
+        )
+        .await;
+
+        let (stdout, stderr) = result.unwrap();
+        assert_eq!(stdout, "one\ntwo\n");
+        assert_eq!(stderr, "err\n");
+        assert_eq!(out_lines, vec!["one", "two"]);
+        assert_eq!(err_lines, vec!["err"]);
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn test_exec_command_streaming_timeout() {
+        let exec = ExecOptions {
+            timeout: Some(Duration::from_millis(100)),
+            ..Default::default()
+        };
+        let start = std::time::Instant::now();
+        let result = exec_command_streaming("sh", Some(vec!["-c", "sleep 5"]), exec).await;
+
+        // 超时后子进程应该被立即杀死，而不是等它自己结束
+        assert!(start.elapsed() < Duration::from_secs(3));
+        assert!(matches!(
+            result,
+            Err(CommonError::CommandError(CommandError::Timeout { .. }))
+        ));
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn test_exec_command_streaming_failure() {
+        let result = exec_command_streaming(
+            "sh",
+            Some(vec!["-c", "echo boom >&2; exit 3"]),
+            ExecOptions::default(),
+        )
+        .await;
+
+        if let Err(CommonError::CommandError(CommandError::CommandFailed {
+            status, stderr, ..
+        })) = result
+        {
+            assert_eq!(status.code(), Some(3));
+            assert_eq!(stderr, "boom\n");
+        } else {
+            panic!("Expected CommandFailed error, but got a different error.");
+        }
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn test_exec_command_streaming_non_utf8_output() {
+        // 字幕和文件名中常见非 UTF-8 的字节，不能因此中断读取
+        let mut lines = Vec::new();
+        let exec = ExecOptions {
+            on_stdout: Some(Box::new(|line| lines.push(line.to_string()))),
+            ..Default::default()
+        };
+        let (stdout, _) = exec_command_streaming(
+            "sh",
+            Some(vec!["-c", "printf 'caf\\351\\r\\nok\\nlast'"]),
+            exec,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(lines, vec!["caf\u{FFFD}", "ok", "last"]);
+        assert_eq!(stdout, "caf\u{FFFD}\nok\nlast\n");
+    }
 }
@@ -0,0 +1,447 @@
+//! 配置文件 `config.toml` 的读取
+//!
+//! 配置文件默认位于系统配置目录下的 `blowup/config.toml`，也可以通过环境变量 `BLOWUP_CONFIG` 指定
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    result,
+    sync::LazyLock,
+    time::Duration,
+};
+
+use serde::{Deserialize, Deserializer, de};
+use thiserror::Error;
+
+use crate::torrent::{
+    client::ClientKind, output::TrackerListFormat, source::TrackerSource, tracker::TrackerScheme,
+};
+
+/// 指定配置文件位置的环境变量
+pub const CONFIG_ENV: &str = "BLOWUP_CONFIG";
+
+const CONFIG_DIR: &str = "blowup";
+const CONFIG_FILE: &str = "config.toml";
+
+/// blowup 的配置，对应配置文件 `config.toml`，所有字段都是可选的
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub ffmpeg: FfmpegConfig,
+    pub opensubtitles: OpenSubtitlesConfig,
+    pub tracker: TrackerConfig,
+    pub clients: ClientsConfig,
+    pub daemon: DaemonConfig,
+    pub ollama: OllamaConfig,
+}
+
+/// `[ffmpeg]` 配置段
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FfmpegConfig {
+    /// ffmpeg 可执行文件或其所在目录的路径，支持 `~`
+    pub ffmpeg: Option<String>,
+    /// ffprobe 可执行文件或其所在目录的路径，支持 `~`
+    pub ffprobe: Option<String>,
+}
+
+/// `[opensubtitles]` 配置段
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OpenSubtitlesConfig {
+    /// OpenSubtitles REST API 的 API key
+    pub api_key: Option<String>,
+    /// 默认搜索的字幕语言，按优先级排列，例如 `["zh-cn", "en"]`
+    pub languages: Vec<String>,
+}
+
+/// `[ollama]` 配置段
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OllamaConfig {
+    /// Ollama 服务的地址，默认为 `http://localhost:11434`
+    pub url: Option<String>,
+    /// `sub translate` 默认使用的模型
+    pub model: Option<String>,
+}
+
+/// `[tracker]` 配置段
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrackerConfig {
+    /// tracker 列表的来源，为空时使用 ngosang/trackerslist
+    pub sources: Vec<TrackerSource>,
+    /// 白名单，glob 模式，匹配主机名或者完整地址，为空时不限制
+    pub allow: Vec<String>,
+    /// 黑名单，glob 模式，优先于白名单
+    pub deny: Vec<String>,
+    /// 协议的排列顺序，默认为 udp、https、http、wss
+    pub order: Vec<TrackerScheme>,
+    /// `tracker update` 默认的输出格式
+    pub format: TrackerListFormat,
+}
+
+/// `[clients]` 配置段，每个 BT 客户端一个子段
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClientsConfig {
+    pub qbittorrent: Option<QbittorrentConfig>,
+    pub transmission: Option<TransmissionConfig>,
+    pub aria2: Option<Aria2Config>,
+}
+
+/// `[clients.qbittorrent]` 配置段
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QbittorrentConfig {
+    /// WebUI 的地址，例如 `http://localhost:8080`
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+/// `[clients.transmission]` 配置段
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransmissionConfig {
+    /// RPC 的完整地址，例如 `http://nas.local:9091/transmission/rpc`
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+/// `[clients.aria2]` 配置段
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Aria2Config {
+    /// RPC 的完整地址，例如 `http://localhost:6800/jsonrpc`
+    pub url: String,
+    /// `--rpc-secret` 设置的密钥
+    pub secret: Option<String>,
+}
+
+/// `[daemon]` 配置段，没有配置的任务不会运行
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DaemonConfig {
+    /// 保存 tracker 列表和运行状态的目录，支持 `~`，默认为系统数据目录下的 `blowup`
+    pub data_dir: Option<String>,
+    /// 日志文件，支持 `~`，默认为数据目录下的 `daemon.log`
+    pub log_file: Option<String>,
+    pub tracker: Option<TrackerJobConfig>,
+    pub push: Option<PushJobConfig>,
+    pub library: Option<LibraryJobConfig>,
+}
+
+/// `[daemon.tracker]` 配置段，定时更新数据目录中的 tracker 列表
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TrackerJobConfig {
+    /// 运行间隔，例如 `30m`、`6h`、`1d`
+    #[serde(deserialize_with = "deserialize_interval")]
+    pub interval: Duration,
+}
+
+/// `[daemon.push]` 配置段，定时将数据目录中的 tracker 列表推送到 BT 客户端
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PushJobConfig {
+    #[serde(deserialize_with = "deserialize_interval")]
+    pub interval: Duration,
+    /// 推送的客户端，为空时推送到 `[clients]` 中配置的所有客户端
+    #[serde(default)]
+    pub clients: Vec<ClientKind>,
+    /// 是否同时修改已有的种子
+    #[serde(default = "default_true")]
+    pub update_existing: bool,
+}
+
+/// `[daemon.library]` 配置段，定时扫描媒体库，为字幕匹配视频
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LibraryJobConfig {
+    #[serde(deserialize_with = "deserialize_interval")]
+    pub interval: Duration,
+    /// 媒体库目录，支持 `~`
+    pub dirs: Vec<String>,
+    /// 是否真正重命名字幕，否则只记录重命名计划
+    #[serde(default)]
+    pub apply: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// 时间间隔的上限，为 3650 天
+const MAX_INTERVAL: Duration = Duration::from_secs(3650 * 24 * 60 * 60);
+
+/// 解析 `30s`、`15m`、`6h`、`1d` 形式的时间间隔，不能为零或超过 3650 天
+pub fn parse_interval(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let unit: u64 = match s.chars().last()? {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        _ => return None,
+    };
+    let value: u64 = s[..s.len() - 1].parse().ok()?;
+    let interval = Duration::from_secs(value.checked_mul(unit)?);
+    (value > 0 && interval <= MAX_INTERVAL).then_some(interval)
+}
+
+/// 在配置文件中以 [`parse_interval`] 的格式读取时间间隔
+fn deserialize_interval<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> result::Result<Duration, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    parse_interval(&s).ok_or_else(|| de::Error::custom(ConfigError::InvalidInterval(s)))
+}
+
+impl Config {
+    /// 从配置文件中加载配置，配置文件不存在时返回默认配置
+    pub fn load() -> Result<Self> {
+        match config_path() {
+            Some(path) => Self::load_from(path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// 从指定的文件中加载配置，文件不存在时返回默认配置
+    pub fn load_from(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        match fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content).map_err(|e| ConfigError::ParseError {
+                path: path.to_path_buf(),
+                source: e,
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(ConfigError::IoError {
+                path: path.to_path_buf(),
+                source: e,
+            }),
+        }
+    }
+}
+
+/// 配置文件的路径，优先使用环境变量 `BLOWUP_CONFIG`，否则为系统配置目录下的 `blowup/config.toml`
+pub fn config_path() -> Option<PathBuf> {
+    match env::var_os(CONFIG_ENV).filter(|p| !p.is_empty()) {
+        Some(path) => Some(PathBuf::from(path)),
+        None => dirs::config_dir().map(|dir| dir.join(CONFIG_DIR).join(CONFIG_FILE)),
+    }
+}
+
+/// blowup 的缓存目录，为系统缓存目录下的 `blowup`
+pub fn cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join(CONFIG_DIR))
+}
+
+/// blowup 的数据目录，为系统数据目录下的 `blowup`
+pub fn data_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(CONFIG_DIR))
+}
+
+/// 全局的配置，只在第一次使用时加载，加载失败时打印警告并使用默认配置
+pub fn config() -> &'static Config {
+    static CONFIG: LazyLock<Config> = LazyLock::new(|| {
+        Config::load().unwrap_or_else(|e| {
+            eprintln!("warning: {}, falling back to the default config", e);
+            Config::default()
+        })
+    });
+    &CONFIG
+}
+
+/// 展开路径开头的 `~`
+pub fn expand_path(path: &str) -> PathBuf {
+    PathBuf::from(shellexpand::tilde(path).as_ref())
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Failed to read the config file {path:?}: {source}")]
+    IoError { path: PathBuf, source: io::Error },
+    #[error("Failed to parse the config file {path:?}: {source}")]
+    ParseError {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error(
+        "Invalid interval {0:?}, expected a number followed by s, m, h or d of at most 3650 days, e.g. 6h"
+    )]
+    InvalidInterval(String),
+}
+
+pub type Result<T> = result::Result<T, ConfigError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::torrent::source::GitHubFile;
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(parse_interval("30s"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_interval("15m"), Some(Duration::from_secs(15 * 60)));
+        assert_eq!(parse_interval(" 6h "), Some(Duration::from_secs(6 * 3600)));
+        assert_eq!(parse_interval("1d"), Some(Duration::from_secs(86400)));
+        assert_eq!(parse_interval("3650d"), Some(MAX_INTERVAL));
+        for s in [
+            "",
+            "6",
+            "h",
+            "0h",
+            "-1h",
+            "1.5h",
+            "1w",
+            "3651d",
+            "18446744073709551615d",
+        ] {
+            assert_eq!(parse_interval(s), None, "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config: Config = toml::from_str(
+            r#"
+            [ffmpeg]
+            ffmpeg = "~/opt/ffmpeg/ffmpeg"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.ffmpeg.ffmpeg.as_deref(), Some("~/opt/ffmpeg/ffmpeg"));
+        assert_eq!(config.ffmpeg.ffprobe, None);
+
+        assert_eq!(toml::from_str::<Config>("").unwrap(), Config::default());
+        assert!(toml::from_str::<Config>("[ffmpeg]\nfmpeg = \"/usr/bin\"").is_err());
+
+        let config: Config = toml::from_str(
+            r#"
+            [opensubtitles]
+            api_key = "secret"
+            languages = ["zh-cn", "en"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.opensubtitles.api_key.as_deref(), Some("secret"));
+        assert_eq!(config.opensubtitles.languages, vec!["zh-cn", "en"]);
+
+        let config: Config = toml::from_str(
+            r#"
+            [ollama]
+            url = "http://gpu.local:11434"
+            model = "qwen2.5:7b"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.ollama.url.as_deref(), Some("http://gpu.local:11434"));
+        assert_eq!(config.ollama.model.as_deref(), Some("qwen2.5:7b"));
+
+        let config: Config = toml::from_str(
+            r#"
+            [[tracker.sources]]
+            github = { repo = "ngosang/trackerslist", path = "trackers_best.txt" }
+
+            [[tracker.sources]]
+            url = "https://newtrackon.com/api/stable"
+
+            [[tracker.sources]]
+            file = "~/trackers.txt"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.tracker.sources,
+            vec![
+                TrackerSource::GitHub(GitHubFile {
+                    repo: "ngosang/trackerslist".to_string(),
+                    path: "trackers_best.txt".to_string(),
+                    branch: None,
+                }),
+                TrackerSource::Url("https://newtrackon.com/api/stable".to_string()),
+                TrackerSource::File("~/trackers.txt".to_string()),
+            ]
+        );
+        assert!(toml::from_str::<Config>("[[tracker.sources]]\nftp = \"x\"").is_err());
+
+        let config: Config = toml::from_str(
+            r#"
+            [tracker]
+            deny = ["*.bad.example"]
+            order = ["http", "udp"]
+            format = "aria2"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.tracker.deny, vec!["*.bad.example"]);
+        assert_eq!(
+            config.tracker.order,
+            vec![TrackerScheme::Http, TrackerScheme::Udp]
+        );
+        assert_eq!(config.tracker.format, TrackerListFormat::Aria2);
+        assert_eq!(config.clients, ClientsConfig::default());
+
+        let config: Config = toml::from_str(
+            r#"
+            [clients.qbittorrent]
+            url = "http://localhost:8080"
+            username = "admin"
+            "#,
+        )
+        .unwrap();
+        let qbittorrent = config.clients.qbittorrent.unwrap();
+        assert_eq!(qbittorrent.url, "http://localhost:8080");
+        assert_eq!(qbittorrent.password, None);
+        assert!(toml::from_str::<Config>("[clients.qbittorrent]\nusername = \"admin\"").is_err());
+
+        let config: Config = toml::from_str(
+            r#"
+            [clients.transmission]
+            url = "http://nas.local:9091/transmission/rpc"
+
+            [clients.aria2]
+            url = "http://localhost:6800/jsonrpc"
+            secret = "token"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.clients.qbittorrent, None);
+        assert_eq!(
+            config.clients.transmission.unwrap().url,
+            "http://nas.local:9091/transmission/rpc"
+        );
+        assert_eq!(
+            config.clients.aria2.unwrap().secret.as_deref(),
+            Some("token")
+        );
+    }
+
+    #[test]
+    fn test_load_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+
+        assert_eq!(Config::load_from(&path).unwrap(), Config::default());
+
+        fs::write(&path, "[ffmpeg]\nffprobe = \"/opt/ffmpeg\"\n").unwrap();
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.ffmpeg.ffprobe.as_deref(), Some("/opt/ffmpeg"));
+
+        fs::write(&path, "[ffmpeg\n").unwrap();
+        assert!(matches!(
+            Config::load_from(&path),
+            Err(ConfigError::ParseError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_expand_path() {
+        assert_eq!(expand_path("/opt/ffmpeg"), PathBuf::from("/opt/ffmpeg"));
+        if let Some(home) = dirs::home_dir() {
+            assert_eq!(expand_path("~/opt"), home.join("opt"));
+        }
+    }
+}
@@ -0,0 +1,389 @@
+//! daemon module
+//!
+//! 在后台常驻运行，按照配置的间隔定时更新 tracker 列表、推送到 BT 客户端以及扫描媒体库。
+//! tracker 列表、运行状态和日志都保存在数据目录中，与启动时的工作目录无关；
+//! 收到 SIGTERM 或 SIGINT 时取消正在运行的任务并退出
+
+pub mod state;
+
+use std::{
+    fmt::Display,
+    fs::{self, File},
+    future::Future,
+    io,
+    path::{Path, PathBuf},
+    result,
+    time::Duration,
+};
+
+use chrono::{DateTime, Local};
+use thiserror::Error;
+use tokio::{
+    signal::unix::{SignalKind, signal},
+    time,
+};
+
+use crate::{
+    config::{Config, data_dir, expand_path},
+    sub::match_library,
+    torrent::{
+        DEFAULT_TRACKER_FILE, TorrentError, cache::TrackerCache, client::push_trackers,
+        download_newest_tracker_with, output::TrackerOutput, source::configured_sources,
+        tracker::TrackerPolicy,
+    },
+};
+use state::{DaemonState, STATE_FILE};
+
+/// 数据目录中默认的日志文件名称
+pub const LOG_FILE: &str = "daemon.log";
+
+#[derive(Debug, Error)]
+pub enum DaemonError {
+    #[error(
+        "No daemon jobs are configured, add a [daemon.tracker], [daemon.push] or [daemon.library] section to the config file"
+    )]
+    NoJobs,
+    #[error("Cannot determine the data directory, set data_dir under [daemon] in the config file")]
+    NoDataDir,
+    #[error("IO error: {0}")]
+    IoError(#[from] io::Error),
+    #[error("{0}")]
+    Torrent(#[from] TorrentError),
+}
+
+pub type Result<T> = result::Result<T, DaemonError>;
+
+/// 守护进程定时运行的任务
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Job {
+    /// 更新数据目录中的 tracker 列表
+    Tracker,
+    /// 将数据目录中的 tracker 列表推送到 BT 客户端
+    Push,
+    /// 为媒体库中的字幕匹配视频
+    Library,
+}
+
+impl Job {
+    /// 日志和状态文件中使用的名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Tracker => "tracker",
+            Self::Push => "push",
+            Self::Library => "library",
+        }
+    }
+}
+
+/// 打印带时间的日志，守护进程运行时标准错误输出被重定向到日志文件
+fn log(message: impl Display) {
+    eprintln!(
+        "[{}] {}",
+        Local::now().format("%Y-%m-%d %H:%M:%S %z"),
+        message
+    );
+}
+
+/// 按照配置定时运行任务的守护进程
+pub struct Daemon {
+    config: Config,
+    data_dir: PathBuf,
+    cache_file: Option<PathBuf>,
+    jobs: Vec<(Job, Duration)>,
+    state: DaemonState,
+}
+
+impl Daemon {
+    /// 使用配置中的数据目录（默认为系统数据目录下的 `blowup`）和默认的 tracker 缓存
+    pub fn new(config: Config) -> Result<Self> {
+        let data_dir = match &config.daemon.data_dir {
+            Some(dir) => expand_path(dir),
+            None => data_dir().ok_or(DaemonError::NoDataDir)?,
+        };
+        Self::with_paths(config, data_dir, TrackerCache::default_path())
+    }
+
+    /// 使用指定的数据目录和 tracker 缓存文件，读取数据目录中已有的运行状态
+    pub fn with_paths(
+        config: Config,
+        data_dir: PathBuf,
+        cache_file: Option<PathBuf>,
+    ) -> Result<Self> {
+        let daemon = &config.daemon;
+        let jobs: Vec<(Job, Duration)> = [
+            daemon.tracker.as_ref().map(|c| (Job::Tracker, c.interval)),
+            daemon.push.as_ref().map(|c| (Job::Push, c.interval)),
+            daemon.library.as_ref().map(|c| (Job::Library, c.interval)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if jobs.is_empty() {
+            return Err(DaemonError::NoJobs);
+        }
+        let state = DaemonState::load(&data_dir.join(STATE_FILE));
+        Ok(Self {
+            config,
+            data_dir,
+            cache_file,
+            jobs,
+            state,
+        })
+    }
+
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
+    /// tracker 任务写入、推送任务读取的 tracker 列表
+    pub fn tracker_file(&self) -> PathBuf {
+        self.data_dir.join(DEFAULT_TRACKER_FILE)
+    }
+
+    /// 日志文件，默认为数据目录下的 `daemon.log`
+    pub fn log_file(&self) -> PathBuf {
+        match &self.config.daemon.log_file {
+            Some(path) => expand_path(path),
+            None => self.data_dir.join(LOG_FILE),
+        }
+    }
+
+    pub fn state(&self) -> &DaemonState {
+        &self.state
+    }
+
+    /// 最早需要运行的任务以及运行时间，从未运行过的任务立即运行
+    pub fn next_run(&self) -> Option<(Job, DateTime<Local>)> {
+        let now = Local::now();
+        self.jobs
+            .iter()
+            .map(|&(job, interval)| {
+                let at = self.state.next_run(job.name(), interval).unwrap_or(now);
+                (job, at)
+            })
+            .min_by_key(|&(_, at)| at)
+    }
+
+    async fn execute(&self, job: Job) -> Result<()> {
+        let daemon = &self.config.daemon;
+        match job {
+            Job::Tracker => {
+                let tracker = &self.config.tracker;
+                let policy = TrackerPolicy::new(&tracker.allow, &tracker.deny, &tracker.order)?;
+                fs::create_dir_all(&self.data_dir)?;
+                download_newest_tracker_with(
+                    &configured_sources(&tracker.sources),
+                    &policy,
+                    &TrackerOutput::File(self.tracker_file()),
+                    tracker.format,
+                    self.cache_file.as_deref(),
+                )
+                .await?
+            }
+            Job::Push => {
+                let push = daemon.push.as_ref().expect("push job is configured");
+                push_trackers(
+                    &self.config.clients,
+                    &push.clients,
+                    self.tracker_file(),
+                    push.update_existing,
+                )
+                .await?
+            }
+            Job::Library => {
+                let library = daemon.library.as_ref().expect("library job is configured");
+                // 一个目录失败不影响其它目录，返回最后一个错误
+                let mut last_error = None;
+                for dir in &library.dirs {
+                    let dir = expand_path(dir);
+                    let apply = library.apply;
+                    log(format!("scanning {}", dir.display()));
+                    let result = tokio::task::spawn_blocking(move || match_library(dir, apply))
+                        .await
+                        .unwrap_or_else(|e| Err(io::Error::other(e)));
+                    if let Err(e) = result {
+                        log(format!("library: {}", e));
+                        last_error = Some(e);
+                    }
+                }
+                if let Some(e) = last_error {
+                    return Err(e.into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 运行一次任务，记录结果并写入状态文件，任务失败只记录错误
+    pub async fn run_job(&mut self, job: Job) {
+        let started = Local::now();
+        log(format!("{}: started", job.name()));
+        let error = match self.execute(job).await {
+            Ok(()) => {
+                log(format!("{}: finished", job.name()));
+                None
+            }
+            Err(e) => {
+                log(format!("{}: failed: {}", job.name(), e));
+                Some(e.to_string())
+            }
+        };
+        self.state.record(job.name(), started, error);
+        let path = self.data_dir.join(STATE_FILE);
+        if let Err(e) = self.state.store(&path) {
+            log(format!(
+                "warning: failed to write the daemon state {:?}: {}",
+                path, e
+            ));
+        }
+    }
+
+    /// 按顺序运行所有已经到时间的任务，返回运行的数量
+    pub async fn run_pending(&mut self) -> usize {
+        let now = Local::now();
+        let due: Vec<Job> = self
+            .jobs
+            .iter()
+            .filter(|&&(job, interval)| {
+                self.state
+                    .next_run(job.name(), interval)
+                    .is_none_or(|at| at <= now)
+            })
+            .map(|&(job, _)| job)
+            .collect();
+        for &job in &due {
+            self.run_job(job).await;
+        }
+        due.len()
+    }
+
+    /// 不管上一次运行的时间，按顺序运行所有任务一次
+    pub async fn run_all(&mut self) {
+        let jobs: Vec<Job> = self.jobs.iter().map(|&(job, _)| job).collect();
+        for job in jobs {
+            self.run_job(job).await;
+        }
+    }
+
+    /// 持续运行到收到 SIGTERM 或 SIGINT，正在运行的任务会被取消
+    pub async fn run(&mut self) -> Result<()> {
+        until_shutdown(async {
+            loop {
+                self.run_pending().await;
+                let (job, at) = self.next_run().expect("at least one job is configured");
+                log(format!("next run: {} at {}", job.name(), at.to_rfc3339()));
+                let wait = (at - Local::now()).to_std().unwrap_or_default();
+                time::sleep(wait).await;
+            }
+        })
+        .await
+    }
+}
+
+/// 运行 `fut` 直到它完成或者收到 SIGTERM、SIGINT，收到信号时丢弃 `fut`，
+/// 正在运行的任务随之取消，任务启动的子进程也会被杀死
+async fn until_shutdown<F: Future>(fut: F) -> Result<()> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    tokio::select! {
+        biased;
+        _ = terminate.recv() => log("received SIGTERM, shutting down"),
+        _ = interrupt.recv() => log("received SIGINT, shutting down"),
+        _ = fut => (),
+    }
+    Ok(())
+}
+
+/// 将标准输出和标准错误输出重定向到日志文件，任务本身的输出也会写入日志
+fn redirect_output(file: &File) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    for fd in [libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+        // SAFETY: 只是复制一个仍然打开的文件描述符
+        if unsafe { libc::dup2(file.as_raw_fd(), fd) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// `blowup daemon` 的入口：准备数据目录，将输出写入日志文件后开始运行
+///
+/// `once` 时所有任务只运行一次就退出，适合仍然使用 cron 的场景
+pub async fn run_daemon(config: Config, once: bool) -> Result<()> {
+    let mut daemon = Daemon::new(config)?;
+    fs::create_dir_all(daemon.data_dir())?;
+    let log_file = daemon.log_file();
+    if let Some(dir) = log_file.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let file = File::options().create(true).append(true).open(&log_file)?;
+    eprintln!("logging to {}", log_file.display());
+    redirect_output(&file)?;
+
+    log(format!(
+        "daemon started, data directory {}",
+        daemon.data_dir().display()
+    ));
+    if once {
+        until_shutdown(daemon.run_all()).await?;
+    } else {
+        daemon.run().await?;
+    }
+    log("daemon stopped");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_daemon_jobs_from_config() {
+        let config: Config = toml::from_str(
+            r#"
+            [daemon.tracker]
+            interval = "6h"
+
+            [daemon.library]
+            interval = "1d"
+            dirs = ["~/Movies"]
+            "#,
+        )
+        .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let daemon = Daemon::with_paths(config, dir.path().to_path_buf(), None).unwrap();
+        assert_eq!(
+            daemon.jobs,
+            vec![
+                (Job::Tracker, Duration::from_secs(6 * 3600)),
+                (Job::Library, Duration::from_secs(86400)),
+            ]
+        );
+        assert_eq!(daemon.tracker_file(), dir.path().join(DEFAULT_TRACKER_FILE));
+        assert_eq!(daemon.log_file(), dir.path().join(LOG_FILE));
+        assert_eq!(daemon.next_run().unwrap().0, Job::Tracker);
+
+        assert!(toml::from_str::<Config>("[daemon.tracker]\ninterval = \"6\"").is_err());
+        assert!(matches!(
+            Daemon::with_paths(Config::default(), dir.path().to_path_buf(), None),
+            Err(DaemonError::NoJobs)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_cancels_running_job() {
+        let start = std::time::Instant::now();
+        tokio::spawn(async {
+            time::sleep(Duration::from_millis(100)).await;
+            // SAFETY: 只是向当前进程发送信号，until_shutdown 已经接管了 SIGTERM
+            unsafe {
+                libc::kill(libc::getpid(), libc::SIGTERM);
+            }
+        });
+        until_shutdown(time::sleep(Duration::from_secs(60)))
+            .await
+            .unwrap();
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+}
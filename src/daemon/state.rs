@@ -0,0 +1,116 @@
+use std::{collections::BTreeMap, fs, io, path::Path, time::Duration};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::torrent::output::write_atomic;
+
+/// 数据目录中运行状态文件的名称
+pub const STATE_FILE: &str = "daemon.json";
+
+/// 每个任务最近一次运行的记录，重启后据此计算下一次运行的时间
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DaemonState {
+    /// 以任务名称为键
+    #[serde(default)]
+    pub jobs: BTreeMap<String, JobState>,
+}
+
+/// 单个任务最近一次运行的结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobState {
+    /// RFC 3339 格式的开始时间
+    pub last_run: String,
+    /// 最近一次成功的开始时间
+    pub last_success: Option<String>,
+    /// 最近一次运行失败时的错误，成功时为空
+    pub last_error: Option<String>,
+}
+
+impl DaemonState {
+    /// 读取状态文件，文件不存在或者损坏时返回空的状态
+    pub fn load(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                eprintln!("warning: ignoring corrupt daemon state {:?}: {}", path, e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// 通过 [`write_atomic`] 写入状态文件，避免中断时留下不完整的文件
+    pub fn store(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        write_atomic(path, serde_json::to_string_pretty(self)?.as_bytes())
+    }
+
+    /// 记录任务在 `started` 开始的一次运行，`error` 为空表示成功
+    pub fn record(&mut self, job: &str, started: DateTime<Local>, error: Option<String>) {
+        let started = started.to_rfc3339();
+        let last_success = match error {
+            None => Some(started.clone()),
+            Some(_) => self.jobs.get(job).and_then(|s| s.last_success.clone()),
+        };
+        self.jobs.insert(
+            job.to_string(),
+            JobState {
+                last_run: started,
+                last_success,
+                last_error: error,
+            },
+        );
+    }
+
+    /// 任务下一次运行的时间，从未运行过或者记录无法解析、超出时间范围时立即运行
+    pub fn next_run(&self, job: &str, interval: Duration) -> Option<DateTime<Local>> {
+        let last_run = DateTime::parse_from_rfc3339(&self.jobs.get(job)?.last_run).ok()?;
+        let interval = chrono::Duration::from_std(interval).ok()?;
+        last_run.with_timezone(&Local).checked_add_signed(interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_next_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join(STATE_FILE);
+        assert_eq!(DaemonState::load(&path), DaemonState::default());
+
+        let mut state = DaemonState::default();
+        let interval = Duration::from_secs(3600);
+        assert_eq!(state.next_run("tracker", interval), None);
+
+        let started = DateTime::parse_from_rfc3339("2026-10-18T12:00:00+08:00")
+            .unwrap()
+            .with_timezone(&Local);
+        state.record("tracker", started, None);
+        state.record(
+            "tracker",
+            started + chrono::Duration::hours(1),
+            Some("offline".into()),
+        );
+        let job = &state.jobs["tracker"];
+        assert_eq!(
+            job.last_success.as_deref(),
+            Some(started.to_rfc3339().as_str())
+        );
+        assert_eq!(job.last_error.as_deref(), Some("offline"));
+        assert_eq!(
+            state.next_run("tracker", interval),
+            Some(started + chrono::Duration::hours(2))
+        );
+
+        state.store(&path).unwrap();
+        assert_eq!(DaemonState::load(&path), state);
+        assert!(!path.with_extension("json.tmp").exists());
+
+        fs::write(&path, "{ not json").unwrap();
+        assert_eq!(DaemonState::load(&path), DaemonState::default());
+    }
+}
@@ -1,8 +1,24 @@
-use std::{ffi::OsStr, path::Path, result};
+pub mod doctor;
+pub mod probe;
+pub mod scene;
+
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    fmt,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    result,
+    time::Duration,
+};
 
 use thiserror::Error;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 
-use crate::common::{CommandError, CommonError, exec_command, find_command_path};
+use crate::{
+    common::{CommonError, ExecOptions, ProcessRunner, SystemRunner},
+    config::{config, expand_path},
+};
 
 pub enum FfmpegTool {
     Ffmpeg,
@@ -15,18 +31,273 @@ impl FfmpegTool {
         path: Option<impl AsRef<Path>>,
         options: Option<Vec<impl AsRef<OsStr>>>,
     ) -> Result<(String, String)> {
-        let exec_path = match self {
-            FfmpegTool::Ffmpeg => {
-                find_command_path(path, FFMPEG_CLI).ok_or(FfmpegError::FfmpegNotFound)?
+        self.exec_with_runner(&SystemRunner, path, options).await
+    }
+
+    /// 与 [`FfmpegTool::exec_with_options`] 相同，但是通过指定的 `runner` 执行命令
+    pub async fn exec_with_runner<R: ProcessRunner>(
+        &self,
+        runner: &R,
+        path: Option<impl AsRef<Path>>,
+        options: Option<Vec<impl AsRef<OsStr>>>,
+    ) -> Result<(String, String)> {
+        self.exec_streaming(runner, path, options, ExecOptions::default())
+            .await
+    }
+
+    /// 流式执行命令，`exec` 中可以设置逐行回调、超时以及是否转发 Ctrl-C
+    pub async fn exec_streaming<R: ProcessRunner>(
+        &self,
+        runner: &R,
+        path: Option<impl AsRef<Path>>,
+        options: Option<Vec<impl AsRef<OsStr>>>,
+        exec: ExecOptions<'_>,
+    ) -> Result<(String, String)> {
+        let exec_path = self.locate_with(runner, path)?;
+        let args: Vec<OsString> = options
+            .into_iter()
+            .flatten()
+            .map(|o| o.as_ref().to_os_string())
+            .collect();
+        runner
+            .run(&exec_path, &args, exec)
+            .await
+            .map_err(|e| e.into())
+    }
+
+    /// 以 `-progress pipe:1` 的方式执行命令，每解析出一个完整的进度块就发送到 `progress` 中
+    ///
+    /// `-nostats -progress pipe:1` 会被加在 `options` 之前，标准输出被当作进度流解析，
+    /// 命令结束后返回标准错误的内容。`forward_interrupt` 见 [`ExecOptions::forward_interrupt`]，
+    /// 只有需要 ffmpeg 收尾输出文件的长时间转码才需要开启
+    pub async fn exec_with_progress<R: ProcessRunner>(
+        &self,
+        runner: &R,
+        path: Option<impl AsRef<Path>>,
+        options: Vec<impl AsRef<OsStr>>,
+        progress: UnboundedSender<FfmpegProgress>,
+        forward_interrupt: bool,
+    ) -> Result<String> {
+        let mut args: Vec<OsString> = ["-nostats", "-progress", "pipe:1"]
+            .into_iter()
+            .map(OsString::from)
+            .collect();
+        args.extend(options.iter().map(|o| o.as_ref().to_os_string()));
+
+        let mut parser = ProgressParser::default();
+        let exec = ExecOptions {
+            on_stdout: Some(Box::new(move |line| {
+                if let Some(p) = parser.push_line(line) {
+                    // 接收端被丢弃只意味着调用者不再关心进度
+                    let _ = progress.send(p);
+                }
+            })),
+            forward_interrupt,
+            ..Default::default()
+        };
+        let (_, stderr) = self.exec_streaming(runner, path, Some(args), exec).await?;
+        Ok(stderr)
+    }
+
+    /// 查找命令的完整路径
+    ///
+    /// 没有指定 `path` 时依次使用环境变量、配置文件中的路径，都没有设置时在 `PATH` 中查找
+    pub fn locate_with<R: ProcessRunner>(
+        &self,
+        runner: &R,
+        path: Option<impl AsRef<Path>>,
+    ) -> Result<PathBuf> {
+        let path = match path {
+            Some(path) => Some(path.as_ref().to_path_buf()),
+            None => self.override_path().map(|(path, _)| path),
+        };
+        runner
+            .locate(path.as_deref(), self.command())
+            .ok_or(FfmpegError::FfmpegNotFound)
+    }
+
+    /// 通过环境变量或配置文件指定的路径，以及它的来源
+    pub fn override_path(&self) -> Option<(PathBuf, PathSource)> {
+        let ffmpeg = &config().ffmpeg;
+        let configured = match self {
+            FfmpegTool::Ffmpeg => ffmpeg.ffmpeg.as_deref(),
+            FfmpegTool::Ffprobe => ffmpeg.ffprobe.as_deref(),
+        };
+        resolve_override(env::var(self.env_var()).ok(), configured)
+    }
+
+    /// 命令的名称
+    pub fn command(&self) -> &'static str {
+        match self {
+            FfmpegTool::Ffmpeg => FFMPEG_CLI,
+            FfmpegTool::Ffprobe => FFPROBE_CLI,
+        }
+    }
+
+    /// 用于覆盖命令路径的环境变量
+    pub fn env_var(&self) -> &'static str {
+        match self {
+            FfmpegTool::Ffmpeg => FFMPEG_ENV,
+            FfmpegTool::Ffprobe => FFPROBE_ENV,
+        }
+    }
+}
+
+/// 命令路径的来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathSource {
+    /// 环境变量 `BLOWUP_FFMPEG` / `BLOWUP_FFPROBE`
+    Env,
+    /// 配置文件中的 `[ffmpeg]` 配置段
+    Config,
+}
+
+impl fmt::Display for PathSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathSource::Env => write!(f, "env"),
+            PathSource::Config => write!(f, "config"),
+        }
+    }
+}
+
+/// 环境变量优先于配置文件，空值视为没有设置
+fn resolve_override(
+    env: Option<String>,
+    configured: Option<&str>,
+) -> Option<(PathBuf, PathSource)> {
+    let env = env
+        .filter(|p| !p.trim().is_empty())
+        .map(|p| (expand_path(p.trim()), PathSource::Env));
+    env.or_else(|| {
+        configured
+            .filter(|p| !p.trim().is_empty())
+            .map(|p| (expand_path(p.trim()), PathSource::Config))
+    })
+}
+
+/// 查询媒体文件的总时长，ffprobe 没有报告时长时返回 `None`
+pub async fn probe_duration(file: impl AsRef<Path>) -> Result<Option<Duration>> {
+    let options = vec![
+        OsStr::new("-v"),
+        OsStr::new("error"),
+        OsStr::new("-show_entries"),
+        OsStr::new("format=duration"),
+        OsStr::new("-of"),
+        OsStr::new("default=noprint_wrappers=1:nokey=1"),
+        file.as_ref().as_os_str(),
+    ];
+    let (stdout, _) = FfmpegTool::Ffprobe
+        .exec_with_options(None::<&'static str>, Some(options))
+        .await?;
+    Ok(stdout
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+        .map(Duration::from_secs_f64))
+}
+
+/// 在标准错误的同一行上持续打印 `label` 任务的进度，直到发送端关闭
+pub async fn report_progress(
+    label: &str,
+    total: Option<Duration>,
+    mut rx: UnboundedReceiver<FfmpegProgress>,
+) {
+    let mut stderr = io::stderr();
+    while let Some(progress) = rx.recv().await {
+        let done = progress
+            .out_time
+            .map(|t| format!("{:.1}s", t.as_secs_f64()))
+            .unwrap_or_else(|| "N/A".to_string());
+        let percent = total
+            .and_then(|total| progress.percent(total))
+            .map(|p| format!("{:5.1}%", p))
+            .unwrap_or_else(|| "  N/A".to_string());
+        let speed = progress
+            .speed
+            .map(|s| format!("{:.2}x", s))
+            .unwrap_or_else(|| "N/A".to_string());
+        let _ = write!(
+            stderr,
+            "\r{} {} time={} speed={}  ",
+            label, percent, done, speed
+        );
+        if progress.finished {
+            let _ = writeln!(stderr);
+        }
+        let _ = stderr.flush();
+    }
+}
+
+/// ffmpeg `-progress` 输出的一个进度块
+///
+/// ffmpeg 会周期性地输出若干 `key=value` 行，并以 `progress=continue` 或 `progress=end` 结束一个块
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FfmpegProgress {
+    /// 已经输出的帧数
+    pub frame: Option<u64>,
+    /// 当前的编码帧率
+    pub fps: Option<f64>,
+    /// 已经处理到的输出时间点
+    pub out_time: Option<Duration>,
+    /// 相对于实时播放的处理速度，如 `2.5x` 记为 2.5
+    pub speed: Option<f64>,
+    /// 是否是最后一个进度块
+    pub finished: bool,
+}
+
+impl FfmpegProgress {
+    /// 根据总时长计算完成的百分比，范围为 0~100
+    pub fn percent(&self, total: Duration) -> Option<f64> {
+        if total.is_zero() {
+            return None;
+        }
+        if self.finished {
+            return Some(100.0);
+        }
+        self.out_time
+            .map(|t| (t.as_secs_f64() / total.as_secs_f64() * 100.0).min(100.0))
+    }
+}
+
+/// 逐行解析 ffmpeg `-progress` 的输出
+#[derive(Debug, Default)]
+pub struct ProgressParser {
+    current: FfmpegProgress,
+}
+
+impl ProgressParser {
+    /// 输入一行输出，当一个进度块结束时返回完整的进度
+    pub fn push_line(&mut self, line: &str) -> Option<FfmpegProgress> {
+        let (key, value) = line.trim().split_once('=')?;
+        let value = value.trim();
+        match key {
+            "frame" => self.current.frame = value.parse().ok(),
+            "fps" => self.current.fps = value.parse().ok(),
+            // 老版本的 ffmpeg 中 out_time_ms 实际上也是微秒
+            "out_time_us" | "out_time_ms" => {
+                if let Ok(us) = value.parse::<u64>() {
+                    self.current.out_time = Some(Duration::from_micros(us));
+                }
             }
-            FfmpegTool::Ffprobe => {
-                find_command_path(path, FFPROBE_CLI).ok_or(FfmpegError::FfmpegNotFound)?
+            "speed" => self.current.speed = value.trim_end_matches('x').trim().parse().ok(),
+            "progress" => {
+                let mut progress = std::mem::take(&mut self.current);
+                progress.finished = value == "end";
+                return Some(progress);
             }
-        };
-        exec_command(exec_path, options).await.map_err(|e| e.into())
+            _ => (),
+        }
+        None
     }
 }
 
+/// 覆盖 ffmpeg 路径的环境变量
+pub const FFMPEG_ENV: &str = "BLOWUP_FFMPEG";
+/// 覆盖 ffprobe 路径的环境变量
+pub const FFPROBE_ENV: &str = "BLOWUP_FFPROBE";
+
 #[cfg(target_family = "unix")]
 const FFMPEG_CLI: &str = "ffmpeg";
 #[cfg(target_family = "unix")]
@@ -42,6 +313,8 @@ pub enum FfmpegError {
     FfmpegNotFound,
     #[error(transparent)]
     CmdExecError(CommonError),
+    #[error("Failed to parse the ffprobe output: {0}")]
+    ProbeParseError(#[from] serde_json::Error),
 }
 
 impl From<CommonError> for FfmpegError {
@@ -51,3 +324,73 @@ impl From<CommonError> for FfmpegError {
 }
 
 pub type Result<T> = result::Result<T, FfmpegError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(parser: &mut ProgressParser, block: &str) -> Vec<FfmpegProgress> {
+        block.lines().filter_map(|l| parser.push_line(l)).collect()
+    }
+
+    #[test]
+    fn test_parse_progress_blocks() {
+        let mut parser = ProgressParser::default();
+        let out = feed(
+            &mut parser,
+            "frame=120\nfps=29.97\nout_time_us=4004000\nout_time=00:00:04.004000\nspeed=2.01x\nprogress=continue\n\
+             frame=240\nfps=30.00\nout_time_us=8008000\nspeed=N/A\nprogress=end\n",
+        );
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0].frame, Some(120));
+        assert_eq!(out[0].fps, Some(29.97));
+        assert_eq!(out[0].out_time, Some(Duration::from_micros(4_004_000)));
+        assert_eq!(out[0].speed, Some(2.01));
+        assert!(!out[0].finished);
+        // 上一个块的字段不应该带到下一个块中
+        assert_eq!(out[1].frame, Some(240));
+        assert_eq!(out[1].speed, None);
+        assert!(out[1].finished);
+    }
+
+    #[test]
+    fn test_parse_progress_ignores_garbage() {
+        let mut parser = ProgressParser::default();
+        assert!(parser.push_line("").is_none());
+        assert!(parser.push_line("not a progress line").is_none());
+        assert!(parser.push_line("out_time_us=N/A").is_none());
+        let progress = parser.push_line("progress=continue").unwrap();
+        assert_eq!(progress.out_time, None);
+    }
+
+    #[test]
+    fn test_resolve_override() {
+        assert_eq!(resolve_override(None, None), None);
+        assert_eq!(
+            resolve_override(Some("/opt/ffmpeg/bin".into()), Some("/usr/local/bin")),
+            Some((PathBuf::from("/opt/ffmpeg/bin"), PathSource::Env))
+        );
+        assert_eq!(
+            resolve_override(Some("  ".into()), Some("/usr/local/bin/ffmpeg")),
+            Some((PathBuf::from("/usr/local/bin/ffmpeg"), PathSource::Config))
+        );
+        assert_eq!(resolve_override(None, Some("")), None);
+    }
+
+    #[test]
+    fn test_progress_percent() {
+        let total = Duration::from_secs(100);
+        let mut progress = FfmpegProgress {
+            out_time: Some(Duration::from_secs(25)),
+            ..Default::default()
+        };
+        assert_eq!(progress.percent(total), Some(25.0));
+        assert_eq!(progress.percent(Duration::ZERO), None);
+        progress.out_time = Some(Duration::from_secs(120));
+        assert_eq!(progress.percent(total), Some(100.0));
+        progress.out_time = None;
+        assert_eq!(progress.percent(total), None);
+        progress.finished = true;
+        assert_eq!(progress.percent(total), Some(100.0));
+    }
+}
@@ -0,0 +1,418 @@
+//! `blowup doctor`：检查 ffmpeg、ffprobe 的位置和版本，以及各个命令依赖的编码器、封装格式和过滤器
+
+use std::{collections::BTreeSet, fmt, path::PathBuf};
+
+use crate::{
+    common::{ProcessRunner, SystemRunner},
+    config::config_path,
+};
+
+use super::{FfmpegTool, PathSource};
+
+/// ffmpeg 能力的种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CapabilityKind {
+    Encoder,
+    /// 输出的封装格式，流复制（`-c copy`）时只需要封装格式而不需要编码器
+    Muxer,
+    Filter,
+}
+
+impl fmt::Display for CapabilityKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CapabilityKind::Encoder => write!(f, "encoder"),
+            CapabilityKind::Muxer => write!(f, "muxer"),
+            CapabilityKind::Filter => write!(f, "filter"),
+        }
+    }
+}
+
+/// blowup 的某个功能所依赖的 ffmpeg 能力
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capability {
+    pub kind: CapabilityKind,
+    pub name: &'static str,
+    /// 依赖这项能力的命令
+    pub used_by: &'static str,
+}
+
+/// 所有需要检查的能力
+pub const CAPABILITIES: &[Capability] = &[
+    Capability {
+        kind: CapabilityKind::Filter,
+        name: "subtitles",
+        used_by: "sub burn",
+    },
+    Capability {
+        kind: CapabilityKind::Filter,
+        name: "ass",
+        used_by: "sub burn (ass/ssa)",
+    },
+    Capability {
+        kind: CapabilityKind::Encoder,
+        name: "libx264",
+        used_by: "sub burn",
+    },
+    Capability {
+        kind: CapabilityKind::Muxer,
+        name: "srt",
+        used_by: "sub export",
+    },
+    Capability {
+        kind: CapabilityKind::Filter,
+        name: "select",
+        used_by: "sub snap",
+    },
+    Capability {
+        kind: CapabilityKind::Filter,
+        name: "showinfo",
+        used_by: "sub snap",
+    },
+    Capability {
+        kind: CapabilityKind::Encoder,
+        name: "libmp3lame",
+        used_by: "sub clip",
+    },
+];
+
+/// 单个命令的检测结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolReport {
+    pub command: &'static str,
+    /// 找到的命令路径，没有找到时为 `None`
+    pub path: Option<PathBuf>,
+    /// 路径的来源，在 `PATH` 中找到时为 `None`
+    pub source: Option<PathSource>,
+    pub version: Option<String>,
+    /// 找不到命令或者执行 `-version` 失败的原因
+    pub error: Option<String>,
+}
+
+/// `blowup doctor` 的检测结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct DoctorReport {
+    pub ffmpeg: ToolReport,
+    pub ffprobe: ToolReport,
+    /// 编译 ffmpeg 时通过 `--enable-*` 启用的功能
+    pub enabled: Vec<String>,
+    pub encoders: BTreeSet<String>,
+    pub muxers: BTreeSet<String>,
+    pub filters: BTreeSet<String>,
+}
+
+impl DoctorReport {
+    /// 当前 ffmpeg 是否支持某项能力
+    pub fn supports(&self, capability: &Capability) -> bool {
+        match capability.kind {
+            CapabilityKind::Encoder => self.encoders.contains(capability.name),
+            CapabilityKind::Muxer => self.muxers.contains(capability.name),
+            CapabilityKind::Filter => self.filters.contains(capability.name),
+        }
+    }
+
+    /// 缺少的能力
+    pub fn missing(&self) -> Vec<&'static Capability> {
+        CAPABILITIES.iter().filter(|c| !self.supports(c)).collect()
+    }
+
+    /// 是否一切正常
+    pub fn is_healthy(&self) -> bool {
+        self.ffmpeg.error.is_none() && self.ffprobe.error.is_none() && self.missing().is_empty()
+    }
+}
+
+/// 检测 ffmpeg、ffprobe 的位置、版本以及 blowup 需要的能力，并打印结果
+pub async fn run_doctor() {
+    let report = diagnose_with(&SystemRunner).await;
+    print_report(&report);
+}
+
+/// 通过指定的 `runner` 检测 ffmpeg 和 ffprobe
+pub async fn diagnose_with<R: ProcessRunner>(runner: &R) -> DoctorReport {
+    let (ffmpeg, version_output) = inspect_tool(runner, FfmpegTool::Ffmpeg).await;
+    let (ffprobe, _) = inspect_tool(runner, FfmpegTool::Ffprobe).await;
+
+    let mut report = DoctorReport {
+        ffmpeg,
+        ffprobe,
+        enabled: version_output
+            .as_deref()
+            .map(parse_enabled)
+            .unwrap_or_default(),
+        encoders: BTreeSet::new(),
+        muxers: BTreeSet::new(),
+        filters: BTreeSet::new(),
+    };
+    if report.ffmpeg.error.is_none() {
+        report.encoders = list_names(runner, "-encoders")
+            .await
+            .map(|out| parse_names(&out))
+            .unwrap_or_default();
+        report.muxers = list_names(runner, "-muxers")
+            .await
+            .map(|out| parse_names(&out))
+            .unwrap_or_default();
+        report.filters = list_names(runner, "-filters")
+            .await
+            .map(|out| parse_filters(&out))
+            .unwrap_or_default();
+    }
+    report
+}
+
+async fn inspect_tool<R: ProcessRunner>(
+    runner: &R,
+    tool: FfmpegTool,
+) -> (ToolReport, Option<String>) {
+    let source = tool.override_path().map(|(_, source)| source);
+    let mut report = ToolReport {
+        command: tool.command(),
+        path: None,
+        source,
+        version: None,
+        error: None,
+    };
+    match tool.locate_with(runner, None::<&str>) {
+        Ok(path) => report.path = Some(path),
+        Err(e) => {
+            report.error = Some(e.to_string());
+            return (report, None);
+        }
+    }
+    match tool
+        .exec_with_runner(runner, None::<&str>, Some(vec!["-version"]))
+        .await
+    {
+        Ok((stdout, _)) => {
+            report.version = parse_version(&stdout);
+            (report, Some(stdout))
+        }
+        Err(e) => {
+            report.error = Some(e.to_string());
+            (report, None)
+        }
+    }
+}
+
+async fn list_names<R: ProcessRunner>(runner: &R, option: &str) -> Option<String> {
+    FfmpegTool::Ffmpeg
+        .exec_with_runner(runner, None::<&str>, Some(vec!["-hide_banner", option]))
+        .await
+        .ok()
+        .map(|(stdout, _)| stdout)
+}
+
+/// 从 `-version` 的第一行 `ffmpeg version 7.1 Copyright ...` 中解析出版本号
+fn parse_version(output: &str) -> Option<String> {
+    let mut words = output.lines().next()?.split_whitespace();
+    words.find(|w| *w == "version")?;
+    words.next().map(|v| v.to_string())
+}
+
+/// 从 `-version` 的 `configuration:` 行中解析出所有 `--enable-*` 选项
+fn parse_enabled(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("configuration:"))
+        .map(|conf| {
+            conf.split_whitespace()
+                .filter_map(|opt| opt.strip_prefix("--enable-"))
+                .map(|opt| opt.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 解析 `-encoders`、`-muxers` 的输出，分隔线 `------`（`-muxers` 中为 `--`）之后每一行的第二列是名称
+fn parse_names(output: &str) -> BTreeSet<String> {
+    output
+        .lines()
+        .skip_while(|l| !l.trim_start().starts_with("--"))
+        .skip(1)
+        .filter_map(|l| l.split_whitespace().nth(1))
+        .map(|name| name.to_string())
+        .collect()
+}
+
+/// 解析 `-filters` 的输出，形如 `TSC subtitles  V->V  Render text subtitles...`
+fn parse_filters(output: &str) -> BTreeSet<String> {
+    output
+        .lines()
+        .filter_map(|l| {
+            let words: Vec<&str> = l.split_whitespace().take(3).collect();
+            match words[..] {
+                [_, name, io] if io.contains("->") => Some(name.to_string()),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn print_tool(tool: &ToolReport) {
+    match (&tool.path, &tool.error) {
+        (Some(path), None) => {
+            let source = tool
+                .source
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| "PATH".to_string());
+            println!(
+                "{}: {} ({}) version {}",
+                tool.command,
+                path.display(),
+                source,
+                tool.version.as_deref().unwrap_or("unknown")
+            );
+        }
+        (_, error) => println!(
+            "{}: NOT OK, {}",
+            tool.command,
+            error.as_deref().unwrap_or("unknown error")
+        ),
+    }
+}
+
+fn print_report(report: &DoctorReport) {
+    print_tool(&report.ffmpeg);
+    print_tool(&report.ffprobe);
+    if report.ffmpeg.error.is_none() {
+        println!("enabled: {}", report.enabled.join(", "));
+        println!(
+            "encoders: {}, muxers: {}, filters: {}",
+            report.encoders.len(),
+            report.muxers.len(),
+            report.filters.len()
+        );
+        for capability in CAPABILITIES {
+            println!(
+                "  [{}] {} {} (used by `{}`)",
+                if report.supports(capability) {
+                    "ok"
+                } else {
+                    "missing"
+                },
+                capability.kind,
+                capability.name,
+                capability.used_by
+            );
+        }
+    }
+    if report.ffmpeg.error.is_some() || report.ffprobe.error.is_some() {
+        println!(
+            "hint: set {} / {} or the `[ffmpeg]` section of {} to point at your ffmpeg build",
+            FfmpegTool::Ffmpeg.env_var(),
+            FfmpegTool::Ffprobe.env_var(),
+            config_path()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|| "the config file".to_string())
+        );
+    }
+    if report.is_healthy() {
+        println!("everything looks good");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::ReplayRunner;
+
+    const VERSION: &str = "ffmpeg version 7.1-static https://johnvansickle.com/ffmpeg/  Copyright (c) 2000-2024 the FFmpeg developers
+built with gcc 8 (Debian 8.3.0-6)
+configuration: --enable-gpl --enable-version3 --enable-static --disable-debug --enable-libass --enable-libx264
+libavutil      59. 39.100 / 59. 39.100
+";
+
+    const ENCODERS: &str = "Encoders:
+ V..... = Video
+ A..... = Audio
+ S..... = Subtitle
+ ------
+ V....D libx264              libx264 H.264 / AVC / MPEG-4 AVC / MPEG-4 part 10 (codec h264)
+ A....D aac                  AAC (Advanced Audio Coding)
+ S..... srt                  SubRip subtitle
+";
+
+    const MUXERS: &str = "File formats:
+ D. = Demuxing supported
+ .E = Muxing supported
+ --
+  E matroska        Matroska
+  E mp4             MP4 (MPEG-4 Part 14)
+  E srt             SubRip subtitle
+";
+
+    const FILTERS: &str = "Filters:
+  T.. = Timeline support
+  .S. = Slice threading
+  ..C = Command support
+  A = Audio input/output
+  | = Source or sink filter
+ ... ass               V->V       Render ASS subtitles onto input video using the libass library.
+ T.C select            V->V       Select video frames to pass in output.
+ TS. scale             V->V       Scale the input video size and/or convert the image format.
+";
+
+    #[test]
+    fn test_parse_version_output() {
+        assert_eq!(parse_version(VERSION).as_deref(), Some("7.1-static"));
+        assert_eq!(parse_version(""), None);
+        assert_eq!(
+            parse_enabled(VERSION),
+            vec!["gpl", "version3", "static", "libass", "libx264"]
+        );
+    }
+
+    #[test]
+    fn test_parse_lists() {
+        let encoders = parse_names(ENCODERS);
+        assert_eq!(encoders.len(), 3);
+        assert!(encoders.contains("libx264"));
+        assert!(!encoders.contains("="));
+
+        let muxers = parse_names(MUXERS);
+        assert_eq!(
+            muxers.into_iter().collect::<Vec<_>>(),
+            vec!["matroska", "mp4", "srt"]
+        );
+
+        let filters = parse_filters(FILTERS);
+        assert_eq!(
+            filters.into_iter().collect::<Vec<_>>(),
+            vec!["ass", "scale", "select"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_diagnose_reports_missing_capabilities() {
+        let runner = ReplayRunner::new();
+        runner.push_success(VERSION, "");
+        runner.push_success(VERSION.replace("ffmpeg version", "ffprobe version"), "");
+        runner.push_success(ENCODERS, "");
+        runner.push_success(MUXERS, "");
+        runner.push_success(FILTERS, "");
+
+        let report = diagnose_with(&runner).await;
+        assert_eq!(report.ffmpeg.version.as_deref(), Some("7.1-static"));
+        assert_eq!(report.ffprobe.version.as_deref(), Some("7.1-static"));
+        assert!(report.ffmpeg.error.is_none());
+        // 测试数据中的 ffmpeg 没有 subtitles、showinfo 过滤器和 libmp3lame 编码器
+        let missing: Vec<_> = report.missing().iter().map(|c| c.name).collect();
+        assert_eq!(missing, vec!["subtitles", "showinfo", "libmp3lame"]);
+        assert!(!report.is_healthy());
+    }
+
+    #[tokio::test]
+    async fn test_diagnose_without_ffmpeg() {
+        let runner = ReplayRunner::new().with_missing(FfmpegTool::Ffmpeg.command());
+        runner.push_success("ffprobe version 6.1.1 Copyright", "");
+
+        let report = diagnose_with(&runner).await;
+        assert!(report.ffmpeg.path.is_none());
+        assert!(report.ffmpeg.error.is_some());
+        assert_eq!(report.ffprobe.version.as_deref(), Some("6.1.1"));
+        // 找不到 ffmpeg 时不会再查询编码器、封装格式和过滤器
+        assert_eq!(runner.calls().len(), 1);
+        assert_eq!(report.missing().len(), CAPABILITIES.len());
+    }
+}
@@ -0,0 +1,524 @@
+//! probe module
+//! ffprobe JSON 输出的完整类型模型
+//!
+//! 对应 `ffprobe -print_format json -show_format -show_streams -show_chapters` 的输出，
+//! ffprobe 会省略无法确定的字段，所以除了索引以外的字段都是可选的
+
+use std::{collections::BTreeMap, ffi::OsStr, path::Path, time::Duration};
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use super::{FfmpegTool, Result};
+
+/// ffprobe 输出的顶层结构
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ProbeOutput {
+    /// 容器信息，只有使用 `-show_format` 时才存在
+    pub format: Option<ProbeFormat>,
+    /// 所有的流
+    pub streams: Vec<ProbeStream>,
+    /// 章节信息
+    pub chapters: Vec<ProbeChapter>,
+}
+
+/// 容器（封装格式）信息
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ProbeFormat {
+    pub filename: String,
+    pub nb_streams: u32,
+    pub nb_programs: u32,
+    /// 封装格式的短名称，可能是逗号分隔的多个别名，如 `matroska,webm`
+    pub format_name: String,
+    pub format_long_name: Option<String>,
+    pub start_time: Option<String>,
+    /// 时长，单位秒
+    pub duration: Option<String>,
+    /// 文件大小，单位字节
+    pub size: Option<String>,
+    /// 总码率，单位 bit/s
+    pub bit_rate: Option<String>,
+    pub probe_score: Option<u32>,
+    pub tags: BTreeMap<String, String>,
+}
+
+impl ProbeFormat {
+    pub fn duration(&self) -> Option<Duration> {
+        parse_secs(self.duration.as_deref())
+    }
+
+    pub fn size(&self) -> Option<u64> {
+        self.size.as_deref().and_then(|s| s.parse().ok())
+    }
+
+    pub fn bit_rate(&self) -> Option<u64> {
+        self.bit_rate.as_deref().and_then(|s| s.parse().ok())
+    }
+}
+
+/// 流的类型
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CodecType {
+    Video,
+    Audio,
+    Subtitle,
+    Data,
+    Attachment,
+    #[default]
+    #[serde(other)]
+    Unknown,
+}
+
+/// 单个流的信息，视频、音频和字幕流共用一个结构，不适用的字段为 `None`
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ProbeStream {
+    /// 流的索引，从 0 开始
+    pub index: u32,
+    pub codec_type: CodecType,
+    pub codec_name: Option<String>,
+    pub codec_long_name: Option<String>,
+    pub profile: Option<String>,
+    pub codec_tag_string: Option<String>,
+
+    // 视频相关
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub sample_aspect_ratio: Option<String>,
+    pub display_aspect_ratio: Option<String>,
+    pub pix_fmt: Option<String>,
+    pub level: Option<i32>,
+    pub color_range: Option<String>,
+    pub color_space: Option<String>,
+    pub color_transfer: Option<String>,
+    pub color_primaries: Option<String>,
+    pub field_order: Option<String>,
+    pub r_frame_rate: Option<String>,
+    pub avg_frame_rate: Option<String>,
+    pub bits_per_raw_sample: Option<String>,
+
+    // 音频相关
+    pub sample_fmt: Option<String>,
+    pub sample_rate: Option<String>,
+    pub channels: Option<u32>,
+    pub channel_layout: Option<String>,
+    pub bits_per_sample: Option<u32>,
+
+    /// 时间基，如 `1/1000`，`*_ts` 和 `*_pts` 字段都以它为单位
+    pub time_base: Option<String>,
+    pub start_pts: Option<i64>,
+    pub start_time: Option<String>,
+    pub duration_ts: Option<u64>,
+    pub duration: Option<String>,
+    pub bit_rate: Option<String>,
+    pub nb_frames: Option<String>,
+
+    pub disposition: Disposition,
+    pub tags: BTreeMap<String, String>,
+    pub side_data_list: Vec<SideData>,
+}
+
+impl ProbeStream {
+    pub fn language(&self) -> Option<&str> {
+        self.tags.get("language").map(String::as_str)
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.tags.get("title").map(String::as_str)
+    }
+
+    pub fn bit_rate(&self) -> Option<u64> {
+        self.bit_rate.as_deref().and_then(|s| s.parse().ok())
+    }
+
+    pub fn sample_rate(&self) -> Option<u32> {
+        self.sample_rate.as_deref().and_then(|s| s.parse().ok())
+    }
+
+    /// 平均帧率，ffprobe 以分数的形式给出，如 `24000/1001`
+    pub fn frame_rate(&self) -> Option<f64> {
+        self.avg_frame_rate
+            .as_deref()
+            .and_then(parse_ratio)
+            .or_else(|| self.r_frame_rate.as_deref().and_then(parse_ratio))
+    }
+
+    /// 流的时长
+    ///
+    /// 优先使用以时间基为单位的 `duration_ts` 换算，其次是以秒为单位的 `duration` 字段，
+    /// 最后回退到 MKV 封装写在标签中的 `DURATION`，如 `00:24:01.441000000`
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration_ts
+            .zip(self.time_base())
+            .and_then(|(ts, (num, den))| ticks_to_duration(ts, num, den))
+            .or_else(|| parse_secs(self.duration.as_deref()))
+            .or_else(|| {
+                self.tags
+                    .iter()
+                    .find(|(k, _)| k.as_str() == "DURATION" || k.starts_with("DURATION-"))
+                    .and_then(|(_, v)| parse_clock(v))
+            })
+    }
+
+    /// 流的起始时间
+    pub fn start_time(&self) -> Option<Duration> {
+        parse_secs(self.start_time.as_deref())
+    }
+
+    /// 解析时间基，返回 `(分子, 分母)`
+    pub fn time_base(&self) -> Option<(u64, u64)> {
+        let (num, den) = self.time_base.as_deref()?.split_once('/')?;
+        let num = num.trim().parse().ok()?;
+        let den = den.trim().parse().ok()?;
+        (den != 0).then_some((num, den))
+    }
+
+    /// 根据视频流的传输特性和附加数据判断 HDR 格式，SDR 视频返回 `None`
+    pub fn hdr_format(&self) -> Option<&'static str> {
+        let dolby_vision = self
+            .side_data_list
+            .iter()
+            .any(|d| d.side_data_type.contains("DOVI"));
+        if dolby_vision {
+            return Some("Dolby Vision");
+        }
+        match self.color_transfer.as_deref() {
+            Some("smpte2084") => {
+                let hdr10_plus = self.side_data_list.iter().any(|d| {
+                    d.side_data_type.contains("HDR10+") || d.side_data_type.contains("SMPTE2094-40")
+                });
+                Some(if hdr10_plus { "HDR10+" } else { "HDR10" })
+            }
+            Some("arib-std-b67") => Some("HLG"),
+            _ => None,
+        }
+    }
+
+    /// 母版显示元数据（SMPTE ST 2086）
+    pub fn mastering_display(&self) -> Option<&SideData> {
+        self.side_data_list
+            .iter()
+            .find(|d| d.side_data_type == "Mastering display metadata")
+    }
+
+    /// 内容亮度信息（MaxCLL / MaxFALL）
+    pub fn content_light_level(&self) -> Option<&SideData> {
+        self.side_data_list
+            .iter()
+            .find(|d| d.side_data_type == "Content light level metadata")
+    }
+}
+
+/// 流的处置标记，ffprobe 使用 0/1 表示
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Disposition {
+    #[serde(deserialize_with = "bool_from_int")]
+    pub default: bool,
+    #[serde(deserialize_with = "bool_from_int")]
+    pub dub: bool,
+    #[serde(deserialize_with = "bool_from_int")]
+    pub original: bool,
+    #[serde(deserialize_with = "bool_from_int")]
+    pub comment: bool,
+    #[serde(deserialize_with = "bool_from_int")]
+    pub lyrics: bool,
+    #[serde(deserialize_with = "bool_from_int")]
+    pub karaoke: bool,
+    #[serde(deserialize_with = "bool_from_int")]
+    pub forced: bool,
+    #[serde(deserialize_with = "bool_from_int")]
+    pub hearing_impaired: bool,
+    #[serde(deserialize_with = "bool_from_int")]
+    pub visual_impaired: bool,
+    #[serde(deserialize_with = "bool_from_int")]
+    pub clean_effects: bool,
+    #[serde(deserialize_with = "bool_from_int")]
+    pub attached_pic: bool,
+    #[serde(deserialize_with = "bool_from_int")]
+    pub captions: bool,
+    #[serde(deserialize_with = "bool_from_int")]
+    pub descriptions: bool,
+}
+
+impl Disposition {
+    /// 所有被设置的标记名称
+    pub fn flags(&self) -> Vec<&'static str> {
+        [
+            (self.default, "default"),
+            (self.dub, "dub"),
+            (self.original, "original"),
+            (self.comment, "comment"),
+            (self.lyrics, "lyrics"),
+            (self.karaoke, "karaoke"),
+            (self.forced, "forced"),
+            (self.hearing_impaired, "hearing_impaired"),
+            (self.visual_impaired, "visual_impaired"),
+            (self.clean_effects, "clean_effects"),
+            (self.attached_pic, "attached_pic"),
+            (self.captions, "captions"),
+            (self.descriptions, "descriptions"),
+        ]
+        .into_iter()
+        .filter_map(|(set, name)| set.then_some(name))
+        .collect()
+    }
+}
+
+/// 流的附加数据，这里只保留 HDR 相关的字段
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SideData {
+    pub side_data_type: String,
+    // Mastering display metadata
+    pub red_x: Option<String>,
+    pub red_y: Option<String>,
+    pub green_x: Option<String>,
+    pub green_y: Option<String>,
+    pub blue_x: Option<String>,
+    pub blue_y: Option<String>,
+    pub white_point_x: Option<String>,
+    pub white_point_y: Option<String>,
+    pub min_luminance: Option<String>,
+    pub max_luminance: Option<String>,
+    // Content light level metadata
+    pub max_content: Option<u32>,
+    pub max_average: Option<u32>,
+    // DOVI configuration record
+    pub dv_profile: Option<u32>,
+    pub dv_level: Option<u32>,
+}
+
+/// 章节信息
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ProbeChapter {
+    pub id: i64,
+    pub time_base: Option<String>,
+    pub start: i64,
+    pub start_time: Option<String>,
+    pub end: i64,
+    pub end_time: Option<String>,
+    pub tags: BTreeMap<String, String>,
+}
+
+impl ProbeChapter {
+    pub fn title(&self) -> Option<&str> {
+        self.tags.get("title").map(String::as_str)
+    }
+
+    pub fn start(&self) -> Option<Duration> {
+        parse_secs(self.start_time.as_deref())
+    }
+
+    pub fn end(&self) -> Option<Duration> {
+        parse_secs(self.end_time.as_deref())
+    }
+}
+
+/// 使用 ffprobe 读取媒体文件的容器、流和章节信息
+pub async fn probe_file(file: impl AsRef<Path>) -> Result<ProbeOutput> {
+    let options = vec![
+        OsStr::new("-v"),
+        OsStr::new("quiet"),
+        OsStr::new("-print_format"),
+        OsStr::new("json"),
+        OsStr::new("-show_format"),
+        OsStr::new("-show_streams"),
+        OsStr::new("-show_chapters"),
+        OsStr::new("--"),
+        file.as_ref().as_os_str(),
+    ];
+    let (stdout, _) = FfmpegTool::Ffprobe
+        .exec_with_options(None::<&'static str>, Some(options))
+        .await?;
+    Ok(serde_json::from_str(&stdout)?)
+}
+
+/// 解析 ffprobe 以字符串表示的秒数
+fn parse_secs(secs: Option<&str>) -> Option<Duration> {
+    secs.and_then(|s| s.parse::<f64>().ok())
+        .filter(|s| s.is_finite() && *s >= 0.0)
+        .map(Duration::from_secs_f64)
+}
+
+/// 将 `ticks` 个时间基 `num/den` 换算为时长，使用整数运算避免精度损失，溢出时返回 `None`
+fn ticks_to_duration(ticks: u64, num: u64, den: u64) -> Option<Duration> {
+    let nanos = u128::from(ticks)
+        .checked_mul(u128::from(num))?
+        .checked_mul(1_000_000_000)?
+        / u128::from(den);
+    u64::try_from(nanos).ok().map(Duration::from_nanos)
+}
+
+/// 解析 `HH:MM:SS.fraction` 形式的时间
+fn parse_clock(clock: &str) -> Option<Duration> {
+    let mut parts = clock.trim().splitn(3, ':');
+    let h: u64 = parts.next()?.parse().ok()?;
+    let m: u64 = parts.next()?.parse().ok()?;
+    let s: f64 = parts.next()?.parse().ok()?;
+    if m > 59 || !(0.0..60.0).contains(&s) {
+        return None;
+    }
+    Some(Duration::from_secs(h * 3600 + m * 60) + Duration::from_secs_f64(s))
+}
+
+/// 解析 `num/den` 形式的分数，分母为 0 时返回 `None`
+fn parse_ratio(ratio: &str) -> Option<f64> {
+    let (num, den) = ratio.split_once('/')?;
+    let num: f64 = num.trim().parse().ok()?;
+    let den: f64 = den.trim().parse().ok()?;
+    if den == 0.0 { None } else { Some(num / den) }
+}
+
+fn bool_from_int<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<bool, D::Error> {
+    Ok(i64::deserialize(deserializer)? != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"{
+        "streams": [
+            {
+                "index": 0,
+                "codec_name": "hevc",
+                "codec_type": "video",
+                "profile": "Main 10",
+                "width": 3840,
+                "height": 2160,
+                "pix_fmt": "yuv420p10le",
+                "color_transfer": "smpte2084",
+                "color_primaries": "bt2020",
+                "avg_frame_rate": "24000/1001",
+                "r_frame_rate": "24000/1001",
+                "time_base": "1/1000",
+                "disposition": { "default": 1, "forced": 0 },
+                "side_data_list": [
+                    { "side_data_type": "Mastering display metadata", "max_luminance": "10000000/10000", "min_luminance": "50/10000" },
+                    { "side_data_type": "Content light level metadata", "max_content": 1000, "max_average": 400 }
+                ]
+            },
+            {
+                "index": 1,
+                "codec_name": "eac3",
+                "codec_type": "audio",
+                "sample_rate": "48000",
+                "channels": 6,
+                "channel_layout": "5.1(side)",
+                "disposition": { "default": 1 },
+                "tags": { "language": "eng", "title": "Surround" }
+            },
+            {
+                "index": 2,
+                "codec_name": "subrip",
+                "codec_type": "subtitle",
+                "disposition": { "default": 0, "forced": 1, "hearing_impaired": 1 },
+                "tags": { "language": "chi" }
+            },
+            { "index": 3, "codec_type": "attachment", "tags": { "filename": "font.ttf" } },
+            { "index": 4, "codec_type": "something-new" }
+        ],
+        "chapters": [
+            { "id": 0, "time_base": "1/1000000000", "start": 0, "start_time": "0.000000", "end": 300000000000, "end_time": "300.000000", "tags": { "title": "Opening" } }
+        ],
+        "format": {
+            "filename": "movie.mkv",
+            "nb_streams": 5,
+            "format_name": "matroska,webm",
+            "duration": "7260.512000",
+            "size": "12345678901",
+            "bit_rate": "13601952",
+            "tags": { "title": "Movie" }
+        }
+    }"#;
+
+    #[test]
+    fn test_deserialize_full_output() {
+        let output: ProbeOutput = serde_json::from_str(SAMPLE).unwrap();
+        let format = output.format.as_ref().unwrap();
+        assert_eq!(format.format_name, "matroska,webm");
+        assert_eq!(format.duration(), Some(Duration::from_secs_f64(7260.512)));
+        assert_eq!(format.size(), Some(12_345_678_901));
+        assert_eq!(format.bit_rate(), Some(13_601_952));
+        assert_eq!(output.streams.len(), 5);
+        assert_eq!(output.streams[3].codec_type, CodecType::Attachment);
+        assert_eq!(output.streams[4].codec_type, CodecType::Unknown);
+        assert_eq!(output.chapters[0].title(), Some("Opening"));
+        assert_eq!(output.chapters[0].end(), Some(Duration::from_secs(300)));
+    }
+
+    #[test]
+    fn test_video_stream_helpers() {
+        let output: ProbeOutput = serde_json::from_str(SAMPLE).unwrap();
+        let video = &output.streams[0];
+        assert_eq!(video.codec_type, CodecType::Video);
+        assert_eq!(video.hdr_format(), Some("HDR10"));
+        assert!((video.frame_rate().unwrap() - 23.976).abs() < 0.001);
+        assert_eq!(video.content_light_level().unwrap().max_content, Some(1000));
+        assert!(video.mastering_display().is_some());
+        assert_eq!(video.disposition.flags(), vec!["default"]);
+    }
+
+    #[test]
+    fn test_audio_and_subtitle_helpers() {
+        let output: ProbeOutput = serde_json::from_str(SAMPLE).unwrap();
+        let audio = &output.streams[1];
+        assert_eq!(audio.sample_rate(), Some(48000));
+        assert_eq!(audio.language(), Some("eng"));
+        assert_eq!(audio.title(), Some("Surround"));
+        assert_eq!(audio.hdr_format(), None);
+        let sub = &output.streams[2];
+        assert_eq!(sub.disposition.flags(), vec!["forced", "hearing_impaired"]);
+        assert_eq!(sub.duration(), None);
+    }
+
+    #[test]
+    fn test_stream_duration_fallbacks() {
+        let mut stream = ProbeStream {
+            time_base: Some("1/90000".to_string()),
+            duration_ts: Some(180_000),
+            duration: Some("3.000000".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(stream.duration(), Some(Duration::from_secs(2)));
+        stream.duration_ts = None;
+        assert_eq!(stream.duration(), Some(Duration::from_secs(3)));
+        stream.duration = None;
+        assert_eq!(stream.duration(), None);
+        stream
+            .tags
+            .insert("DURATION-eng".to_string(), "00:01:02.500000000".to_string());
+        assert_eq!(stream.duration(), Some(Duration::from_millis(62_500)));
+        // 时间基不合法时不能使用 duration_ts
+        stream.time_base = Some("1/0".to_string());
+        stream.duration_ts = Some(100);
+        assert_eq!(stream.duration(), Some(Duration::from_millis(62_500)));
+        // 损坏的文件中的数值可能导致溢出
+        stream.time_base = Some(format!("{}/1", u64::MAX));
+        stream.duration_ts = Some(u64::MAX);
+        assert_eq!(stream.duration(), Some(Duration::from_millis(62_500)));
+    }
+
+    #[test]
+    fn test_parse_clock() {
+        assert_eq!(
+            parse_clock("01:59:43.123000000"),
+            Some(Duration::from_millis(7_183_123))
+        );
+        assert_eq!(parse_clock("00:61:00.0"), None);
+        assert_eq!(parse_clock("garbage"), None);
+    }
+
+    #[test]
+    fn test_parse_ratio() {
+        assert_eq!(parse_ratio("25/1"), Some(25.0));
+        assert_eq!(parse_ratio("0/0"), None);
+        assert_eq!(parse_ratio("abc"), None);
+    }
+}
@@ -0,0 +1,229 @@
+//! scene module
+//! 使用 ffmpeg 的 `select='gt(scene,x)'` 滤镜检测镜头切换点
+//!
+//! 检测需要解码整个视频，耗时较长，所以检测结果会按照视频文件和阈值缓存在缓存目录中，
+//! 视频文件的大小或修改时间变化后缓存失效
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{self, UnboundedSender};
+
+use crate::{
+    common::{ProcessRunner, SystemRunner},
+    config::cache_dir,
+};
+
+use super::{FfmpegProgress, FfmpegTool, Result, probe_duration, report_progress};
+
+/// 默认的场景变化阈值，取值范围为 0~1，越小检测到的切换点越多
+pub const DEFAULT_SCENE_THRESHOLD: f64 = 0.3;
+
+const SCENE_CACHE_DIR: &str = "scenes";
+
+/// 检测视频中的镜头切换点，优先使用缓存，检测过程中在标准错误输出实时进度
+pub async fn scene_cuts(video: impl AsRef<Path>, threshold: f64) -> Result<Vec<Duration>> {
+    let video = video.as_ref();
+    let cache = cache_dir().map(|dir| dir.join(SCENE_CACHE_DIR));
+    if let Some(cuts) = cache
+        .as_deref()
+        .and_then(|dir| load_cached_cuts(dir, video, threshold))
+    {
+        return Ok(cuts);
+    }
+
+    let total = probe_duration(video).await?;
+    let (tx, rx) = mpsc::unbounded_channel::<FfmpegProgress>();
+    let (cuts, _) = tokio::join!(
+        detect_scene_cuts_with(&SystemRunner, video, threshold, tx),
+        report_progress("detecting scene cuts", total, rx)
+    );
+    let cuts = cuts?;
+
+    if let Some(dir) = cache.as_deref()
+        && let Err(e) = store_cached_cuts(dir, video, threshold, &cuts)
+    {
+        eprintln!("warning: failed to cache the scene cuts: {}", e);
+    }
+    Ok(cuts)
+}
+
+/// 通过指定的 `runner` 执行一次完整的场景检测，不使用缓存
+///
+/// 返回按时间排序的切换点，即新镜头第一帧的时间
+pub async fn detect_scene_cuts_with<R: ProcessRunner>(
+    runner: &R,
+    video: impl AsRef<Path>,
+    threshold: f64,
+    progress: UnboundedSender<FfmpegProgress>,
+) -> Result<Vec<Duration>> {
+    let filter = format!("select='gt(scene,{})',showinfo", threshold);
+    let options = vec![
+        "-hide_banner",
+        "-i",
+        video.as_ref().to_str().unwrap_or(""),
+        "-an",
+        "-sn",
+        "-dn",
+        "-vf",
+        &filter,
+        "-f",
+        "null",
+        "-",
+    ];
+    let stderr = FfmpegTool::Ffmpeg
+        .exec_with_progress(runner, None::<&'static str>, options, progress, false)
+        .await?;
+    Ok(parse_showinfo(&stderr))
+}
+
+/// 从 `showinfo` 滤镜的输出中解析出每一帧的 `pts_time`
+fn parse_showinfo(stderr: &str) -> Vec<Duration> {
+    let mut cuts: Vec<Duration> = stderr
+        .lines()
+        .filter(|l| l.contains("Parsed_showinfo"))
+        .filter_map(|l| {
+            let (_, rest) = l.split_once("pts_time:")?;
+            let secs: f64 = rest.split_whitespace().next()?.parse().ok()?;
+            (secs.is_finite() && secs >= 0.0).then(|| Duration::from_secs_f64(secs))
+        })
+        .collect();
+    cuts.sort();
+    cuts.dedup();
+    cuts
+}
+
+/// 缓存文件的内容，保存视频文件的信息用于判断缓存是否失效
+#[derive(Debug, Serialize, Deserialize)]
+struct SceneCache {
+    file: PathBuf,
+    size: u64,
+    modified: u64,
+    threshold: f64,
+    cuts_ms: Vec<u64>,
+}
+
+impl SceneCache {
+    fn matches(&self, other: &SceneCache) -> bool {
+        self.file == other.file
+            && self.size == other.size
+            && self.modified == other.modified
+            && self.threshold == other.threshold
+    }
+}
+
+/// 根据视频文件的当前状态生成一个不包含切换点的缓存记录
+fn cache_key(video: &Path, threshold: f64) -> io::Result<SceneCache> {
+    let file = fs::canonicalize(video)?;
+    let meta = fs::metadata(&file)?;
+    let modified = meta
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    Ok(SceneCache {
+        file,
+        size: meta.len(),
+        modified,
+        threshold,
+        cuts_ms: Vec::new(),
+    })
+}
+
+fn cache_file(dir: &Path, key: &SceneCache) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    key.file.hash(&mut hasher);
+    key.threshold.to_bits().hash(&mut hasher);
+    dir.join(format!("{:016x}.json", hasher.finish()))
+}
+
+/// 读取缓存的切换点，缓存不存在或已经失效时返回 `None`
+pub fn load_cached_cuts(dir: &Path, video: &Path, threshold: f64) -> Option<Vec<Duration>> {
+    let key = cache_key(video, threshold).ok()?;
+    let content = fs::read_to_string(cache_file(dir, &key)).ok()?;
+    let cache: SceneCache = serde_json::from_str(&content).ok()?;
+    cache.matches(&key).then(|| {
+        cache
+            .cuts_ms
+            .into_iter()
+            .map(Duration::from_millis)
+            .collect()
+    })
+}
+
+/// 将切换点写入缓存目录 `dir`
+pub fn store_cached_cuts(
+    dir: &Path,
+    video: &Path,
+    threshold: f64,
+    cuts: &[Duration],
+) -> io::Result<()> {
+    let mut cache = cache_key(video, threshold)?;
+    cache.cuts_ms = cuts.iter().map(|c| c.as_millis() as u64).collect();
+    fs::create_dir_all(dir)?;
+    fs::write(cache_file(dir, &cache), serde_json::to_string(&cache)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::ReplayRunner;
+
+    const SHOWINFO: &str = "Input #0, matroska,webm, from 'movie.mkv':
+[Parsed_showinfo_1 @ 0x5581c8a4c0] config in time_base: 1/1000, frame_rate: 24000/1001
+[Parsed_showinfo_1 @ 0x5581c8a4c0] n:   0 pts:   4296 pts_time:4.296   duration:     42 pos:   123 fmt:yuv420p
+[Parsed_showinfo_1 @ 0x5581c8a4c0]   color_range:tv color_space:bt709
+[Parsed_showinfo_1 @ 0x5581c8a4c0] n:   1 pts:  12012 pts_time:12.012  duration:     42 pos:  4567 fmt:yuv420p
+[out#0/null @ 0x5581c8b000] video:0KiB audio:0KiB
+";
+
+    #[test]
+    fn test_parse_showinfo() {
+        assert_eq!(
+            parse_showinfo(SHOWINFO),
+            vec![Duration::from_millis(4_296), Duration::from_millis(12_012)]
+        );
+        assert!(parse_showinfo("").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_detect_scene_cuts() {
+        let runner = ReplayRunner::new();
+        runner.push_success("progress=end\n", SHOWINFO);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let cuts = detect_scene_cuts_with(&runner, "movie.mkv", 0.4, tx)
+            .await
+            .unwrap();
+        assert_eq!(cuts.len(), 2);
+        assert!(rx.recv().await.unwrap().finished);
+        let args = &runner.calls()[0].args;
+        assert!(args.iter().any(|a| a == "select='gt(scene,0.4)',showinfo"));
+        assert_eq!(args.last().map(String::as_str), Some("-"));
+    }
+
+    #[test]
+    fn test_scene_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let video = dir.path().join("movie.mkv");
+        fs::write(&video, "not really a video").unwrap();
+        let cache_dir = dir.path().join("cache");
+        let cuts = vec![Duration::from_millis(1_001), Duration::from_millis(5_005)];
+
+        assert_eq!(load_cached_cuts(&cache_dir, &video, 0.3), None);
+        store_cached_cuts(&cache_dir, &video, 0.3, &cuts).unwrap();
+        assert_eq!(load_cached_cuts(&cache_dir, &video, 0.3), Some(cuts));
+        // 阈值不同的结果分开缓存
+        assert_eq!(load_cached_cuts(&cache_dir, &video, 0.5), None);
+        // 视频文件变化后缓存失效
+        fs::write(&video, "a different video").unwrap();
+        assert_eq!(load_cached_cuts(&cache_dir, &video, 0.3), None);
+    }
+}
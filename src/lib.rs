@@ -1,5 +1,9 @@
 pub mod ai;
+pub mod batch;
 pub mod common;
+pub mod config;
+pub mod daemon;
 pub mod ffmpeg;
+pub mod media;
 pub mod sub;
 pub mod torrent;
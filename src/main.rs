@@ -1,11 +1,36 @@
-use std::error;
+use std::{
+    error,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use blowup::{
+    batch::default_jobs,
+    config::config,
+    daemon::run_daemon,
+    ffmpeg::{doctor::run_doctor, scene::DEFAULT_SCENE_THRESHOLD},
+    media::show_media_info,
     sub::{
-        OutputFormat, OverlapFixMode, compare_two_srt_file, extract_sub_srt,
-        list_all_subtitle_stream, update_srt_time,
+        BurnStyle, ClipKind, ClipOptions, EncodePreset, EntrySelection, FetchOptions, OutputFormat,
+        OverlapFixMode, SnapOptions, SubPosition, TranslateOptions, burn_subtitle, clip_entries,
+        compare_two_srt_file, extract_sub_srt_batch, fetch_subtitle,
+        list_all_subtitle_stream_batch, match_library, parse_imdb_id, snap_srt_to_scene_cuts,
+        translate_srt, update_srt_time_batch,
+    },
+    torrent::{
+        DEFAULT_TRACKER_FILE,
+        client::{ClientKind, push_trackers},
+        create::{CreateOptions, TorrentVersion, create_torrent_file, parse_piece_size},
+        download_newest_tracker,
+        health::{CheckOptions, DEFAULT_ALIVE_FILE, check_tracker_file},
+        inject::add_trackers,
+        magnet::torrent_to_magnet,
+        metainfo::show_torrent_info,
+        output::{TrackerListFormat, TrackerOutput},
+        source::{TrackerSource, configured_sources},
+        tracker::TrackerPolicy,
+        verify::verify_torrent_file,
     },
-    torrent::download_newest_tracker,
 };
 use clap::{Args, Parser, Subcommand};
 
@@ -20,8 +45,21 @@ struct Hermes {
 enum Commands {
     #[command(about = "handle all things about tracker list")]
     Tracker(TrackerArgs),
+    #[command(about = "inspect and modify .torrent files and magnet links")]
+    Torrent(TorrentArgs),
     #[command(about = "subtitle file processing tools")]
     Sub(SubArgs),
+    #[command(about = "inspect media files")]
+    Media(MediaArgs),
+    #[command(about = "check the ffmpeg/ffprobe installation and the capabilities blowup needs")]
+    Doctor,
+    #[command(
+        about = "run the jobs configured under [daemon] on their intervals, logging to a file in the data directory"
+    )]
+    Daemon {
+        #[arg(long, help = "Run every configured job once and exit")]
+        once: bool,
+    },
 }
 
 #[derive(Args)]
@@ -32,21 +70,228 @@ struct TrackerArgs {
 
 #[derive(Subcommand)]
 enum TrackerCommands {
-    #[command(about = "update the newest tracker list")]
-    Update {},
+    #[command(
+        about = "update the newest tracker list from all configured sources, merged and deduplicated"
+    )]
+    Update {
+        #[arg(
+            short,
+            long,
+            default_value = DEFAULT_TRACKER_FILE,
+            help = "Output file of the merged tracker list, - for stdout; only replaced after a successful fetch"
+        )]
+        output: TrackerOutput,
+        #[arg(
+            short = 'f',
+            long = "format",
+            value_enum,
+            help = "Output format: lines, aria2 (comma-separated), qbittorrent (blank-line tiers) or json, default is tracker.format in the config or lines"
+        )]
+        format: Option<TrackerListFormat>,
+        #[arg(
+            long = "source",
+            help = "Tracker list source instead of the configured ones, repeatable: github:owner/repo/path[@branch], an http(s) URL or a local file"
+        )]
+        sources: Vec<TrackerSource>,
+    },
+    #[command(
+        about = "probe every tracker in a list over UDP (BEP 15) or HTTP and keep only the live ones"
+    )]
+    Check {
+        #[arg(default_value = DEFAULT_TRACKER_FILE, help = "Tracker list to check")]
+        input: PathBuf,
+        #[arg(
+            short,
+            long,
+            default_value = DEFAULT_ALIVE_FILE,
+            help = "Output file of the live trackers"
+        )]
+        output: PathBuf,
+        #[arg(long, default_value_t = 5, help = "Timeout of each tracker in seconds")]
+        timeout: u64,
+        #[arg(
+            short,
+            long,
+            default_value_t = 32,
+            help = "Number of trackers checked concurrently"
+        )]
+        jobs: usize,
+    },
+    #[command(
+        about = "push a tracker list to qBittorrent, Transmission or aria2 as default trackers and add it to existing torrents"
+    )]
+    Push {
+        #[arg(default_value = DEFAULT_TRACKER_FILE, help = "Tracker list to push")]
+        input: PathBuf,
+        #[arg(
+            long = "client",
+            value_enum,
+            help = "Client to update, repeatable, default is every client configured under [clients]"
+        )]
+        clients: Vec<ClientKind>,
+        #[arg(
+            long,
+            help = "Only set the default trackers for new torrents, leave existing torrents untouched"
+        )]
+        skip_existing: bool,
+    },
+}
+
+#[derive(Args)]
+struct TorrentArgs {
+    #[command(subcommand)]
+    commands: TorrentCommands,
+}
+
+#[derive(Subcommand)]
+enum TorrentCommands {
+    #[command(
+        about = "Show the info-hashes, name, piece size, file tree, private flag, creation date and trackers of a .torrent file"
+    )]
+    Info {
+        file_name: PathBuf,
+        #[arg(
+            short = 'f',
+            long = "format",
+            help = "Output format: list/json/tab, default is list"
+        )]
+        format: Option<OutputFormat>,
+    },
+    #[command(
+        about = "Add a tracker list to a .torrent file (info-hash unchanged) or a magnet link, private torrents are refused"
+    )]
+    AddTrackers {
+        #[arg(help = ".torrent file or quoted magnet link")]
+        input: String,
+        #[arg(
+            short,
+            long,
+            default_value = DEFAULT_TRACKER_FILE,
+            help = "Tracker list to add"
+        )]
+        trackers: PathBuf,
+        #[arg(
+            short,
+            long,
+            help = "Output .torrent file, default is to overwrite the input; magnet links are printed"
+        )]
+        output: Option<PathBuf>,
+    },
+    #[command(
+        about = "Print the magnet link of a .torrent file with the current tracker list appended"
+    )]
+    Magnet {
+        #[arg(help = ".torrent file")]
+        file_name: PathBuf,
+        #[arg(
+            short,
+            long,
+            default_value = DEFAULT_TRACKER_FILE,
+            help = "Tracker list to append"
+        )]
+        trackers: PathBuf,
+        #[arg(long, help = "Only include the trackers of the torrent itself")]
+        no_trackers: bool,
+    },
+    #[command(about = "Create a .torrent file from a local file or directory")]
+    Create {
+        #[arg(help = "File or directory to share")]
+        path: PathBuf,
+        #[arg(
+            short,
+            long,
+            help = "Output .torrent file, default is <name>.torrent in the current directory"
+        )]
+        output: Option<PathBuf>,
+        #[arg(long, value_enum, default_value_t = TorrentVersion::Hybrid, help = "Torrent format")]
+        mode: TorrentVersion,
+        #[arg(
+            long,
+            value_parser = parse_piece_size,
+            help = "Piece size in KiB, a power of two, default is chosen from the total size"
+        )]
+        piece_size: Option<u64>,
+        #[arg(long, help = "Set the private flag, only --announce trackers are used")]
+        private: bool,
+        #[arg(long, help = "Comment")]
+        comment: Option<String>,
+        #[arg(long, help = "Source tag stored in the info dictionary")]
+        source: Option<String>,
+        #[arg(long = "web-seed", help = "Web seed URL, repeatable")]
+        web_seeds: Vec<String>,
+        #[arg(long, help = "Tracker placed before the tracker list, repeatable")]
+        announce: Vec<String>,
+        #[arg(
+            short,
+            long,
+            default_value = DEFAULT_TRACKER_FILE,
+            help = "Tracker list for the announce list, skipped if the file does not exist"
+        )]
+        trackers: PathBuf,
+        #[arg(long, help = "Do not add the tracker list")]
+        no_trackers: bool,
+        #[arg(
+            short,
+            long,
+            help = "Number of pieces hashed concurrently, default is the number of CPUs"
+        )]
+        jobs: Option<usize>,
+    },
+    #[command(
+        about = "Re-hash local data against a .torrent file and report missing, truncated and corrupt files"
+    )]
+    Verify {
+        #[arg(help = ".torrent file")]
+        file_name: PathBuf,
+        #[arg(help = "Download directory containing the torrent's content, or the content itself")]
+        dir: PathBuf,
+        #[arg(
+            short,
+            long,
+            help = "Number of pieces hashed concurrently, default is the number of CPUs"
+        )]
+        jobs: Option<usize>,
+    },
+}
+
+#[derive(Args)]
+struct MediaArgs {
+    #[command(subcommand)]
+    commands: MediaCommands,
+}
+
+#[derive(Subcommand)]
+enum MediaCommands {
+    #[command(about = "Show container, stream and chapter information of a media file")]
+    Info {
+        file_name: String,
+        #[arg(
+            short = 'f',
+            long = "format",
+            help = "Output format: list/json/tab, default is list"
+        )]
+        format: Option<OutputFormat>,
+    },
 }
 
 #[derive(Args)]
 struct SubArgs {
     #[command(subcommand)]
     commands: SubCommands,
+    #[arg(
+        short,
+        long,
+        global = true,
+        help = "Number of files processed concurrently in batch mode, default is the number of CPUs"
+    )]
+    jobs: Option<usize>,
 }
 
 #[derive(Subcommand)]
 enum SubCommands {
     #[command(about = "Modify SRT time entries: add or subtract a millisecond offset")]
     Incr {
-        #[arg(help = "Video file path")]
+        #[arg(help = "SRT file, directory or quoted glob pattern")]
         file_name: String,
         #[arg(help = "Modification duration in milliseconds")]
         ms: i64,
@@ -60,7 +305,9 @@ enum SubCommands {
         about = "Extract subtitle streams from the specified video container to a designated location"
     )]
     ExportSub {
+        #[arg(help = "Video file, directory or quoted glob pattern")]
         file_name: String,
+        #[arg(help = "Output SRT file, or output directory when exporting a directory/glob")]
         output_path: String,
     },
     #[command(
@@ -68,6 +315,7 @@ enum SubCommands {
         about = "List the number of subtitle streams in a video container"
     )]
     ListSubStream {
+        #[arg(help = "Video file, directory or quoted glob pattern")]
         file_name: String,
         #[arg(
             short = 'f',
@@ -86,6 +334,151 @@ enum SubCommands {
         #[arg(short, help = "enable interactive display")]
         interactive: bool,
     },
+    #[command(about = "Burn a subtitle file into the video frames (hardsub)")]
+    Burn {
+        #[arg(help = "Video file path")]
+        video: String,
+        #[arg(help = "Subtitle file path (srt/ass/ssa)")]
+        subtitle: String,
+        #[arg(help = "Output video file path, must not exist")]
+        output: String,
+        #[arg(long, help = "Font name")]
+        font: Option<String>,
+        #[arg(long, help = "Font size")]
+        size: Option<u32>,
+        #[arg(long, help = "Outline width")]
+        outline: Option<f32>,
+        #[arg(long, help = "Vertical margin from the edge of the frame")]
+        margin: Option<u32>,
+        #[arg(long, value_enum, default_value_t = SubPosition::Bottom, help = "Subtitle position")]
+        position: SubPosition,
+        #[arg(long, value_enum, default_value_t = EncodePreset::Balanced, help = "Encoding preset")]
+        preset: EncodePreset,
+    },
+    #[command(
+        about = "Snap subtitle start/end times to nearby shot changes of the video, writing <name>_snap.srt"
+    )]
+    Snap {
+        #[arg(help = "Video file path")]
+        video: String,
+        #[arg(help = "SRT file path")]
+        subtitle: String,
+        #[arg(long, default_value_t = DEFAULT_SCENE_THRESHOLD, help = "Scene change threshold between 0 and 1, lower detects more cuts")]
+        threshold: f64,
+        #[arg(
+            long,
+            default_value_t = 250,
+            help = "Only move times within this many milliseconds of a cut"
+        )]
+        tolerance: u64,
+        #[arg(
+            long,
+            default_value_t = 700,
+            help = "Minimum cue duration in milliseconds after snapping"
+        )]
+        min_duration: u64,
+    },
+    #[command(
+        about = "Cut audio snippets or thumbnails for selected subtitle entries, optionally as an Anki export"
+    )]
+    Clip {
+        #[arg(help = "Video file path")]
+        video: String,
+        #[arg(help = "SRT file path")]
+        subtitle: String,
+        #[arg(long, help = "Entry indexes to clip, e.g. 120-130 or 3,7,10-12")]
+        entries: EntrySelection,
+        #[arg(long, value_enum, default_value_t = ClipKind::Audio, help = "What to extract for each entry")]
+        kind: ClipKind,
+        #[arg(
+            long,
+            default_value_t = 200,
+            help = "Extra audio kept before and after each entry in milliseconds"
+        )]
+        padding: u64,
+        #[arg(
+            short,
+            long,
+            help = "Output directory, default is <subtitle>_clips next to the subtitle"
+        )]
+        output: Option<PathBuf>,
+        #[arg(
+            long,
+            help = "Write notes.csv and a media folder that can be imported into Anki"
+        )]
+        anki: bool,
+    },
+    #[command(
+        name = "match",
+        about = "Pair subtitles with videos in a directory tree and rename them to <video-stem>.<lang>.srt (dry run by default)"
+    )]
+    Match {
+        #[arg(help = "Media library directory, searched recursively")]
+        dir: String,
+        #[arg(
+            long,
+            help = "Actually rename the files instead of only printing the plan"
+        )]
+        apply: bool,
+    },
+    #[command(
+        about = "Search OpenSubtitles by movie hash, IMDB id or title and download the best match next to the video"
+    )]
+    Fetch {
+        #[arg(help = "Video file path")]
+        video: String,
+        #[arg(
+            short,
+            long = "lang",
+            value_delimiter = ',',
+            help = "Preferred languages, e.g. zh-cn,en, default is opensubtitles.languages in the config or en"
+        )]
+        languages: Vec<String>,
+        #[arg(long, value_parser = parse_imdb_id, help = "IMDB id, e.g. tt0060176")]
+        imdb: Option<u32>,
+        #[arg(long, help = "Search by title as well as by movie hash")]
+        query: Option<String>,
+        #[arg(long, help = "Only list the search results without downloading")]
+        list: bool,
+        #[arg(long, help = "Overwrite an existing subtitle next to the video")]
+        force: bool,
+    },
+    #[command(
+        about = "Translate an SRT file with a local Ollama model, keeping indices and timings, writing <name>.<lang>.srt"
+    )]
+    Translate {
+        #[arg(help = "SRT file path")]
+        subtitle: PathBuf,
+        #[arg(long, help = "Target language, e.g. zh, ja or \"Traditional Chinese\"")]
+        to: String,
+        #[arg(long, help = "Source language, default is detected by the model")]
+        from: Option<String>,
+        #[arg(short, long, help = "Output SRT file, must not exist")]
+        output: Option<PathBuf>,
+        #[arg(
+            long,
+            help = "Ollama model, default is ollama.model in the config or gemma3n:e4b"
+        )]
+        model: Option<String>,
+        #[arg(
+            long,
+            default_value_t = 20,
+            help = "Number of cues translated per request"
+        )]
+        batch: usize,
+        #[arg(
+            long,
+            default_value_t = 3,
+            help = "Number of neighbouring cues sent before and after each batch as context"
+        )]
+        context: usize,
+        #[arg(
+            long,
+            default_value_t = 2,
+            help = "Retries when the model does not return one translation per cue"
+        )]
+        retries: usize,
+    },
 }
 
 #[tokio::main]
@@ -96,33 +489,225 @@ async fn main() -> Result<(), Box<dyn error::Error>> {
 
     match &cli.commands {
         Commands::Tracker(tracker_args) => match &tracker_args.commands {
-            TrackerCommands::Update {} => download_newest_tracker().await?,
+            TrackerCommands::Update {
+                output,
+                format,
+                sources,
+            } => {
+                let sources = if sources.is_empty() {
+                    configured_sources(&config().tracker.sources)
+                } else {
+                    sources.clone()
+                };
+                let tracker_config = &config().tracker;
+                let policy = TrackerPolicy::new(
+                    &tracker_config.allow,
+                    &tracker_config.deny,
+                    &tracker_config.order,
+                )?;
+                let format = format.unwrap_or(tracker_config.format);
+                download_newest_tracker(&sources, &policy, output, format).await?
+            }
+            TrackerCommands::Check {
+                input,
+                output,
+                timeout,
+                jobs,
+            } => {
+                let options = CheckOptions {
+                    timeout: Duration::from_secs(*timeout),
+                    jobs: *jobs,
+                };
+                check_tracker_file(input, output, options).await?
+            }
+            TrackerCommands::Push {
+                input,
+                clients,
+                skip_existing,
+            } => push_trackers(&config().clients, clients, input, !*skip_existing).await?,
         },
-        Commands::Sub(sub_args) => match &sub_args.commands {
-            SubCommands::Incr {
+        Commands::Torrent(torrent_args) => match &torrent_args.commands {
+            TorrentCommands::Info { file_name, format } => {
+                show_torrent_info(file_name, format.unwrap_or(OutputFormat::List)).await?
+            }
+            TorrentCommands::AddTrackers {
+                input,
+                trackers,
+                output,
+            } => add_trackers(input, trackers, output.as_deref()).await?,
+            TorrentCommands::Magnet {
                 file_name,
-                ms,
-                overlap_mode,
-            } => update_srt_time(file_name, *ms, overlap_mode.clone()),
-            SubCommands::ExportSub {
+                trackers,
+                no_trackers,
+            } => {
+                let trackers = (!*no_trackers).then_some(trackers.as_path());
+                println!("{}", torrent_to_magnet(file_name, trackers).await?)
+            }
+            TorrentCommands::Create {
+                path,
+                output,
+                mode,
+                piece_size,
+                private,
+                comment,
+                source,
+                web_seeds,
+                announce,
+                trackers,
+                no_trackers,
+                jobs,
+            } => {
+                let options = CreateOptions {
+                    version: *mode,
+                    piece_length: *piece_size,
+                    private: *private,
+                    comment: comment.clone(),
+                    source: source.clone(),
+                    web_seeds: web_seeds.clone(),
+                    trackers: announce.clone(),
+                    tracker_file: (!*no_trackers).then(|| trackers.clone()),
+                    jobs: jobs.unwrap_or_else(default_jobs),
+                };
+                create_torrent_file(path, output.as_deref(), &options).await?;
+            }
+            TorrentCommands::Verify {
                 file_name,
-                output_path,
-            } => extract_sub_srt(file_name, output_path)
-                .await
-                .expect("Failed to extract the subtitle stream from media file"),
-            SubCommands::ListSubStream { file_name, format } => {
-                list_all_subtitle_stream(file_name, format.unwrap_or(OutputFormat::List).clone())
+                dir,
+                jobs,
+            } => verify_torrent_file(file_name, dir, jobs.unwrap_or_else(default_jobs)).await?,
+        },
+        Commands::Sub(sub_args) => {
+            let jobs = sub_args.jobs.unwrap_or_else(default_jobs);
+            match &sub_args.commands {
+                SubCommands::Incr {
+                    file_name,
+                    ms,
+                    overlap_mode,
+                } => update_srt_time_batch(file_name, *ms, overlap_mode.clone(), jobs).await?,
+                SubCommands::ExportSub {
+                    file_name,
+                    output_path,
+                } => extract_sub_srt_batch(file_name, output_path, jobs).await?,
+                SubCommands::ListSubStream { file_name, format } => {
+                    list_all_subtitle_stream_batch(
+                        file_name,
+                        format.unwrap_or(OutputFormat::List),
+                        jobs,
+                    )
+                    .await?
+                }
+                SubCommands::CmpTwoSrt {
+                    srt_1,
+                    srt_2,
+                    interactive,
+                } => compare_two_srt_file(srt_1.clone(), srt_2.clone(), *interactive)
                     .await
-                    .expect("Failed to retrieve the information about subtitle stream")
+                    .expect("Failed to compare the two srt files"),
+                SubCommands::Burn {
+                    video,
+                    subtitle,
+                    output,
+                    font,
+                    size,
+                    outline,
+                    margin,
+                    position,
+                    preset,
+                } => {
+                    let style = BurnStyle {
+                        font: font.clone(),
+                        size: *size,
+                        outline: *outline,
+                        margin: *margin,
+                        position: *position,
+                    };
+                    burn_subtitle(video, subtitle, output, &style, *preset).await?
+                }
+                SubCommands::Snap {
+                    video,
+                    subtitle,
+                    threshold,
+                    tolerance,
+                    min_duration,
+                } => {
+                    let options = SnapOptions {
+                        tolerance: Duration::from_millis(*tolerance),
+                        min_duration: Duration::from_millis(*min_duration),
+                    };
+                    snap_srt_to_scene_cuts(video, subtitle, *threshold, options).await?
+                }
+                SubCommands::Clip {
+                    video,
+                    subtitle,
+                    entries,
+                    kind,
+                    padding,
+                    output,
+                    anki,
+                } => {
+                    let options = ClipOptions {
+                        kind: *kind,
+                        padding: Duration::from_millis(*padding),
+                        output: output
+                            .clone()
+                            .unwrap_or_else(|| ClipOptions::default_output(Path::new(subtitle))),
+                        anki: *anki,
+                    };
+                    clip_entries(video, subtitle, entries, &options).await?
+                }
+                SubCommands::Match { dir, apply } => match_library(dir, *apply)?,
+                SubCommands::Fetch {
+                    video,
+                    languages,
+                    imdb,
+                    query,
+                    list,
+                    force,
+                } => {
+                    let languages = if !languages.is_empty() {
+                        languages.clone()
+                    } else if !config().opensubtitles.languages.is_empty() {
+                        config().opensubtitles.languages.clone()
+                    } else {
+                        vec!["en".to_string()]
+                    };
+                    let options = FetchOptions {
+                        imdb_id: *imdb,
+                        query: query.clone(),
+                        languages,
+                        list: *list,
+                        force: *force,
+                    };
+                    fetch_subtitle(video, &options).await?;
+                }
+                SubCommands::Translate {
+                    subtitle,
+                    to,
+                    from,
+                    output,
+                    model,
+                    batch,
+                    context,
+                    retries,
+                } => {
+                    let options = TranslateOptions {
+                        to: to.clone(),
+                        from: from.clone(),
+                        batch_size: *batch,
+                        context: *context,
+                        retries: *retries,
+                    };
+                    translate_srt(subtitle, output.as_deref(), model.clone(), &options).await?;
+                }
+            }
+        }
+        Commands::Media(media_args) => match &media_args.commands {
+            MediaCommands::Info { file_name, format } => {
+                show_media_info(file_name, format.unwrap_or(OutputFormat::List)).await?
             }
-            SubCommands::CmpTwoSrt {
-                srt_1,
-                srt_2,
-                interactive,
-            } => compare_two_srt_file(srt_1.clone(), srt_2.clone(), *interactive)
-                .await
-                .expect("Failed to compare the two srt files"),
         },
+        Commands::Doctor => run_doctor().await,
+        Commands::Daemon { once } => run_daemon(config().clone(), *once).await?,
     }
     Ok(())
 }
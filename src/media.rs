@@ -0,0 +1,459 @@
+//! media module
+//!
+//! 基于 ffprobe 的完整媒体信息查询：容器、视频/音频/字幕流以及章节
+
+use std::path::Path;
+
+use prettytable::{Cell, Row, Table};
+use serde::Serialize;
+
+use crate::{
+    common::OutputFormat,
+    ffmpeg::probe::{CodecType, ProbeOutput, ProbeStream, probe_file},
+};
+
+/// 媒体文件信息的汇总，用于展示
+#[derive(Debug, Clone, Serialize)]
+pub struct MediaInfo {
+    pub file: String,
+    /// 封装格式
+    pub container: String,
+    /// 时长，单位秒
+    pub duration: Option<f64>,
+    /// 文件大小，单位字节
+    pub size: Option<u64>,
+    /// 总码率，单位 bit/s
+    pub bit_rate: Option<u64>,
+    pub video: Vec<VideoInfo>,
+    pub audio: Vec<AudioInfo>,
+    pub subtitles: Vec<SubtitleInfo>,
+    pub chapters: Vec<ChapterInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VideoInfo {
+    pub index: u32,
+    pub codec: Option<String>,
+    pub profile: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub frame_rate: Option<f64>,
+    pub pix_fmt: Option<String>,
+    pub bit_rate: Option<u64>,
+    /// HDR 格式，SDR 视频为 `None`
+    pub hdr: Option<String>,
+    pub color_primaries: Option<String>,
+    pub color_transfer: Option<String>,
+    /// 最大内容亮度 MaxCLL，单位 nit
+    pub max_cll: Option<u32>,
+    /// 最大帧平均亮度 MaxFALL，单位 nit
+    pub max_fall: Option<u32>,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    pub disposition: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AudioInfo {
+    pub index: u32,
+    pub codec: Option<String>,
+    pub profile: Option<String>,
+    pub channels: Option<u32>,
+    pub channel_layout: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub bit_rate: Option<u64>,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    pub disposition: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SubtitleInfo {
+    pub index: u32,
+    pub codec: Option<String>,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    pub disposition: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChapterInfo {
+    pub id: i64,
+    /// 起始时间，单位秒
+    pub start: Option<f64>,
+    /// 结束时间，单位秒
+    pub end: Option<f64>,
+    pub title: Option<String>,
+}
+
+impl From<ProbeOutput> for MediaInfo {
+    fn from(output: ProbeOutput) -> Self {
+        let format = output.format.unwrap_or_default();
+        let mut info = MediaInfo {
+            file: format.filename.clone(),
+            container: format
+                .format_long_name
+                .clone()
+                .unwrap_or_else(|| format.format_name.clone()),
+            duration: format.duration().map(|d| d.as_secs_f64()),
+            size: format.size(),
+            bit_rate: format.bit_rate(),
+            video: Vec::new(),
+            audio: Vec::new(),
+            subtitles: Vec::new(),
+            chapters: Vec::new(),
+        };
+        for stream in &output.streams {
+            match stream.codec_type {
+                // 封面图片也是视频流，但不是正片内容
+                CodecType::Video if !stream.disposition.attached_pic => {
+                    info.video.push(VideoInfo::from(stream))
+                }
+                CodecType::Audio => info.audio.push(AudioInfo::from(stream)),
+                CodecType::Subtitle => info.subtitles.push(SubtitleInfo::from(stream)),
+                _ => (),
+            }
+        }
+        info.chapters = output
+            .chapters
+            .iter()
+            .map(|chapter| ChapterInfo {
+                id: chapter.id,
+                start: chapter.start().map(|d| d.as_secs_f64()),
+                end: chapter.end().map(|d| d.as_secs_f64()),
+                title: chapter.title().map(String::from),
+            })
+            .collect();
+        info
+    }
+}
+
+impl From<&ProbeStream> for VideoInfo {
+    fn from(stream: &ProbeStream) -> Self {
+        let light_level = stream.content_light_level();
+        VideoInfo {
+            index: stream.index,
+            codec: stream.codec_name.clone(),
+            profile: stream.profile.clone(),
+            width: stream.width,
+            height: stream.height,
+            frame_rate: stream.frame_rate(),
+            pix_fmt: stream.pix_fmt.clone(),
+            bit_rate: stream.bit_rate(),
+            hdr: stream.hdr_format().map(String::from),
+            color_primaries: stream.color_primaries.clone(),
+            color_transfer: stream.color_transfer.clone(),
+            max_cll: light_level.and_then(|l| l.max_content),
+            max_fall: light_level.and_then(|l| l.max_average),
+            language: stream.language().map(String::from),
+            title: stream.title().map(String::from),
+            disposition: flags(stream),
+        }
+    }
+}
+
+impl From<&ProbeStream> for AudioInfo {
+    fn from(stream: &ProbeStream) -> Self {
+        AudioInfo {
+            index: stream.index,
+            codec: stream.codec_name.clone(),
+            profile: stream.profile.clone(),
+            channels: stream.channels,
+            channel_layout: stream.channel_layout.clone(),
+            sample_rate: stream.sample_rate(),
+            bit_rate: stream.bit_rate(),
+            language: stream.language().map(String::from),
+            title: stream.title().map(String::from),
+            disposition: flags(stream),
+        }
+    }
+}
+
+impl From<&ProbeStream> for SubtitleInfo {
+    fn from(stream: &ProbeStream) -> Self {
+        SubtitleInfo {
+            index: stream.index,
+            codec: stream.codec_name.clone(),
+            language: stream.language().map(String::from),
+            title: stream.title().map(String::from),
+            disposition: flags(stream),
+        }
+    }
+}
+
+fn flags(stream: &ProbeStream) -> Vec<String> {
+    stream
+        .disposition
+        .flags()
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+/// 读取媒体文件的完整信息，并按照指定的格式打印
+pub async fn show_media_info(
+    file: impl AsRef<Path>,
+    format: OutputFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let file_path = file.as_ref();
+    if !file_path.exists() {
+        return Err(format!("文件不存在: {}", file_path.display()).into());
+    }
+    let info = MediaInfo::from(probe_file(file_path).await?);
+
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&info)?),
+        OutputFormat::Table => print_table(&info),
+        OutputFormat::List => print_list(&info),
+    }
+    Ok(())
+}
+
+fn na<T: ToString>(value: &Option<T>) -> String {
+    value
+        .as_ref()
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| "N/A".to_string())
+}
+
+fn secs(value: Option<f64>) -> String {
+    value
+        .map(|v| format!("{:.3}s", v))
+        .unwrap_or_else(|| "N/A".to_string())
+}
+
+fn resolution(video: &VideoInfo) -> String {
+    match (video.width, video.height) {
+        (Some(w), Some(h)) => format!("{}x{}", w, h),
+        _ => "N/A".to_string(),
+    }
+}
+
+fn fps(video: &VideoInfo) -> String {
+    video
+        .frame_rate
+        .map(|f| format!("{:.3}", f))
+        .unwrap_or_else(|| "N/A".to_string())
+}
+
+fn print_list(info: &MediaInfo) {
+    println!(
+        "File({}) Container({}) Duration({}) Size({}) BitRate({})",
+        info.file,
+        info.container,
+        secs(info.duration),
+        na(&info.size),
+        na(&info.bit_rate)
+    );
+    for v in &info.video {
+        println!(
+            "Video Index({}) Codec({}) Profile({}) Resolution({}) FPS({}) PixFmt({}) HDR({}) MaxCLL({}) MaxFALL({}) Language({}) Flags({})",
+            v.index,
+            na(&v.codec),
+            na(&v.profile),
+            resolution(v),
+            fps(v),
+            na(&v.pix_fmt),
+            v.hdr.as_deref().unwrap_or("SDR"),
+            na(&v.max_cll),
+            na(&v.max_fall),
+            na(&v.language),
+            v.disposition.join(",")
+        );
+    }
+    for a in &info.audio {
+        println!(
+            "Audio Index({}) Codec({}) Channels({}) Layout({}) SampleRate({}) BitRate({}) Language({}) Title({}) Flags({})",
+            a.index,
+            na(&a.codec),
+            na(&a.channels),
+            na(&a.channel_layout),
+            na(&a.sample_rate),
+            na(&a.bit_rate),
+            na(&a.language),
+            na(&a.title),
+            a.disposition.join(",")
+        );
+    }
+    for s in &info.subtitles {
+        println!(
+            "Subtitle Index({}) Codec({}) Language({}) Title({}) Flags({})",
+            s.index,
+            na(&s.codec),
+            na(&s.language),
+            na(&s.title),
+            s.disposition.join(",")
+        );
+    }
+    for c in &info.chapters {
+        println!(
+            "Chapter Id({}) Start({}) End({}) Title({})",
+            c.id,
+            secs(c.start),
+            secs(c.end),
+            na(&c.title)
+        );
+    }
+}
+
+fn print_table(info: &MediaInfo) {
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("File"),
+        Cell::new("Container"),
+        Cell::new("Duration"),
+        Cell::new("Size"),
+        Cell::new("Bit Rate"),
+    ]));
+    table.add_row(Row::new(vec![
+        Cell::new(&info.file),
+        Cell::new(&info.container),
+        Cell::new(&secs(info.duration)),
+        Cell::new(&na(&info.size)),
+        Cell::new(&na(&info.bit_rate)),
+    ]));
+    table.printstd();
+
+    if !info.video.is_empty() {
+        let mut table = Table::new();
+        table.add_row(Row::new(
+            [
+                "Index",
+                "Codec",
+                "Profile",
+                "Resolution",
+                "FPS",
+                "Pix Fmt",
+                "HDR",
+                "MaxCLL",
+                "MaxFALL",
+                "Language",
+                "Flags",
+            ]
+            .iter()
+            .map(|h| Cell::new(h))
+            .collect(),
+        ));
+        for v in &info.video {
+            table.add_row(Row::new(vec![
+                Cell::new(&v.index.to_string()),
+                Cell::new(&na(&v.codec)),
+                Cell::new(&na(&v.profile)),
+                Cell::new(&resolution(v)),
+                Cell::new(&fps(v)),
+                Cell::new(&na(&v.pix_fmt)),
+                Cell::new(v.hdr.as_deref().unwrap_or("SDR")),
+                Cell::new(&na(&v.max_cll)),
+                Cell::new(&na(&v.max_fall)),
+                Cell::new(&na(&v.language)),
+                Cell::new(&v.disposition.join(",")),
+            ]));
+        }
+        table.printstd();
+    }
+
+    if !info.audio.is_empty() {
+        let mut table = Table::new();
+        table.add_row(Row::new(
+            [
+                "Index",
+                "Codec",
+                "Channels",
+                "Layout",
+                "Sample Rate",
+                "Bit Rate",
+                "Language",
+                "Title",
+                "Flags",
+            ]
+            .iter()
+            .map(|h| Cell::new(h))
+            .collect(),
+        ));
+        for a in &info.audio {
+            table.add_row(Row::new(vec![
+                Cell::new(&a.index.to_string()),
+                Cell::new(&na(&a.codec)),
+                Cell::new(&na(&a.channels)),
+                Cell::new(&na(&a.channel_layout)),
+                Cell::new(&na(&a.sample_rate)),
+                Cell::new(&na(&a.bit_rate)),
+                Cell::new(&na(&a.language)),
+                Cell::new(&na(&a.title)),
+                Cell::new(&a.disposition.join(",")),
+            ]));
+        }
+        table.printstd();
+    }
+
+    if !info.subtitles.is_empty() {
+        let mut table = Table::new();
+        table.add_row(Row::new(
+            ["Index", "Codec", "Language", "Title", "Flags"]
+                .iter()
+                .map(|h| Cell::new(h))
+                .collect(),
+        ));
+        for s in &info.subtitles {
+            table.add_row(Row::new(vec![
+                Cell::new(&s.index.to_string()),
+                Cell::new(&na(&s.codec)),
+                Cell::new(&na(&s.language)),
+                Cell::new(&na(&s.title)),
+                Cell::new(&s.disposition.join(",")),
+            ]));
+        }
+        table.printstd();
+    }
+
+    if !info.chapters.is_empty() {
+        let mut table = Table::new();
+        table.add_row(Row::new(
+            ["Id", "Start", "End", "Title"]
+                .iter()
+                .map(|h| Cell::new(h))
+                .collect(),
+        ));
+        for c in &info.chapters {
+            table.add_row(Row::new(vec![
+                Cell::new(&c.id.to_string()),
+                Cell::new(&secs(c.start)),
+                Cell::new(&secs(c.end)),
+                Cell::new(&na(&c.title)),
+            ]));
+        }
+        table.printstd();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_media_info_from_probe_output() {
+        let output: ProbeOutput = serde_json::from_str(
+            r#"{
+                "streams": [
+                    { "index": 0, "codec_type": "video", "codec_name": "h264", "width": 1920, "height": 1080, "avg_frame_rate": "25/1" },
+                    { "index": 1, "codec_type": "audio", "codec_name": "aac", "channels": 2, "sample_rate": "44100", "tags": { "language": "jpn" } },
+                    { "index": 2, "codec_type": "subtitle", "codec_name": "ass", "disposition": { "forced": 1 } },
+                    { "index": 3, "codec_type": "video", "codec_name": "mjpeg", "disposition": { "attached_pic": 1 } }
+                ],
+                "chapters": [ { "id": 1, "start_time": "0.000000", "end_time": "60.500000" } ],
+                "format": { "filename": "a.mp4", "format_name": "mov,mp4,m4a,3gp,3g2,mj2", "duration": "120.000000" }
+            }"#,
+        )
+        .unwrap();
+        let info = MediaInfo::from(output);
+        assert_eq!(info.container, "mov,mp4,m4a,3gp,3g2,mj2");
+        assert_eq!(info.duration, Some(120.0));
+        assert_eq!(info.video.len(), 1);
+        assert_eq!(resolution(&info.video[0]), "1920x1080");
+        assert_eq!(info.video[0].hdr, None);
+        assert_eq!(info.audio[0].sample_rate, Some(44100));
+        assert_eq!(info.audio[0].language.as_deref(), Some("jpn"));
+        assert_eq!(info.subtitles[0].disposition, vec!["forced"]);
+        assert_eq!(info.chapters[0].end, Some(60.5));
+    }
+}
@@ -3,32 +3,82 @@
 //! 关于字幕处理的一些方法
 //!
 //! 1.对于srt类型的文件，调整其时间
+//! 2.将字幕烧录到视频画面中
+//! 3.将字幕的起止时间吸附到镜头切换点上
+//! 4.按字幕条目截取音频片段或画面，导出为 Anki 卡片
+//! 5.在媒体库中为字幕匹配视频并按播放器的命名规范重命名
+//! 6.通过视频哈希从 OpenSubtitles 搜索并下载字幕
+//! 7.使用本地大语言模型翻译字幕
 
+mod burn;
+mod clip;
+mod matcher;
+mod provider;
 mod srt;
+mod translate;
 
-use clap::ValueEnum;
+pub use burn::{BurnStyle, EncodePreset, SubPosition, burn_subtitle};
+pub use clip::{
+    Clip, ClipError, ClipKind, ClipOptions, EntrySelection, clip_entries, clip_entries_with,
+};
+pub use matcher::{
+    MatchOutcome, MediaName, SUBTITLE_EXTENSIONS, match_library, match_subtitles, plan_library,
+};
 use prettytable::Cell;
 use prettytable::Row;
 use prettytable::Table;
-use serde::Deserialize;
+pub use provider::{
+    API_KEY_ENV, DEFAULT_BASE_URL, DownloadedSubtitle, FetchOptions, HASH_CHUNK_SIZE,
+    OpenSubtitlesClient, ProviderError, SubtitleCandidate, SubtitleProvider, SubtitleQuery,
+    fetch_subtitle, fetch_subtitle_with, format_movie_hash, movie_hash, parse_imdb_id,
+    rank_candidates, subtitle_path,
+};
 use serde::Serialize;
 pub use srt::OverlapFixMode;
 pub use srt::SrtFile;
+pub use srt::{SnapOptions, SnapReport};
 use tokio::io::AsyncBufReadExt;
 use tokio::io::BufReader;
 use tokio::io::stdin;
+pub use translate::{
+    TranslateError, TranslateOptions, build_prompt, parse_translations, translate_entries,
+    translate_srt, translate_srt_with, translated_path,
+};
 
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
 use std::io::Cursor;
-use std::{fs::File, path::Path};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
 
+pub use crate::common::OutputFormat;
 use crate::common::read_multiple_file_to_string;
 use crate::{
-    common::same_path_with,
-    ffmpeg::{FfmpegError, FfmpegTool},
+    batch::{SRT_EXTENSIONS, VIDEO_EXTENSIONS, collect_inputs, is_batch_input, run_batch},
+    common::{ProcessRunner, SystemRunner, same_path_with},
+    ffmpeg::{
+        FfmpegError, FfmpegTool,
+        probe::{CodecType, ProbeOutput, ProbeStream},
+        scene::scene_cuts,
+    },
 };
 
+/// `incr` 生成的文件名后缀
+const MOD_SUFFIX: &str = "mod";
+
 /// 将 file 视频容器中的字幕流以srt文件的格式提取到 sub 路径中
 pub async fn extract_sub_srt<P: AsRef<Path>>(file: P, sub: P) -> Result<(), FfmpegError> {
+    extract_sub_srt_with(&SystemRunner, file, sub).await
+}
+
+/// 与 [`extract_sub_srt`] 相同，但是通过指定的 `runner` 执行 ffmpeg
+pub async fn extract_sub_srt_with<R: ProcessRunner, P: AsRef<Path>>(
+    runner: &R,
+    file: P,
+    sub: P,
+) -> Result<(), FfmpegError> {
     let options = vec![
         "-i",
         file.as_ref().to_str().unwrap_or(""),
@@ -39,66 +89,139 @@ pub async fn extract_sub_srt<P: AsRef<Path>>(file: P, sub: P) -> Result<(), Ffmp
         sub.as_ref().to_str().unwrap_or(""),
     ];
     FfmpegTool::Ffmpeg
-        .exec_with_options(None::<&'static str>, Some(options))
+        .exec_with_runner(runner, None::<&'static str>, Some(options))
         .await?;
     Ok(())
 }
 
-/// 更新srt字幕文件 `file` 中的所有时间戳向前或向后移动 `ms` 毫秒
-/// # Panic
-/// 如果移动后的结果超过时间范围则程序退出，不生成修改后的文件
-pub fn update_srt_time<P: AsRef<Path>>(file: P, ms: i64, mode: OverlapFixMode) {
+/// 批量导出字幕流，`input` 可以是视频文件、目录或者 glob 模式，最多同时执行 `jobs` 个 ffmpeg
+///
+/// 批量模式下 `output` 是输出目录，输入为目录时保留视频相对于该目录的子目录结构，
+/// 每个视频导出为同名的 `.srt` 文件
+pub async fn extract_sub_srt_batch(
+    input: &str,
+    output: &str,
+    jobs: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !is_batch_input(input) {
+        extract_sub_srt(input, output).await?;
+        return Ok(());
+    }
+    let files = collect_inputs(input, VIDEO_EXTENSIONS)?;
+    let root = Path::new(input);
+    let output = Path::new(output);
+    let summary = run_batch(files, jobs, |file| {
+        let relative = match file.strip_prefix(root) {
+            Ok(relative) if root.is_dir() => relative.to_path_buf(),
+            _ => PathBuf::from(file.file_name().unwrap_or_default()),
+        };
+        let sub = output.join(relative).with_extension("srt");
+        async move {
+            if let Some(parent) = sub.parent() {
+                tokio::fs::create_dir_all(parent)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            extract_sub_srt(file, sub.clone())
+                .await
+                .map(|_| sub)
+                .map_err(|e| e.to_string())
+        }
+    })
+    .await;
+    summary.print();
+    Ok(summary.into_result()?)
+}
+
+/// 更新srt字幕文件 `file` 中的所有时间戳向前或向后移动 `ms` 毫秒，结果写入同目录下的 `<name>_mod.srt`，
+/// 返回新文件的路径
+/// # Error
+/// 如果移动后的结果超过时间范围则返回错误，不生成修改后的文件
+pub fn update_srt_time<P: AsRef<Path>>(
+    file: P,
+    ms: i64,
+    mode: OverlapFixMode,
+) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
     let p = file.as_ref();
-    let new_file = same_path_with(p, "mod", "_").expect("new file name error");
-    let f = File::open(p).expect("failed to open the subtitle file");
-    let mut srt_file = SrtFile::read(f).expect("failed to parse the srt file");
-    srt_file
-        .adjust_timestamps(ms, mode)
-        .expect("failed to adjust the timestamps");
-    let mut nf = File::create(new_file).expect("failed to create the new srt file");
-    srt_file
-        .write(&mut nf)
-        .expect("failed to write content to new srt file");
+    let new_file = same_path_with(p, MOD_SUFFIX, "_")?;
+    let f = File::open(p)?;
+    let mut srt_file = SrtFile::read(f)?;
+    srt_file.adjust_timestamps(ms, mode)?;
+    let mut nf = File::create(&new_file)?;
+    srt_file.write(&mut nf)?;
+    Ok(new_file)
 }
 
-/// 视频流的顶层结构体，用于解析 ffprobe 的 JSON 输出。
+/// 批量调整时间戳，`input` 可以是字幕文件、目录或者 glob 模式，最多同时处理 `jobs` 个文件
 ///
-/// ffprobe -show_streams -select_streams s 命令的输出格式为：
-/// {
-///   "streams": [
-///     { ... }, // 字幕流 1
-///     { ... }  // 字幕流 2
-///   ]
-/// }
-#[derive(Debug, Deserialize, Serialize)]
-struct FfprobeOutput {
-    streams: Vec<FfprobeStream>,
+/// 批量模式下会跳过之前生成的 `<name>_mod.srt` 文件
+pub async fn update_srt_time_batch(
+    input: &str,
+    ms: i64,
+    mode: OverlapFixMode,
+    jobs: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !is_batch_input(input) {
+        return update_srt_time(input, ms, mode)
+            .map(|_| ())
+            .map_err(|e| e as Box<dyn std::error::Error>);
+    }
+    let files: Vec<PathBuf> = collect_inputs(input, SRT_EXTENSIONS)?
+        .into_iter()
+        .filter(|f| {
+            !f.file_stem()
+                .is_some_and(|s| s.to_string_lossy().ends_with(&format!("_{}", MOD_SUFFIX)))
+        })
+        .collect();
+    let summary = run_batch(files, jobs, |file| {
+        let mode = mode.clone();
+        async move {
+            match tokio::task::spawn_blocking(move || update_srt_time(file, ms, mode)).await {
+                Ok(result) => result.map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            }
+        }
+    })
+    .await;
+    summary.print();
+    Ok(summary.into_result()?)
 }
 
-/// 单个流的详细信息
-#[derive(Debug, Deserialize, Serialize)]
-struct FfprobeStream {
-    /// 流的索引，从 0 开始。
-    index: u32,
-    /// 编解码器类型
-    codec_type: String,
-    /// 编解码器名称
-    codec_name: String,
-    // 开始时间
-    start_time: String,
-    // 持续时间，单位ms
-    duration_ts: u32,
-    /// 元数据标签，其中可能包含语言信息
-    tags: Option<FfprobeTags>,
-}
+/// 将字幕文件 `file` 中条目的起止时间吸附到视频 `video` 的镜头切换点上，
+/// 结果写入同目录下的 `<name>_snap.srt` 文件，并打印被移动的条目数
+///
+/// 镜头切换点的检测结果会被缓存，同一个视频和阈值只需要检测一次
+pub async fn snap_srt_to_scene_cuts(
+    video: impl AsRef<Path>,
+    file: impl AsRef<Path>,
+    threshold: f64,
+    options: SnapOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !(threshold > 0.0 && threshold < 1.0) {
+        return Err(format!("场景阈值必须在 0 和 1 之间: {}", threshold).into());
+    }
+    let (video, file) = (video.as_ref(), file.as_ref());
+    for path in [video, file] {
+        if !path.exists() {
+            return Err(format!("文件不存在: {}", path.display()).into());
+        }
+    }
+    let mut srt_file = SrtFile::read(File::open(file)?)?;
+    let cuts = scene_cuts(video, threshold).await?;
+    let report = srt_file.snap_to_cuts(&cuts, options);
 
-/// 流的标签信息，用于获取语言等元数据
-#[derive(Debug, Deserialize, Serialize)]
-struct FfprobeTags {
-    /// 字幕语言，如 "chi" (中文), "eng" (英文) 等
-    language: Option<String>,
-    /// 标题或流名称
-    title: Option<String>,
+    let new_file = same_path_with(file, "snap", "_")?;
+    srt_file.write(&mut File::create(&new_file)?)?;
+    println!(
+        "{} scene cuts, moved {} of {} cues ({} starts, {} ends), written to {}",
+        cuts.len(),
+        report.moved,
+        srt_file.len(),
+        report.starts,
+        report.ends,
+        new_file.display()
+    );
+    Ok(())
 }
 
 /// 最终返回给调用者的字幕流信息结构体
@@ -108,24 +231,62 @@ pub struct SubtitleStreamInfo {
     pub index: u32,
     /// 编解码器名称
     pub codec_name: String,
-    /// 持续时间，单位ms
-    pub duration: u32,
+    /// 持续时间，单位ms，ffprobe 无法确定时长时为 `None`
+    pub duration: Option<u64>,
     /// 语言标签，如果存在的话
     pub language: Option<String>,
     /// 标题标签，如果存在的话
     pub title: Option<String>,
 }
 
-/// 定义输出格式的枚举类型。
-#[derive(Debug, Clone, Copy, ValueEnum)]
-pub enum OutputFormat {
-    /// 输出为 JSON 格式
-    Json,
-    /// 输出为表格格式
-    #[clap(name = "tab")]
-    Table,
-    /// 输出为列表格式
-    List,
+impl From<&ProbeStream> for SubtitleStreamInfo {
+    fn from(stream: &ProbeStream) -> Self {
+        SubtitleStreamInfo {
+            index: stream.index,
+            codec_name: stream
+                .codec_name
+                .clone()
+                .unwrap_or_else(|| "unknown".to_string()),
+            duration: stream
+                .duration()
+                .map(|d| u64::try_from(d.as_millis()).unwrap_or(u64::MAX)),
+            language: stream.language().map(String::from),
+            title: stream.title().map(String::from),
+        }
+    }
+}
+
+/// 使用 ffprobe 读取视频文件中所有的字幕流信息，`runner` 用于执行 ffprobe
+pub async fn subtitle_streams_with<R: ProcessRunner>(
+    runner: &R,
+    file: impl AsRef<Path>,
+) -> Result<Vec<SubtitleStreamInfo>, FfmpegError> {
+    let args = vec![
+        OsStr::new("-v"),
+        OsStr::new("quiet"),
+        OsStr::new("-print_format"),
+        OsStr::new("json"),
+        OsStr::new("-show_streams"),
+        OsStr::new("-select_streams"),
+        OsStr::new("s"),
+        OsStr::new("--"), // 分隔符，将选项与文件名分开
+        file.as_ref().as_os_str(),
+    ];
+    let (stdout, _) = FfmpegTool::Ffprobe
+        .exec_with_runner(runner, None::<&'static str>, Some(args))
+        .await?;
+
+    // 如果 ffprobe 输出为空，说明没有找到字幕流。
+    if stdout.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let output: ProbeOutput = serde_json::from_str(&stdout)?;
+    Ok(output
+        .streams
+        .iter()
+        .filter(|stream| stream.codec_type == CodecType::Subtitle)
+        .map(SubtitleStreamInfo::from)
+        .collect())
 }
 
 /// 异步函数，列出视频文件中所有的字幕流信息并直接打印。
@@ -157,47 +318,63 @@ pub async fn list_all_subtitle_stream(
         return Err(format!("文件不存在: {}", file_path.display()).into());
     }
 
-    // 1. 构建 ffprobe 命令
-    let mut args: Vec<String> = vec![
-        "-v".to_string(),
-        "quiet".to_string(),
-        "-print_format".to_string(),
-        "json".to_string(),
-        "-show_streams".to_string(),
-        "-select_streams".to_string(),
-        "s".to_string(),
-        "--".to_string(), // 分隔符，将选项与文件名分开
-    ];
-    args.push(file_path.to_string_lossy().to_string());
-
-    // 2. 调用外部 ffprobe 工具并获取标准输出
-    // 假设你有一个名为 `run_external_command` 的异步函数来执行此操作。
-    // 该函数应返回一个 `Result<(String, String), Box<dyn std::error::Error>>`。
-    let (stdout, _) = FfmpegTool::Ffprobe
-        .exec_with_options(None::<&'static str>, Some(args))
-        .await?;
-
-    // 如果 ffprobe 输出为空，说明没有找到字幕流。
-    if stdout.is_empty() {
+    let subtitle_streams = subtitle_streams_with(&SystemRunner, file_path).await?;
+    if subtitle_streams.is_empty() {
         println!("未找到任何字幕流。");
         return Ok(());
     }
 
-    // 3. 解析 JSON 输出
-    let output: FfprobeOutput = serde_json::from_str(&stdout)?;
-    let subtitle_streams: Vec<SubtitleStreamInfo> = output
-        .streams
-        .into_iter()
-        .map(|stream| SubtitleStreamInfo {
-            index: stream.index,
-            codec_name: stream.codec_name,
-            language: stream.tags.as_ref().and_then(|tags| tags.language.clone()),
-            title: stream.tags.as_ref().and_then(|tags| tags.title.clone()),
-            duration: stream.duration_ts,
-        })
-        .collect();
+    print_subtitle_streams(subtitle_streams, format)
+}
+
+/// 批量列出字幕流，`input` 可以是视频文件、目录或者 glob 模式，最多同时执行 `jobs` 个 ffprobe
+///
+/// JSON 格式下输出一个以文件路径为键的对象，每个文件的处理结果在标准错误中汇总
+pub async fn list_all_subtitle_stream_batch(
+    input: &str,
+    format: OutputFormat,
+    jobs: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !is_batch_input(input) {
+        return list_all_subtitle_stream(input, format).await;
+    }
+    let files = collect_inputs(input, VIDEO_EXTENSIONS)?;
+    let summary = run_batch(files, jobs, |file| async move {
+        subtitle_streams_with(&SystemRunner, file).await
+    })
+    .await;
+
+    match format {
+        OutputFormat::Json => {
+            let all: BTreeMap<String, &Vec<SubtitleStreamInfo>> = summary
+                .outcomes
+                .iter()
+                .filter_map(|o| Some((o.path.display().to_string(), o.result.as_ref().ok()?)))
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&all)?);
+        }
+        OutputFormat::Table | OutputFormat::List => {
+            for outcome in &summary.outcomes {
+                if let Ok(streams) = &outcome.result {
+                    println!("{}:", outcome.path.display());
+                    if streams.is_empty() {
+                        println!("未找到任何字幕流。");
+                    } else {
+                        print_subtitle_streams(streams.clone(), format)?;
+                    }
+                }
+            }
+        }
+    }
+    summary.print();
+    Ok(summary.into_result()?)
+}
 
-    // 4. 根据指定的格式打印结果
+/// 按照指定的格式打印字幕流信息
+fn print_subtitle_streams(
+    subtitle_streams: Vec<SubtitleStreamInfo>,
+    format: OutputFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
     match format {
         OutputFormat::Json => {
             let json_output = serde_json::to_string_pretty(&subtitle_streams)?;
@@ -216,7 +393,12 @@ pub async fn list_all_subtitle_stream(
                 table.add_row(Row::new(vec![
                     Cell::new(&stream.index.to_string()),
                     Cell::new(&stream.codec_name),
-                    Cell::new(&stream.duration.to_string()),
+                    Cell::new(
+                        &stream
+                            .duration
+                            .map(|d| d.to_string())
+                            .unwrap_or_else(|| "N/A".to_string()),
+                    ),
                     Cell::new(&stream.language.unwrap_or_else(|| "N/A".to_string())),
                     Cell::new(&stream.title.unwrap_or_else(|| "N/A".to_string())),
                 ]));
@@ -226,10 +408,13 @@ pub async fn list_all_subtitle_stream(
         OutputFormat::List => {
             for stream in subtitle_streams {
                 println!(
-                    "Index({}) Codec Name({}) Duration({}ms) Language({}) Title({})",
+                    "Index({}) Codec Name({}) Duration({}) Language({}) Title({})",
                     stream.index,
                     stream.codec_name,
-                    stream.duration,
+                    stream
+                        .duration
+                        .map(|d| format!("{}ms", d))
+                        .unwrap_or_else(|| "N/A".to_string()),
                     stream.language.unwrap_or_else(|| "N/A".to_string()),
                     stream.title.unwrap_or_else(|| "N/A".to_string())
                 );
@@ -0,0 +1,232 @@
+//! burn module
+//! 将字幕烧录（硬字幕）到视频画面中
+//!
+//! 使用 ffmpeg 的 `subtitles` / `ass` 滤镜渲染字幕，视频统一使用软件编码器 libx264 重新编码，
+//! 不依赖任何硬件加速，音频流直接复制
+
+use std::path::Path;
+
+use clap::ValueEnum;
+use tokio::sync::mpsc;
+
+use crate::{
+    common::SystemRunner,
+    ffmpeg::{FfmpegError, FfmpegProgress, FfmpegTool, probe_duration, report_progress},
+};
+
+/// 字幕在画面中的位置
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SubPosition {
+    #[default]
+    Bottom,
+    Middle,
+    Top,
+}
+
+impl SubPosition {
+    /// 对应 ASS 样式中按小键盘布局的 `Alignment` 值
+    fn alignment(&self) -> u8 {
+        match self {
+            SubPosition::Bottom => 2,
+            SubPosition::Middle => 5,
+            SubPosition::Top => 8,
+        }
+    }
+}
+
+/// 编码预设，只使用 libx264 的软件编码参数
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EncodePreset {
+    /// 速度优先：veryfast / crf 23
+    Fast,
+    /// 速度和质量的平衡：medium / crf 20
+    #[default]
+    Balanced,
+    /// 质量优先：slow / crf 18
+    Quality,
+}
+
+impl EncodePreset {
+    fn x264_args(&self) -> [&'static str; 6] {
+        let (preset, crf) = match self {
+            EncodePreset::Fast => ("veryfast", "23"),
+            EncodePreset::Balanced => ("medium", "20"),
+            EncodePreset::Quality => ("slow", "18"),
+        };
+        ["-c:v", "libx264", "-preset", preset, "-crf", crf]
+    }
+}
+
+/// 烧录字幕时的样式设置，`None` 表示使用字幕文件或 libass 的默认值
+#[derive(Debug, Default, Clone)]
+pub struct BurnStyle {
+    /// 字体名称
+    pub font: Option<String>,
+    /// 字号
+    pub size: Option<u32>,
+    /// 描边宽度
+    pub outline: Option<f32>,
+    /// 距离画面上下边缘的垂直边距
+    pub margin: Option<u32>,
+    /// 字幕位置
+    pub position: SubPosition,
+}
+
+impl BurnStyle {
+    /// 生成 `force_style` 参数的内容，没有需要覆盖的样式时返回 `None`
+    fn force_style(&self) -> Option<String> {
+        let mut styles = Vec::new();
+        if let Some(font) = &self.font {
+            styles.push(format!("FontName={}", font));
+        }
+        if let Some(size) = self.size {
+            styles.push(format!("FontSize={}", size));
+        }
+        if let Some(outline) = self.outline {
+            styles.push(format!("Outline={}", outline));
+        }
+        if let Some(margin) = self.margin {
+            styles.push(format!("MarginV={}", margin));
+        }
+        if self.position != SubPosition::Bottom {
+            styles.push(format!("Alignment={}", self.position.alignment()));
+        }
+        if styles.is_empty() {
+            None
+        } else {
+            Some(styles.join(","))
+        }
+    }
+}
+
+/// 根据字幕文件类型和样式构建视频滤镜
+///
+/// ASS/SSA 字幕在没有样式覆盖时使用 `ass` 滤镜，保留字幕作者的原始排版；
+/// 其余情况使用 `subtitles` 滤镜并通过 `force_style` 应用样式
+fn build_filter(sub: &Path, style: &BurnStyle) -> String {
+    let is_ass = sub
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("ass") || ext.eq_ignore_ascii_case("ssa"));
+    let file_name = escape_filter_value(&sub.to_string_lossy());
+    match style.force_style() {
+        None if is_ass => format!("ass=filename={}", file_name),
+        None => format!("subtitles=filename={}", file_name),
+        Some(force_style) => format!(
+            "subtitles=filename={}:force_style={}",
+            file_name,
+            escape_filter_value(&force_style)
+        ),
+    }
+}
+
+/// 对滤镜参数进行两层转义：先转义选项值中的特殊字符，再转义滤镜图中的特殊字符
+fn escape_filter_value(value: &str) -> String {
+    let mut option = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | '\'' | ':') {
+            option.push('\\');
+        }
+        option.push(c);
+    }
+    let mut graph = String::with_capacity(option.len());
+    for c in option.chars() {
+        if matches!(c, '\\' | '\'' | '[' | ']' | ',' | ';') {
+            graph.push('\\');
+        }
+        graph.push(c);
+    }
+    graph
+}
+
+/// 将 `sub` 字幕烧录进 `video`，输出到 `output`，执行过程中在标准错误输出实时进度
+///
+/// 输出文件已存在时 ffmpeg 会直接失败，不会覆盖
+pub async fn burn_subtitle<P: AsRef<Path>>(
+    video: P,
+    sub: P,
+    output: P,
+    style: &BurnStyle,
+    preset: EncodePreset,
+) -> Result<(), FfmpegError> {
+    let video = video.as_ref();
+    let total = probe_duration(video).await?;
+
+    let filter = build_filter(sub.as_ref(), style);
+    let mut options = vec![
+        "-hide_banner",
+        "-loglevel",
+        "error",
+        "-n",
+        "-i",
+        video.to_str().unwrap_or(""),
+        "-vf",
+        &filter,
+    ];
+    options.extend(preset.x264_args());
+    options.extend(["-c:a", "copy", output.as_ref().to_str().unwrap_or("")]);
+
+    let (tx, rx) = mpsc::unbounded_channel::<FfmpegProgress>();
+    let (res, _) = tokio::join!(
+        FfmpegTool::Ffmpeg.exec_with_progress(
+            &SystemRunner,
+            None::<&'static str>,
+            options,
+            tx,
+            true
+        ),
+        report_progress("burning", total, rx)
+    );
+    res?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_filter_value() {
+        assert_eq!(escape_filter_value("/tmp/a.srt"), "/tmp/a.srt");
+        assert_eq!(escape_filter_value("C:\\sub.srt"), "C\\\\:\\\\\\\\sub.srt");
+        assert_eq!(escape_filter_value("it's.srt"), "it\\\\\\'s.srt");
+        assert_eq!(escape_filter_value("a,b[1].srt"), "a\\,b\\[1\\].srt");
+    }
+
+    #[test]
+    fn test_build_filter_without_style() {
+        let style = BurnStyle::default();
+        assert_eq!(
+            build_filter(Path::new("/tmp/a.srt"), &style),
+            "subtitles=filename=/tmp/a.srt"
+        );
+        assert_eq!(
+            build_filter(Path::new("/tmp/a.ASS"), &style),
+            "ass=filename=/tmp/a.ASS"
+        );
+    }
+
+    #[test]
+    fn test_build_filter_with_style() {
+        let style = BurnStyle {
+            font: Some("Noto Sans CJK SC".to_string()),
+            size: Some(24),
+            outline: Some(1.5),
+            margin: Some(30),
+            position: SubPosition::Top,
+        };
+        assert_eq!(
+            build_filter(Path::new("/tmp/a.ass"), &style),
+            "subtitles=filename=/tmp/a.ass:force_style=FontName=Noto Sans CJK SC\\,FontSize=24\\,Outline=1.5\\,MarginV=30\\,Alignment=8"
+        );
+    }
+
+    #[test]
+    fn test_encode_preset_args() {
+        assert_eq!(
+            EncodePreset::Fast.x264_args(),
+            ["-c:v", "libx264", "-preset", "veryfast", "-crf", "23"]
+        );
+        assert_eq!(EncodePreset::default(), EncodePreset::Balanced);
+    }
+}
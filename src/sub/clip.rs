@@ -0,0 +1,463 @@
+//! clip module
+//! 按字幕条目从视频中截取音频片段或画面截图，并可以导出为 Anki 可导入的 CSV 和媒体目录
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    result,
+    str::FromStr,
+    time::Duration,
+};
+
+use clap::ValueEnum;
+use thiserror::Error;
+
+use crate::{
+    common::{ProcessRunner, SystemRunner},
+    ffmpeg::{FfmpegError, FfmpegTool},
+};
+
+use super::srt::{SrtError, SrtFile, SubtitleEntry};
+
+/// Anki 导出时媒体文件所在的子目录
+const ANKI_MEDIA_DIR: &str = "media";
+/// Anki 导出时的 CSV 文件名
+const ANKI_CSV_FILE: &str = "notes.csv";
+
+/// 要截取的内容
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ClipKind {
+    /// 条目时间范围内的音频，编码为 mp3
+    #[default]
+    Audio,
+    /// 条目中间时刻的画面，保存为 jpg
+    Thumbnail,
+    /// 同时截取音频和画面
+    Both,
+}
+
+impl ClipKind {
+    fn audio(&self) -> bool {
+        matches!(self, ClipKind::Audio | ClipKind::Both)
+    }
+
+    fn thumbnail(&self) -> bool {
+        matches!(self, ClipKind::Thumbnail | ClipKind::Both)
+    }
+}
+
+/// 字幕条目的选择，格式为逗号分隔的序号或序号范围，如 `120-130`、`3,7,10-12`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntrySelection(Vec<RangeInclusive<u32>>);
+
+impl EntrySelection {
+    pub fn contains(&self, index: u32) -> bool {
+        self.0.iter().any(|r| r.contains(&index))
+    }
+}
+
+impl FromStr for EntrySelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        let parse = |n: &str| {
+            n.trim()
+                .parse::<u32>()
+                .map_err(|_| format!("invalid entry index: {}", n.trim()))
+        };
+        let ranges = s
+            .split(',')
+            .filter(|part| !part.trim().is_empty())
+            .map(|part| match part.split_once('-') {
+                Some((beg, end)) => {
+                    let (beg, end) = (parse(beg)?, parse(end)?);
+                    if beg > end {
+                        return Err(format!("invalid entry range: {}", part.trim()));
+                    }
+                    Ok(beg..=end)
+                }
+                None => parse(part).map(|n| n..=n),
+            })
+            .collect::<result::Result<Vec<_>, _>>()?;
+        if ranges.is_empty() {
+            return Err("no entries selected".to_string());
+        }
+        Ok(Self(ranges))
+    }
+}
+
+/// 截取时的参数
+#[derive(Debug, Clone)]
+pub struct ClipOptions {
+    pub kind: ClipKind,
+    /// 音频片段前后额外保留的时长
+    pub padding: Duration,
+    /// 输出目录
+    pub output: PathBuf,
+    /// 是否生成 Anki 导出（`notes.csv` 以及 `media` 目录）
+    pub anki: bool,
+}
+
+impl ClipOptions {
+    /// 默认的输出目录：字幕文件同目录下的 `<name>_clips`
+    pub fn default_output(srt: &Path) -> PathBuf {
+        let stem = srt
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        srt.with_file_name(format!("{}_clips", stem))
+    }
+}
+
+/// 一个字幕条目截取得到的文件
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clip {
+    pub index: u32,
+    pub start: Duration,
+    pub text: String,
+    pub audio: Option<PathBuf>,
+    pub thumbnail: Option<PathBuf>,
+}
+
+#[derive(Debug, Error)]
+pub enum ClipError {
+    #[error("No subtitle entries match the selection")]
+    NoEntries,
+    #[error(transparent)]
+    FfmpegError(#[from] FfmpegError),
+    #[error("Failed to parse the srt file: {0}")]
+    SrtError(#[from] SrtError),
+    #[error("IO error: {0}")]
+    IoError(#[from] io::Error),
+}
+
+pub type Result<T> = result::Result<T, ClipError>;
+
+/// 截取 `srt` 字幕文件中被选中的条目对应的音频或画面，打印生成的文件数量
+pub async fn clip_entries(
+    video: impl AsRef<Path>,
+    srt: impl AsRef<Path>,
+    selection: &EntrySelection,
+    options: &ClipOptions,
+) -> Result<()> {
+    let srt_file = SrtFile::read(File::open(srt)?)?;
+    let entries: Vec<SubtitleEntry> = srt_file
+        .iter()
+        .filter(|e| selection.contains(e.index))
+        .cloned()
+        .collect();
+    if entries.is_empty() {
+        return Err(ClipError::NoEntries);
+    }
+    let clips = clip_entries_with(&SystemRunner, video.as_ref(), &entries, options).await?;
+    match options.anki {
+        true => println!(
+            "{} entries exported to {}",
+            clips.len(),
+            options.output.join(ANKI_CSV_FILE).display()
+        ),
+        false => println!(
+            "{} entries clipped into {}",
+            clips.len(),
+            options.output.display()
+        ),
+    }
+    Ok(())
+}
+
+/// 通过指定的 `runner` 为每个条目执行 ffmpeg，开启 Anki 导出时同时写入 CSV 文件
+pub async fn clip_entries_with<R: ProcessRunner>(
+    runner: &R,
+    video: &Path,
+    entries: &[SubtitleEntry],
+    options: &ClipOptions,
+) -> Result<Vec<Clip>> {
+    let media_dir = match options.anki {
+        true => options.output.join(ANKI_MEDIA_DIR),
+        false => options.output.clone(),
+    };
+    fs::create_dir_all(&media_dir)?;
+    let stem = video
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "clip".to_string());
+
+    let mut clips = Vec::with_capacity(entries.len());
+    for (i, entry) in entries.iter().enumerate() {
+        eprint!("\rclipping {}/{}", i + 1, entries.len());
+        let mut clip = Clip {
+            index: entry.index,
+            start: entry.start_time(),
+            text: entry.text.clone(),
+            audio: None,
+            thumbnail: None,
+        };
+        if options.kind.audio() {
+            let out = media_dir.join(format!("{}_{}.mp3", stem, entry.index));
+            let args = audio_args(video, entry, options.padding, &out);
+            FfmpegTool::Ffmpeg
+                .exec_with_runner(runner, None::<&'static str>, Some(args))
+                .await?;
+            clip.audio = Some(out);
+        }
+        if options.kind.thumbnail() {
+            let out = media_dir.join(format!("{}_{}.jpg", stem, entry.index));
+            let args = thumbnail_args(video, entry, &out);
+            FfmpegTool::Ffmpeg
+                .exec_with_runner(runner, None::<&'static str>, Some(args))
+                .await?;
+            clip.thumbnail = Some(out);
+        }
+        clips.push(clip);
+    }
+    if !entries.is_empty() {
+        eprintln!();
+    }
+
+    if options.anki {
+        let mut w = BufWriter::new(File::create(options.output.join(ANKI_CSV_FILE))?);
+        write_anki_csv(&mut w, &stem, &clips)?;
+        w.flush()?;
+    }
+    Ok(clips)
+}
+
+fn secs(d: Duration) -> String {
+    format!("{:.3}", d.as_secs_f64())
+}
+
+fn audio_args(video: &Path, entry: &SubtitleEntry, padding: Duration, out: &Path) -> Vec<String> {
+    let start = entry.start_time().saturating_sub(padding);
+    let end = entry.end_time() + padding;
+    vec![
+        "-hide_banner".to_string(),
+        "-loglevel".to_string(),
+        "error".to_string(),
+        "-y".to_string(),
+        "-ss".to_string(),
+        secs(start),
+        "-i".to_string(),
+        video.to_string_lossy().into_owned(),
+        "-t".to_string(),
+        secs(end - start),
+        "-map".to_string(),
+        "0:a:0".to_string(),
+        "-c:a".to_string(),
+        "libmp3lame".to_string(),
+        "-q:a".to_string(),
+        "4".to_string(),
+        out.to_string_lossy().into_owned(),
+    ]
+}
+
+fn thumbnail_args(video: &Path, entry: &SubtitleEntry, out: &Path) -> Vec<String> {
+    let mid = (entry.start_time() + entry.end_time()) / 2;
+    vec![
+        "-hide_banner".to_string(),
+        "-loglevel".to_string(),
+        "error".to_string(),
+        "-y".to_string(),
+        "-ss".to_string(),
+        secs(mid),
+        "-i".to_string(),
+        video.to_string_lossy().into_owned(),
+        "-frames:v".to_string(),
+        "1".to_string(),
+        "-vf".to_string(),
+        "scale='min(640,iw)':-2".to_string(),
+        "-q:v".to_string(),
+        "3".to_string(),
+        out.to_string_lossy().into_owned(),
+    ]
+}
+
+/// 写入 Anki 可以直接导入的 CSV，字段依次为：字幕文本、媒体、时间、来源
+///
+/// 媒体字段使用 Anki 的 `[sound:...]` 与 `<img>` 语法，只引用文件名，
+/// 导入前需要把 `media` 目录中的文件复制到 Anki 的 `collection.media` 目录
+fn write_anki_csv<W: Write>(w: &mut W, source: &str, clips: &[Clip]) -> io::Result<()> {
+    writeln!(w, "#separator:comma")?;
+    writeln!(w, "#html:true")?;
+    writeln!(w, "#columns:Text,Media,Time,Source")?;
+    let file_name = |p: &PathBuf| {
+        p.file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    };
+    for clip in clips {
+        let mut media = String::new();
+        if let Some(audio) = &clip.audio {
+            media.push_str(&format!("[sound:{}]", file_name(audio)));
+        }
+        if let Some(thumbnail) = &clip.thumbnail {
+            media.push_str(&format!("<img src=\"{}\">", file_name(thumbnail)));
+        }
+        let text = clip.text.lines().collect::<Vec<_>>().join("<br>");
+        let secs = clip.start.as_secs();
+        let time = format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60);
+        let source = format!("{} #{}", source, clip.index);
+        writeln!(
+            w,
+            "{},{},{},{}",
+            csv_field(&text),
+            csv_field(&media),
+            csv_field(&time),
+            csv_field(&source)
+        )?;
+    }
+    Ok(())
+}
+
+/// 按 RFC 4180 的规则转义 CSV 字段
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::common::ReplayRunner;
+
+    const SRT: &str = "1
+00:00:01,000 --> 00:00:02,500
+Hello, \"world\"
+
+2
+00:01:05,000 --> 00:01:07,000
+second
+line
+
+3
+00:02:00,000 --> 00:02:01,000
+third
+";
+
+    fn entries(selection: &str) -> Vec<SubtitleEntry> {
+        let selection: EntrySelection = selection.parse().unwrap();
+        SrtFile::read(Cursor::new(SRT))
+            .unwrap()
+            .iter()
+            .filter(|e| selection.contains(e.index))
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_entry_selection() {
+        let selection: EntrySelection = "120-130".parse().unwrap();
+        assert!(selection.contains(120) && selection.contains(130));
+        assert!(!selection.contains(131));
+
+        let selection: EntrySelection = "3, 7,10-12".parse().unwrap();
+        assert!(selection.contains(3) && selection.contains(7) && selection.contains(11));
+        assert!(!selection.contains(9));
+
+        assert!("".parse::<EntrySelection>().is_err());
+        assert!("12-10".parse::<EntrySelection>().is_err());
+        assert!("a-3".parse::<EntrySelection>().is_err());
+    }
+
+    #[test]
+    fn test_default_output() {
+        assert_eq!(
+            ClipOptions::default_output(Path::new("/movies/a.en.srt")),
+            PathBuf::from("/movies/a.en_clips")
+        );
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[tokio::test]
+    async fn test_clip_audio() {
+        let dir = tempfile::tempdir().unwrap();
+        let runner = ReplayRunner::new();
+        runner.push_success("", "");
+        let options = ClipOptions {
+            kind: ClipKind::Audio,
+            padding: Duration::from_millis(200),
+            output: dir.path().to_path_buf(),
+            anki: false,
+        };
+
+        let clips = clip_entries_with(&runner, Path::new("movie.mkv"), &entries("1"), &options)
+            .await
+            .unwrap();
+        assert_eq!(clips.len(), 1);
+        assert_eq!(clips[0].audio, Some(dir.path().join("movie_1.mp3")));
+        assert_eq!(clips[0].thumbnail, None);
+
+        let args = &runner.calls()[0].args;
+        let ss = args.iter().position(|a| a == "-ss").unwrap();
+        assert_eq!(args[ss + 1], "0.800");
+        let t = args.iter().position(|a| a == "-t").unwrap();
+        assert_eq!(args[t + 1], "1.900");
+        assert!(!dir.path().join(ANKI_CSV_FILE).exists());
+    }
+
+    #[tokio::test]
+    async fn test_clip_anki_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let runner = ReplayRunner::new();
+        for _ in 0..4 {
+            runner.push_success("", "");
+        }
+        let options = ClipOptions {
+            kind: ClipKind::Both,
+            padding: Duration::ZERO,
+            output: dir.path().to_path_buf(),
+            anki: true,
+        };
+
+        let clips = clip_entries_with(&runner, Path::new("movie.mkv"), &entries("1-2"), &options)
+            .await
+            .unwrap();
+        assert_eq!(clips.len(), 2);
+        assert_eq!(
+            clips[1].thumbnail,
+            Some(dir.path().join(ANKI_MEDIA_DIR).join("movie_2.jpg"))
+        );
+        // 截图取条目的中间时刻
+        let args = &runner.calls()[3].args;
+        let ss = args.iter().position(|a| a == "-ss").unwrap();
+        assert_eq!(args[ss + 1], "66.000");
+
+        let csv = fs::read_to_string(dir.path().join(ANKI_CSV_FILE)).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(
+            lines[3],
+            "\"Hello, \"\"world\"\"\",\"[sound:movie_1.mp3]<img src=\"\"movie_1.jpg\"\">\",00:00:01,movie #1"
+        );
+        assert!(lines[4].starts_with("second<br>line,"));
+    }
+
+    #[tokio::test]
+    async fn test_clip_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let runner = ReplayRunner::new();
+        runner.push_failure(1, "Stream map '0:a:0' matches no streams.");
+        let options = ClipOptions {
+            kind: ClipKind::Audio,
+            padding: Duration::ZERO,
+            output: dir.path().to_path_buf(),
+            anki: false,
+        };
+
+        let result =
+            clip_entries_with(&runner, Path::new("movie.mkv"), &entries("3"), &options).await;
+        assert!(matches!(result, Err(ClipError::FfmpegError(_))));
+    }
+}
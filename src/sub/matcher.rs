@@ -0,0 +1,513 @@
+//! matcher module
+//! 在媒体库目录中为字幕文件匹配对应的视频，并重命名为播放器能够自动加载的 `<视频名>.<语言>.srt`
+//!
+//! 匹配依据文件名中的标题、年份、季集编号，字幕文件名末尾的语言标记会被识别并规范化
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use regex::Regex;
+
+use crate::batch::{BatchError, VIDEO_EXTENSIONS, collect_inputs};
+
+/// 参与匹配的字幕文件扩展名
+pub const SUBTITLE_EXTENSIONS: &[&str] = &["srt", "ass", "ssa"];
+
+/// 标题相似度的最低要求
+const MIN_SIMILARITY: f64 = 0.5;
+
+static SXXEXX_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^s(\d{1,2})e(\d{1,3})$").unwrap());
+static NXNN_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(\d{1,2})x(\d{2,3})$").unwrap());
+static EP_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(?:e|ep)(\d{1,3})$").unwrap());
+
+/// 出现在标题之后的画质、编码、来源等标记
+const RELEASE_TOKENS: &[&str] = &[
+    "480p", "576p", "720p", "1080p", "1080i", "2160p", "4k", "uhd", "hdr", "hdr10", "dv", "bluray",
+    "bdrip", "brrip", "webrip", "web", "webdl", "hdtv", "dvdrip", "x264", "x265", "h264", "h265",
+    "hevc", "avc", "aac", "ac3", "dts", "remux", "proper", "repack", "10bit",
+];
+
+/// 将字幕文件名中的语言标记规范化为播放器识别的语言代码
+fn normalize_lang(token: &str) -> Option<&'static str> {
+    let lang = match token {
+        "zh" | "chi" | "zho" | "chinese" | "cn" => "zh",
+        "chs" | "sc" | "gb" | "hans" => "zh-Hans",
+        "cht" | "tc" | "big5" | "hant" => "zh-Hant",
+        "en" | "eng" | "english" => "en",
+        "ja" | "jp" | "jpn" | "japanese" => "ja",
+        "ko" | "kor" | "korean" => "ko",
+        "fr" | "fre" | "fra" | "french" => "fr",
+        "de" | "ger" | "deu" | "german" => "de",
+        "es" | "spa" | "spanish" => "es",
+        "it" | "ita" | "italian" => "it",
+        "ru" | "rus" | "russian" => "ru",
+        "pt" | "por" | "portuguese" => "pt",
+        _ => return None,
+    };
+    Some(lang)
+}
+
+/// 从文件名中解析出的信息
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MediaName {
+    /// 规范化后的标题单词，全部小写
+    pub title: Vec<String>,
+    pub year: Option<u16>,
+    /// (季, 集)，只有集号时季为 `None`
+    pub episode: Option<(Option<u32>, u32)>,
+    /// 规范化后的语言代码，只对字幕文件解析
+    pub lang: Option<&'static str>,
+}
+
+impl MediaName {
+    /// 解析文件名（不含扩展名），`detect_lang` 为真时识别末尾的语言标记
+    pub fn parse(stem: &str, detect_lang: bool) -> Self {
+        let mut tokens: Vec<String> = stem
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|t| !t.is_empty())
+            .map(|t| t.to_lowercase())
+            .collect();
+        let mut name = MediaName::default();
+
+        if detect_lang && tokens.len() > 1 {
+            // 如 `zh-cn`、`zh.tw` 这样的两段式语言标记
+            if let [.., a, b] = &tokens[..]
+                && let (Some(lang), Some(_)) = (normalize_lang(a), region(b))
+            {
+                name.lang = Some(match (lang, b.as_str()) {
+                    ("zh", "tw" | "hk") => "zh-Hant",
+                    ("zh", _) => "zh-Hans",
+                    (lang, _) => lang,
+                });
+                tokens.truncate(tokens.len() - 2);
+            } else if let Some(lang) = tokens.last().and_then(|t| normalize_lang(t)) {
+                name.lang = Some(lang);
+                tokens.pop();
+            }
+        }
+
+        let mut title_end = tokens.len();
+        for (i, token) in tokens.iter().enumerate() {
+            let episode = parse_episode(token);
+            let year = token
+                .parse::<u16>()
+                .ok()
+                .filter(|y| token.len() == 4 && (1900..=2099).contains(y) && i > 0);
+            if name.episode.is_none() && episode.is_some() {
+                name.episode = episode;
+            } else if name.year.is_none() && year.is_some() {
+                name.year = year;
+            } else if !RELEASE_TOKENS.contains(&token.as_str()) {
+                continue;
+            }
+            title_end = title_end.min(i);
+        }
+        tokens.truncate(title_end);
+        name.title = tokens;
+        name
+    }
+
+    /// 两个标题的 Jaccard 相似度
+    fn similarity(&self, other: &MediaName) -> f64 {
+        let a: HashSet<&String> = self.title.iter().collect();
+        let b: HashSet<&String> = other.title.iter().collect();
+        let union = a.union(&b).count();
+        if union == 0 {
+            return 0.0;
+        }
+        a.intersection(&b).count() as f64 / union as f64
+    }
+}
+
+fn region(token: &str) -> Option<&str> {
+    matches!(
+        token,
+        "cn" | "tw" | "hk" | "sg" | "us" | "gb" | "uk" | "br" | "pt"
+    )
+    .then_some(token)
+}
+
+fn parse_episode(token: &str) -> Option<(Option<u32>, u32)> {
+    if let Some(caps) = SXXEXX_RE
+        .captures(token)
+        .or_else(|| NXNN_RE.captures(token))
+    {
+        return Some((caps[1].parse().ok(), caps[2].parse().ok()?));
+    }
+    EP_RE
+        .captures(token)
+        .and_then(|caps| Some((None, caps[1].parse().ok()?)))
+}
+
+/// 单个字幕文件的匹配结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MatchOutcome {
+    /// 需要重命名为 `target`
+    Rename {
+        subtitle: PathBuf,
+        video: PathBuf,
+        target: PathBuf,
+    },
+    /// 已经符合命名规范
+    AlreadyNamed { subtitle: PathBuf, video: PathBuf },
+    /// 没有找到匹配的视频
+    Unmatched { subtitle: PathBuf },
+    /// 有多个同样匹配的视频，无法确定
+    Ambiguous {
+        subtitle: PathBuf,
+        videos: Vec<PathBuf>,
+    },
+    /// 目标文件已经存在，或者与其它字幕的目标相同
+    Conflict { subtitle: PathBuf, target: PathBuf },
+}
+
+/// 计算字幕与视频的匹配分数，不可能匹配时返回 `None`
+fn score(sub: &MediaName, sub_path: &Path, video: &MediaName, video_path: &Path) -> Option<f64> {
+    let mut score = 0.0;
+    match (sub.episode, video.episode) {
+        (Some((s1, e1)), Some((s2, e2))) => {
+            if e1 != e2 || s1.zip(s2).is_some_and(|(s1, s2)| s1 != s2) {
+                return None;
+            }
+            score += 0.3;
+        }
+        (None, None) => (),
+        _ => return None,
+    }
+    match (sub.year, video.year) {
+        (Some(y1), Some(y2)) if y1 != y2 => return None,
+        (Some(_), Some(_)) => score += 0.1,
+        _ => (),
+    }
+    let same_dir = sub_path.parent() == video_path.parent();
+    if same_dir {
+        score += 0.2;
+    }
+    if sub.title.is_empty() {
+        // 只有季集编号的字幕只与同一目录中的剧集匹配
+        if !same_dir || sub.episode.is_none() {
+            return None;
+        }
+        score += MIN_SIMILARITY;
+    } else {
+        let similarity = sub.similarity(video);
+        if similarity < MIN_SIMILARITY {
+            return None;
+        }
+        score += similarity;
+    }
+    Some(score)
+}
+
+/// 字幕文件的目标路径：与视频同目录的 `<视频名>.<语言>.<扩展名>`
+fn target_path(subtitle: &Path, video: &Path, lang: Option<&str>) -> PathBuf {
+    let stem = video
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let ext = subtitle
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_else(|| "srt".to_string());
+    let name = match lang {
+        Some(lang) => format!("{}.{}.{}", stem, lang, ext),
+        None => format!("{}.{}", stem, ext),
+    };
+    video.with_file_name(name)
+}
+
+/// 为每个字幕文件在 `videos` 中寻找最匹配的视频，并生成重命名计划
+pub fn match_subtitles(videos: &[PathBuf], subtitles: &[PathBuf]) -> Vec<MatchOutcome> {
+    let stem = |p: &Path| {
+        p.file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    };
+    let parsed_videos: Vec<(&PathBuf, MediaName)> = videos
+        .iter()
+        .map(|v| (v, MediaName::parse(&stem(v), false)))
+        .collect();
+    let sub_set: HashSet<&PathBuf> = subtitles.iter().collect();
+    let mut planned: BTreeSet<PathBuf> = BTreeSet::new();
+
+    subtitles
+        .iter()
+        .map(|subtitle| {
+            let sub = MediaName::parse(&stem(subtitle), true);
+            let mut candidates: Vec<(f64, &PathBuf)> = parsed_videos
+                .iter()
+                .filter_map(|(path, video)| Some((score(&sub, subtitle, video, path)?, *path)))
+                .collect();
+            candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+            let video = match &candidates[..] {
+                [] => {
+                    return MatchOutcome::Unmatched {
+                        subtitle: subtitle.clone(),
+                    };
+                }
+                [(best, _), (second, _), ..] if (best - second).abs() < f64::EPSILON => {
+                    return MatchOutcome::Ambiguous {
+                        subtitle: subtitle.clone(),
+                        videos: candidates
+                            .iter()
+                            .take_while(|(s, _)| (best - s).abs() < f64::EPSILON)
+                            .map(|(_, v)| (*v).clone())
+                            .collect(),
+                    };
+                }
+                [(_, video), ..] => (*video).clone(),
+            };
+
+            let target = target_path(subtitle, &video, sub.lang);
+            if &target == subtitle {
+                return MatchOutcome::AlreadyNamed {
+                    subtitle: subtitle.clone(),
+                    video,
+                };
+            }
+            // 目标是另一个尚未处理的字幕文件时同样视为冲突，避免覆盖
+            if target.exists() || sub_set.contains(&target) || !planned.insert(target.clone()) {
+                return MatchOutcome::Conflict {
+                    subtitle: subtitle.clone(),
+                    target,
+                };
+            }
+            MatchOutcome::Rename {
+                subtitle: subtitle.clone(),
+                video,
+                target,
+            }
+        })
+        .collect()
+}
+
+/// 递归扫描 `root` 目录，返回其中所有字幕文件的匹配结果
+pub fn plan_library(root: impl AsRef<Path>) -> io::Result<Vec<MatchOutcome>> {
+    let root = root.as_ref();
+    if !root.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("目录不存在: {}", root.display()),
+        ));
+    }
+    let input = root.to_str().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("path is not valid UTF-8: {:?}", root),
+        )
+    })?;
+    let videos = library_files(input, VIDEO_EXTENSIONS)?;
+    let subtitles = library_files(input, SUBTITLE_EXTENSIONS)?;
+    Ok(match_subtitles(&videos, &subtitles))
+}
+
+/// 目录中扩展名在 `extensions` 中的文件，没有这样的文件不是错误
+fn library_files(input: &str, extensions: &[&str]) -> io::Result<Vec<PathBuf>> {
+    match collect_inputs(input, extensions) {
+        Ok(files) => Ok(files),
+        Err(BatchError::NoInput(_)) => Ok(Vec::new()),
+        Err(e) => Err(io::Error::other(e)),
+    }
+}
+
+/// 为 `root` 目录中的字幕匹配视频并打印重命名计划，`apply` 为真时才会真正重命名
+pub fn match_library(root: impl AsRef<Path>, apply: bool) -> io::Result<()> {
+    let outcomes = plan_library(root)?;
+    let (mut renamed, mut skipped) = (0, 0);
+    for outcome in &outcomes {
+        match outcome {
+            MatchOutcome::Rename {
+                subtitle, target, ..
+            } => {
+                if apply {
+                    fs::rename(subtitle, target)?;
+                    println!("renamed: {} -> {}", subtitle.display(), target.display());
+                } else {
+                    println!("rename:  {} -> {}", subtitle.display(), target.display());
+                }
+                renamed += 1;
+            }
+            MatchOutcome::AlreadyNamed { .. } => (),
+            MatchOutcome::Unmatched { subtitle } => {
+                println!("no match: {}", subtitle.display());
+                skipped += 1;
+            }
+            MatchOutcome::Ambiguous { subtitle, videos } => {
+                println!("ambiguous: {}", subtitle.display());
+                for video in videos {
+                    println!("    {}", video.display());
+                }
+                skipped += 1;
+            }
+            MatchOutcome::Conflict { subtitle, target } => {
+                println!(
+                    "conflict: {} -> {} already taken",
+                    subtitle.display(),
+                    target.display()
+                );
+                skipped += 1;
+            }
+        }
+    }
+    println!(
+        "{} to rename, {} already named, {} skipped",
+        renamed,
+        outcomes.len() - renamed - skipped,
+        skipped
+    );
+    if !apply && renamed > 0 {
+        println!("dry run, pass --apply to rename the files");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touch(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+    }
+
+    #[test]
+    fn test_parse_movie_names() {
+        let video = MediaName::parse("Movie.2019.1080p.BluRay.x264", false);
+        assert_eq!(video.title, vec!["movie"]);
+        assert_eq!(video.year, Some(2019));
+        assert_eq!(video.lang, None);
+
+        let sub = MediaName::parse("movie_2019_zh", true);
+        assert_eq!(sub.title, vec!["movie"]);
+        assert_eq!(sub.year, Some(2019));
+        assert_eq!(sub.lang, Some("zh"));
+
+        // 标题本身是年份的电影
+        let name = MediaName::parse("2012.2009.720p", false);
+        assert_eq!(name.title, vec!["2012"]);
+        assert_eq!(name.year, Some(2009));
+
+        assert_eq!(
+            MediaName::parse("Blow-Up.1966.zh-TW", true).lang,
+            Some("zh-Hant")
+        );
+        assert_eq!(
+            MediaName::parse("Blow-Up.1966.chs", true).lang,
+            Some("zh-Hans")
+        );
+        // 单独的一个单词不会被当作语言标记
+        assert_eq!(MediaName::parse("English", true).lang, None);
+    }
+
+    #[test]
+    fn test_parse_episode_names() {
+        let name = MediaName::parse("The.Office.S02E05.720p.WEB", false);
+        assert_eq!(name.title, vec!["the", "office"]);
+        assert_eq!(name.episode, Some((Some(2), 5)));
+        assert_eq!(
+            MediaName::parse("the office 2x05 eng", true).episode,
+            Some((Some(2), 5))
+        );
+        assert_eq!(
+            MediaName::parse("Show.EP12", false).episode,
+            Some((None, 12))
+        );
+    }
+
+    #[test]
+    fn test_match_library() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let movie = root.join("Movie (2019)/Movie.2019.1080p.mkv");
+        let remake = root.join("Movie (2021)/Movie.2021.1080p.mkv");
+        let e01 = root.join("Show/Show.S01E01.mkv");
+        let e02 = root.join("Show/Show.S01E02.mkv");
+        for video in [&movie, &remake, &e01, &e02] {
+            touch(video);
+        }
+        let movie_zh = root.join("Movie (2019)/movie_2019_zh.srt");
+        let movie_en = root.join("Movie (2021)/Movie.2021.1080p.en.srt");
+        let show_sub = root.join("Show/subs/show.s01e02.chs.srt");
+        let show_bare = root.join("Show/S01E01.srt");
+        let unknown = root.join("Other/Documentary.srt");
+        for sub in [&movie_zh, &movie_en, &show_sub, &show_bare, &unknown] {
+            touch(sub);
+        }
+
+        let outcomes = plan_library(root).unwrap();
+        assert_eq!(outcomes.len(), 5);
+        assert!(outcomes.contains(&MatchOutcome::AlreadyNamed {
+            subtitle: movie_en.clone(),
+            video: remake.clone(),
+        }));
+        assert!(outcomes.contains(&MatchOutcome::Rename {
+            subtitle: movie_zh.clone(),
+            video: movie.clone(),
+            target: root.join("Movie (2019)/Movie.2019.1080p.zh.srt"),
+        }));
+        assert!(outcomes.contains(&MatchOutcome::Rename {
+            subtitle: show_sub.clone(),
+            video: e02.clone(),
+            target: root.join("Show/Show.S01E02.zh-Hans.srt"),
+        }));
+        assert!(outcomes.contains(&MatchOutcome::Rename {
+            subtitle: show_bare.clone(),
+            video: e01.clone(),
+            target: root.join("Show/Show.S01E01.srt"),
+        }));
+        assert!(outcomes.contains(&MatchOutcome::Unmatched {
+            subtitle: unknown.clone(),
+        }));
+
+        // 没有字幕或视频的目录没有匹配结果
+        let empty = tempfile::tempdir().unwrap();
+        assert!(plan_library(empty.path()).unwrap().is_empty());
+
+        // 默认不修改任何文件
+        match_library(root, false).unwrap();
+        assert!(movie_zh.exists());
+        match_library(root, true).unwrap();
+        assert!(!movie_zh.exists());
+        assert!(root.join("Movie (2019)/Movie.2019.1080p.zh.srt").exists());
+        assert!(root.join("Show/Show.S01E02.zh-Hans.srt").exists());
+    }
+
+    #[test]
+    fn test_match_conflicts_and_ambiguity() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let a = root.join("a/Film.1080p.mkv");
+        let b = root.join("b/Film.720p.mkv");
+        let video = root.join("c/Other.Film.2001.mkv");
+        let existing = root.join("c/Other.Film.2001.en.srt");
+        let dup = root.join("c/other film 2001 eng.srt");
+        let sub = root.join("film.srt");
+
+        let outcomes = match_subtitles(
+            &[a.clone(), b.clone(), video.clone()],
+            &[sub.clone(), dup.clone()],
+        );
+        assert_eq!(
+            outcomes[0],
+            MatchOutcome::Ambiguous {
+                subtitle: sub,
+                videos: vec![a, b],
+            }
+        );
+        touch(&existing);
+        let outcomes = match_subtitles(&[video], std::slice::from_ref(&dup));
+        assert_eq!(
+            outcomes[0],
+            MatchOutcome::Conflict {
+                subtitle: dup,
+                target: existing,
+            }
+        );
+    }
+}
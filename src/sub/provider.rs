@@ -0,0 +1,346 @@
+//! 在线字幕下载
+//!
+//! 使用 OpenSubtitles 的视频哈希（文件大小加上首尾各 64KiB 内容的和）精确查找字幕，
+//! 也可以按照 IMDB 编号或者关键字搜索，下载的字幕保存在视频旁边
+
+mod opensubtitles;
+
+use std::{
+    fs::File,
+    future::Future,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    result,
+};
+
+pub use opensubtitles::{API_KEY_ENV, DEFAULT_BASE_URL, OpenSubtitlesClient};
+use prettytable::{Cell, Row, Table};
+use thiserror::Error;
+
+/// 计算视频哈希时，在文件首尾各读取的字节数
+pub const HASH_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// 计算 OpenSubtitles 的视频哈希
+///
+/// 哈希为文件大小与首尾各 64KiB 内容按小端 u64 累加的和（溢出回绕），文件不能小于 64KiB
+pub fn movie_hash(path: impl AsRef<Path>) -> Result<u64> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    if size < HASH_CHUNK_SIZE {
+        return Err(ProviderError::FileTooSmall(size));
+    }
+
+    let mut hash = size;
+    let mut buf = vec![0u8; HASH_CHUNK_SIZE as usize];
+    for offset in [0, size - HASH_CHUNK_SIZE] {
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut buf)?;
+        hash = buf
+            .chunks_exact(8)
+            .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+            .fold(hash, u64::wrapping_add);
+    }
+    Ok(hash)
+}
+
+/// 视频哈希的十六进制表示，即 API 中使用的格式
+pub fn format_movie_hash(hash: u64) -> String {
+    format!("{:016x}", hash)
+}
+
+/// 解析 IMDB 编号，允许带有 `tt` 前缀
+pub fn parse_imdb_id(value: &str) -> result::Result<u32, String> {
+    value
+        .trim()
+        .trim_start_matches("tt")
+        .parse()
+        .map_err(|_| format!("invalid IMDB id: {}", value))
+}
+
+/// 字幕的搜索条件，没有设置的条件不参与搜索
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SubtitleQuery {
+    pub moviehash: Option<String>,
+    pub imdb_id: Option<u32>,
+    pub query: Option<String>,
+    /// 语言代码，例如 `en`、`zh-cn`
+    pub languages: Vec<String>,
+}
+
+/// 搜索到的一个字幕文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubtitleCandidate {
+    pub file_id: u64,
+    pub file_name: String,
+    pub language: String,
+    pub release: String,
+    pub download_count: u64,
+    /// 是否通过视频哈希精确匹配
+    pub hash_match: bool,
+}
+
+/// 下载到的字幕文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadedSubtitle {
+    pub file_name: String,
+    pub content: Vec<u8>,
+}
+
+/// 字幕来源
+pub trait SubtitleProvider: Sync {
+    /// 按照条件搜索字幕
+    fn search(
+        &self,
+        query: &SubtitleQuery,
+    ) -> impl Future<Output = Result<Vec<SubtitleCandidate>>> + Send;
+
+    /// 下载搜索到的字幕文件
+    fn download(
+        &self,
+        candidate: &SubtitleCandidate,
+    ) -> impl Future<Output = Result<DownloadedSubtitle>> + Send;
+}
+
+/// `sub fetch` 的选项
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FetchOptions {
+    pub imdb_id: Option<u32>,
+    pub query: Option<String>,
+    /// 按优先级排列的语言代码
+    pub languages: Vec<String>,
+    /// 只列出搜索结果，不下载
+    pub list: bool,
+    /// 覆盖已经存在的字幕文件
+    pub force: bool,
+}
+
+/// 对搜索结果排序：哈希匹配的优先，其次是语言的优先级，最后是下载次数
+pub fn rank_candidates(candidates: &mut [SubtitleCandidate], languages: &[String]) {
+    let priority = |lang: &str| {
+        languages
+            .iter()
+            .position(|l| l.eq_ignore_ascii_case(lang))
+            .unwrap_or(languages.len())
+    };
+    candidates.sort_by(|a, b| {
+        b.hash_match
+            .cmp(&a.hash_match)
+            .then_with(|| priority(&a.language).cmp(&priority(&b.language)))
+            .then_with(|| b.download_count.cmp(&a.download_count))
+    });
+}
+
+/// 将 OpenSubtitles 的语言代码转换为播放器识别的文件名语言标记
+fn file_lang(language: &str) -> String {
+    match language.to_ascii_lowercase().as_str() {
+        "zh-cn" => "zh-Hans".to_string(),
+        "zh-tw" => "zh-Hant".to_string(),
+        lang => lang.to_string(),
+    }
+}
+
+/// 下载的字幕在视频旁边保存的路径：`<视频名>.<语言>.<扩展名>`
+pub fn subtitle_path(video: &Path, language: &str, file_name: &str) -> PathBuf {
+    let stem = video
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let ext = Path::new(file_name)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_else(|| "srt".to_string());
+    video.with_file_name(format!("{}.{}.{}", stem, file_lang(language), ext))
+}
+
+/// 使用 OpenSubtitles 为视频搜索并下载字幕
+pub async fn fetch_subtitle(
+    video: impl AsRef<Path>,
+    options: &FetchOptions,
+) -> Result<Option<PathBuf>> {
+    let client = OpenSubtitlesClient::from_config()?;
+    fetch_subtitle_with(&client, video, options).await
+}
+
+/// 与 [`fetch_subtitle`] 相同，但是使用指定的字幕来源
+///
+/// 列出结果时返回 `None`，否则下载排序后的第一个字幕并返回保存的路径。
+/// 没有设置 `force` 时不覆盖已有的字幕文件
+pub async fn fetch_subtitle_with<P: SubtitleProvider>(
+    provider: &P,
+    video: impl AsRef<Path>,
+    options: &FetchOptions,
+) -> Result<Option<PathBuf>> {
+    let video = video.as_ref();
+    let moviehash = match movie_hash(video) {
+        Ok(hash) => Some(format_movie_hash(hash)),
+        // 只提供了其它搜索条件时，无法计算哈希不影响搜索
+        Err(e) if options.imdb_id.is_some() || options.query.is_some() => {
+            eprintln!("warning: {}, searching without the movie hash", e);
+            None
+        }
+        Err(e) => return Err(e),
+    };
+    let query = SubtitleQuery {
+        moviehash,
+        imdb_id: options.imdb_id,
+        query: options.query.clone(),
+        languages: options.languages.clone(),
+    };
+
+    let mut candidates = provider.search(&query).await?;
+    if candidates.is_empty() {
+        return Err(ProviderError::NoResults);
+    }
+    rank_candidates(&mut candidates, &options.languages);
+
+    if options.list {
+        print_candidates(&candidates);
+        return Ok(None);
+    }
+
+    let best = &candidates[0];
+    // 下载次数有限额，先按照搜索结果中的文件名检查
+    let expected = subtitle_path(video, &best.language, &best.file_name);
+    if !options.force && expected.exists() {
+        return Err(ProviderError::SubtitleExists(expected));
+    }
+    let subtitle = provider.download(best).await?;
+    let path = subtitle_path(video, &best.language, &subtitle.file_name);
+    let mut file = if options.force {
+        File::create(&path)?
+    } else {
+        File::create_new(&path).map_err(|e| match e.kind() {
+            io::ErrorKind::AlreadyExists => ProviderError::SubtitleExists(path.clone()),
+            _ => e.into(),
+        })?
+    };
+    file.write_all(&subtitle.content)?;
+    println!("{}", path.display());
+    Ok(Some(path))
+}
+
+fn print_candidates(candidates: &[SubtitleCandidate]) {
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("File ID"),
+        Cell::new("Language"),
+        Cell::new("Hash Match"),
+        Cell::new("Downloads"),
+        Cell::new("Release"),
+    ]));
+    for candidate in candidates {
+        table.add_row(Row::new(vec![
+            Cell::new(&candidate.file_id.to_string()),
+            Cell::new(&candidate.language),
+            Cell::new(if candidate.hash_match { "yes" } else { "no" }),
+            Cell::new(&candidate.download_count.to_string()),
+            Cell::new(&candidate.release),
+        ]));
+    }
+    table.printstd();
+}
+
+#[derive(Debug, Error)]
+pub enum ProviderError {
+    #[error("File is too small to hash: {0} bytes, at least 65536 bytes are needed")]
+    FileTooSmall(u64),
+    #[error(
+        "No OpenSubtitles API key, set BLOWUP_OPENSUBTITLES_API_KEY or `api_key` in the [opensubtitles] section of the config file"
+    )]
+    MissingApiKey,
+    #[error("No subtitles found")]
+    NoResults,
+    #[error("Subtitle {0:?} already exists, use --force to overwrite it")]
+    SubtitleExists(PathBuf),
+    #[error("Subtitle provider returned {status}: {message}")]
+    ApiError { status: u16, message: String },
+    #[error("HTTP error: {0}")]
+    HttpError(#[from] reqwest::Error),
+    #[error("IO error: {0}")]
+    IoError(#[from] io::Error),
+}
+
+pub type Result<T> = result::Result<T, ProviderError>;
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn write_words(path: &Path, words: &[u64]) {
+        let bytes: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+        fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn test_movie_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let words_per_chunk = (HASH_CHUNK_SIZE / 8) as usize;
+
+        // 首块全为 1，中间为 0，尾块全为 2
+        let video = dir.path().join("movie.mkv");
+        let mut words = vec![1u64; words_per_chunk];
+        words.extend(vec![0u64; words_per_chunk]);
+        words.extend(vec![2u64; words_per_chunk]);
+        write_words(&video, &words);
+        let size = 3 * HASH_CHUNK_SIZE;
+        assert_eq!(
+            movie_hash(&video).unwrap(),
+            size + words_per_chunk as u64 * 3
+        );
+
+        // 累加时溢出回绕，只有 64KiB 的文件首尾块重合
+        let small = dir.path().join("small.mkv");
+        write_words(&small, &vec![u64::MAX; words_per_chunk]);
+        let expected =
+            (0..2 * words_per_chunk).fold(HASH_CHUNK_SIZE, |h, _| h.wrapping_add(u64::MAX));
+        assert_eq!(movie_hash(&small).unwrap(), expected);
+        assert_eq!(format_movie_hash(0x1a2b), "0000000000001a2b");
+
+        let tiny = dir.path().join("tiny.mkv");
+        fs::write(&tiny, b"tiny").unwrap();
+        assert!(matches!(
+            movie_hash(&tiny),
+            Err(ProviderError::FileTooSmall(4))
+        ));
+    }
+
+    #[test]
+    fn test_rank_candidates_and_path() {
+        let candidate = |id, lang: &str, downloads, hash_match| SubtitleCandidate {
+            file_id: id,
+            file_name: format!("{}.srt", id),
+            language: lang.to_string(),
+            release: String::new(),
+            download_count: downloads,
+            hash_match,
+        };
+        let mut candidates = vec![
+            candidate(1, "en", 900, false),
+            candidate(2, "en", 10, true),
+            candidate(3, "zh-cn", 5, true),
+            candidate(4, "zh-cn", 50, true),
+        ];
+        rank_candidates(&mut candidates, &["zh-cn".to_string(), "en".to_string()]);
+        let ids: Vec<u64> = candidates.iter().map(|c| c.file_id).collect();
+        assert_eq!(ids, vec![4, 3, 2, 1]);
+
+        let video = Path::new("/movies/Blow-Up (1966).mkv");
+        assert_eq!(
+            subtitle_path(video, "zh-CN", "blowup.chs.srt"),
+            PathBuf::from("/movies/Blow-Up (1966).zh-Hans.srt")
+        );
+        assert_eq!(
+            subtitle_path(video, "en", "noext"),
+            PathBuf::from("/movies/Blow-Up (1966).en.srt")
+        );
+    }
+
+    #[test]
+    fn test_parse_imdb_id() {
+        assert_eq!(parse_imdb_id("tt0060176"), Ok(60176));
+        assert_eq!(parse_imdb_id("60176"), Ok(60176));
+        assert!(parse_imdb_id("tt").is_err());
+    }
+}
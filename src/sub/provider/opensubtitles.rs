@@ -0,0 +1,232 @@
+//! OpenSubtitles REST API（v1）的字幕来源
+
+use std::{env, time::Duration};
+
+use reqwest::{Client, RequestBuilder, Response};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{
+    DownloadedSubtitle, ProviderError, Result, SubtitleCandidate, SubtitleProvider, SubtitleQuery,
+};
+use crate::config::config;
+
+/// OpenSubtitles REST API 的地址
+pub const DEFAULT_BASE_URL: &str = "https://api.opensubtitles.com/api/v1";
+
+/// 指定 OpenSubtitles API key 的环境变量，优先于配置文件
+pub const API_KEY_ENV: &str = "BLOWUP_OPENSUBTITLES_API_KEY";
+
+/// OpenSubtitles 要求每个请求都带有标识客户端的 User-Agent
+const USER_AGENT: &str = concat!("blowup v", env!("CARGO_PKG_VERSION"));
+
+/// 单个请求（包括下载字幕文件）的超时时间
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// OpenSubtitles REST API 的客户端
+#[derive(Debug, Clone)]
+pub struct OpenSubtitlesClient {
+    http: Client,
+    base_url: String,
+    api_key: String,
+}
+
+impl OpenSubtitlesClient {
+    pub fn new(api_key: impl Into<String>) -> Self {
+        let http = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Self {
+            http,
+            base_url: DEFAULT_BASE_URL.to_string(),
+            api_key: api_key.into(),
+        }
+    }
+
+    /// 使用其它的 API 地址，例如测试用的本地服务
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// 使用环境变量 `BLOWUP_OPENSUBTITLES_API_KEY` 或者配置文件中的 API key 创建客户端
+    pub fn from_config() -> Result<Self> {
+        env::var(API_KEY_ENV)
+            .ok()
+            .or_else(|| config().opensubtitles.api_key.clone())
+            .filter(|key| !key.is_empty())
+            .map(Self::new)
+            .ok_or(ProviderError::MissingApiKey)
+    }
+
+    fn request(&self, builder: RequestBuilder) -> RequestBuilder {
+        builder
+            .header("Api-Key", &self.api_key)
+            .header(reqwest::header::USER_AGENT, USER_AGENT)
+            .header(reqwest::header::ACCEPT, "application/json")
+    }
+}
+
+/// 请求失败时，尽量从响应的 `message` 字段中取出错误信息
+async fn check_status(response: Response) -> Result<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    let message = serde_json::from_str::<ApiMessage>(&body)
+        .ok()
+        .and_then(|m| m.message)
+        .unwrap_or(body);
+    Err(ProviderError::ApiError {
+        status: status.as_u16(),
+        message,
+    })
+}
+
+/// 搜索参数，API 建议参数按名称排序、语言代码小写并排序以便命中缓存
+fn search_params(query: &SubtitleQuery) -> Vec<(&'static str, String)> {
+    let mut params = Vec::new();
+    if let Some(imdb_id) = query.imdb_id {
+        params.push(("imdb_id", imdb_id.to_string()));
+    }
+    if !query.languages.is_empty() {
+        let mut languages: Vec<String> = query
+            .languages
+            .iter()
+            .map(|l| l.to_ascii_lowercase())
+            .collect();
+        languages.sort();
+        languages.dedup();
+        params.push(("languages", languages.join(",")));
+    }
+    if let Some(hash) = &query.moviehash {
+        params.push(("moviehash", hash.clone()));
+    }
+    if let Some(text) = &query.query {
+        params.push(("query", text.to_lowercase()));
+    }
+    params
+}
+
+impl SubtitleProvider for OpenSubtitlesClient {
+    async fn search(&self, query: &SubtitleQuery) -> Result<Vec<SubtitleCandidate>> {
+        let url = format!("{}/subtitles", self.base_url);
+        let response = self
+            .request(self.http.get(url))
+            .query(&search_params(query))
+            .send()
+            .await?;
+        let response: SearchResponse = check_status(response).await?.json().await?;
+
+        Ok(response
+            .data
+            .into_iter()
+            .flat_map(|item| {
+                let attributes = item.attributes;
+                attributes
+                    .files
+                    .into_iter()
+                    .map(move |file| SubtitleCandidate {
+                        file_id: file.file_id,
+                        file_name: file.file_name.unwrap_or_default(),
+                        language: attributes.language.clone().unwrap_or_default(),
+                        release: attributes.release.clone().unwrap_or_default(),
+                        download_count: attributes.download_count,
+                        hash_match: attributes.moviehash_match,
+                    })
+            })
+            .collect())
+    }
+
+    async fn download(&self, candidate: &SubtitleCandidate) -> Result<DownloadedSubtitle> {
+        let url = format!("{}/download", self.base_url);
+        let response = self
+            .request(self.http.post(url))
+            .json(&json!({ "file_id": candidate.file_id }))
+            .send()
+            .await?;
+        let link: DownloadResponse = check_status(response).await?.json().await?;
+
+        // 下载链接是临时生成的文件地址，不需要 API key
+        let response = self
+            .http
+            .get(&link.link)
+            .header(reqwest::header::USER_AGENT, USER_AGENT)
+            .send()
+            .await?;
+        let content = check_status(response).await?.bytes().await?;
+        Ok(DownloadedSubtitle {
+            file_name: link
+                .file_name
+                .unwrap_or_else(|| candidate.file_name.clone()),
+            content: content.to_vec(),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiMessage {
+    message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResponse {
+    #[serde(default)]
+    data: Vec<SearchItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchItem {
+    attributes: SubtitleAttributes,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubtitleAttributes {
+    language: Option<String>,
+    release: Option<String>,
+    #[serde(default)]
+    download_count: u64,
+    #[serde(default)]
+    moviehash_match: bool,
+    #[serde(default)]
+    files: Vec<SubtitleFile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubtitleFile {
+    file_id: u64,
+    file_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DownloadResponse {
+    link: String,
+    file_name: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_params() {
+        let query = SubtitleQuery {
+            moviehash: Some("8e245d9679d31e12".to_string()),
+            imdb_id: Some(60176),
+            query: Some("Blow-Up".to_string()),
+            languages: vec!["zh-CN".to_string(), "en".to_string(), "en".to_string()],
+        };
+        assert_eq!(
+            search_params(&query),
+            vec![
+                ("imdb_id", "60176".to_string()),
+                ("languages", "en,zh-cn".to_string()),
+                ("moviehash", "8e245d9679d31e12".to_string()),
+                ("query", "blow-up".to_string()),
+            ]
+        );
+        assert!(search_params(&SubtitleQuery::default()).is_empty());
+    }
+}
@@ -18,6 +18,7 @@ use regex::Regex;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
+#[allow(clippy::enum_variant_names)]
 pub enum SrtError {
     #[error("Parse time error: {0}")]
     ParseTimeError(String),
@@ -60,9 +61,7 @@ impl Deref for SrtFile {
 impl SrtFile {
     pub fn read<R: Read>(r: R) -> Result<Self> {
         let br = BufReader::new(r);
-        let mut lines = br
-            .lines()
-            .map(|line| line.map_err(|e| SrtError::IoError(e)));
+        let mut lines = br.lines().map(|line| line.map_err(SrtError::IoError));
         let mut entries = Vec::new();
 
         loop {
@@ -117,12 +116,19 @@ impl SrtFile {
                 "{}\n{}\n{}\n\n",
                 entry.index, entry.timestamp, entry.text
             ))
-            .map_err(|e| SrtError::IoError(e))?;
+            .map_err(SrtError::IoError)?;
         }
-        bw.flush().map_err(|e| SrtError::IoError(e))?;
+        bw.flush().map_err(SrtError::IoError)?;
         Ok(())
     }
 
+    /// 按顺序替换条目的文本，序号和时间不变，多出的文本会被忽略
+    pub fn replace_texts(&mut self, texts: impl IntoIterator<Item = String>) {
+        for (entry, text) in self.entries.iter_mut().zip(texts) {
+            entry.text = text;
+        }
+    }
+
     /// 检查srt文件中条目之间的时间段是否有重合
     pub fn check_ts_overlap(&self) -> bool {
         match self.entries.len() {
@@ -231,6 +237,104 @@ impl SrtFile {
     }
 }
 
+/// 将字幕时间吸附到镜头切换点时的参数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SnapOptions {
+    /// 只移动与切换点距离不超过该值的起止时间
+    pub tolerance: std::time::Duration,
+    /// 移动后条目的持续时间不能短于该值，原本就短于该值的条目只允许变长
+    pub min_duration: std::time::Duration,
+}
+
+impl Default for SnapOptions {
+    fn default() -> Self {
+        Self {
+            tolerance: std::time::Duration::from_millis(250),
+            min_duration: std::time::Duration::from_millis(700),
+        }
+    }
+}
+
+/// 吸附的结果统计
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SnapReport {
+    /// 起始或结束时间被移动的条目数
+    pub moved: usize,
+    /// 被移动的起始时间数
+    pub starts: usize,
+    /// 被移动的结束时间数
+    pub ends: usize,
+}
+
+impl SrtFile {
+    /// 将条目的起止时间吸附到 `tolerance` 范围内最近的镜头切换点上
+    ///
+    /// 切换点 `cuts` 必须按时间排序。移动后的条目不会与前后条目重叠，也不会短于 `min_duration`，
+    /// 不满足条件的移动会被放弃
+    pub fn snap_to_cuts(
+        &mut self,
+        cuts: &[std::time::Duration],
+        options: SnapOptions,
+    ) -> SnapReport {
+        let to_chrono = |d: std::time::Duration| Duration::from_std(d).unwrap_or(Duration::MAX);
+        let cuts: Vec<Duration> = cuts.iter().map(|c| to_chrono(*c)).collect();
+        let tolerance = to_chrono(options.tolerance);
+        let min_duration = to_chrono(options.min_duration);
+
+        let mut report = SnapReport::default();
+        for i in 0..self.entries.len() {
+            let prev_end = i
+                .checked_sub(1)
+                .map(|p| self.entries[p].timestamp.end_ts)
+                .unwrap_or(Duration::zero());
+            let next_beg = self.entries.get(i + 1).map(|n| n.timestamp.beg_ts);
+            let ts = &mut self.entries[i].timestamp;
+            let mut moved = false;
+
+            if let Some(cut) = nearest_cut(&cuts, ts.beg_ts, tolerance)
+                && cut >= prev_end
+                && duration_allowed(ts.end_ts - cut, ts.dur, min_duration)
+            {
+                ts.update_beg_ts(cut);
+                report.starts += 1;
+                moved = true;
+            }
+            if let Some(cut) = nearest_cut(&cuts, ts.end_ts, tolerance)
+                && next_beg.is_none_or(|next| cut <= next)
+                && duration_allowed(cut - ts.beg_ts, ts.dur, min_duration)
+            {
+                ts.update_end_ts(cut);
+                report.ends += 1;
+                moved = true;
+            }
+            if moved {
+                report.moved += 1;
+            }
+        }
+        report
+    }
+}
+
+/// 查找距离 `ts` 不超过 `tolerance` 的最近的切换点，`ts` 本身就在切换点上时返回 `None`
+fn nearest_cut(cuts: &[Duration], ts: Duration, tolerance: Duration) -> Option<Duration> {
+    let idx = cuts.partition_point(|c| *c < ts);
+    if cuts.get(idx) == Some(&ts) {
+        return None;
+    }
+    let before = idx.checked_sub(1).map(|i| cuts[i]);
+    let after = cuts.get(idx).copied();
+    [before, after]
+        .into_iter()
+        .flatten()
+        .filter(|c| (*c - ts).abs() <= tolerance)
+        .min_by_key(|c| (*c - ts).abs())
+}
+
+/// 新的持续时间满足最短时长，或者比原来更长
+fn duration_allowed(new_dur: Duration, old_dur: Duration, min_duration: Duration) -> bool {
+    new_dur > Duration::zero() && (new_dur >= min_duration || new_dur >= old_dur)
+}
+
 #[derive(Debug, Default, Clone, PartialEq)]
 pub struct SubtitleEntry {
     pub index: u32,
@@ -246,6 +350,16 @@ impl SubtitleEntry {
         res[2] = self.text.clone();
         res
     }
+
+    /// 条目的起始时间
+    pub fn start_time(&self) -> std::time::Duration {
+        self.timestamp.beg_ts.to_std().unwrap_or_default()
+    }
+
+    /// 条目的结束时间
+    pub fn end_time(&self) -> std::time::Duration {
+        self.timestamp.end_ts.to_std().unwrap_or_default()
+    }
 }
 
 static SRT_TIME_RE: LazyLock<Regex> = LazyLock::new(|| {
@@ -270,7 +384,8 @@ impl Display for SrtTime {
 }
 
 impl SrtTime {
-    pub fn new(beg_ts: Duration, end_ts: Duration) -> Self {
+    #[cfg(test)]
+    fn new(beg_ts: Duration, end_ts: Duration) -> Self {
         Self {
             beg_ts,
             end_ts,
@@ -278,14 +393,6 @@ impl SrtTime {
         }
     }
 
-    pub fn entry_dur_secs(&self) -> i64 {
-        self.dur.num_seconds()
-    }
-
-    pub fn entry_dur_millis(&self) -> i64 {
-        self.dur.num_milliseconds()
-    }
-
     fn update_beg_ts(&mut self, new_ts: Duration) {
         self.beg_ts = new_ts;
         self.dur = self.end_ts - self.beg_ts
@@ -311,7 +418,7 @@ impl SrtTime {
         let s = s % 60;
         let h = m / 60;
         let m = m % 60;
-        return format!("{}{:02}:{:02}:{:02},{:03}", sig, h, m, s, ms);
+        format!("{}{:02}:{:02}:{:02},{:03}", sig, h, m, s, ms)
     }
 
     fn from_line(ts_line: &str) -> Result<Self> {
@@ -338,19 +445,23 @@ impl SrtTime {
 
     fn from_seg(h: &str, m: &str, s: &str, ms: &str) -> Result<Duration> {
         let mut start = Duration::zero();
-        let h: i64 = h.parse().map_err(|_| {
-            SrtError::ParseTimeError(format!("invalid hour value {}", h.to_string()))
-        })?;
-        let m: i64 = m.parse().map_err(|_| {
-            SrtError::ParseTimeError(format!("invalid minute value {}", m.to_string()))
-        })?;
-        let s: i64 = s.parse().map_err(|_| {
-            SrtError::ParseTimeError(format!("invalid second value {}", s.to_string()))
-        })?;
-        let ms: i64 = ms.parse().map_err(|_| {
-            SrtError::ParseTimeError(format!("invalid millisecond value {}", ms.to_string()))
-        })?;
-        if h > 99 || h < 0 || m > 59 || m < 0 || s > 59 || s < 0 || ms > 999 || ms < 0 {
+        let h: i64 = h
+            .parse()
+            .map_err(|_| SrtError::ParseTimeError(format!("invalid hour value {}", h)))?;
+        let m: i64 = m
+            .parse()
+            .map_err(|_| SrtError::ParseTimeError(format!("invalid minute value {}", m)))?;
+        let s: i64 = s
+            .parse()
+            .map_err(|_| SrtError::ParseTimeError(format!("invalid second value {}", s)))?;
+        let ms: i64 = ms
+            .parse()
+            .map_err(|_| SrtError::ParseTimeError(format!("invalid millisecond value {}", ms)))?;
+        if !(0..=99).contains(&h)
+            || !(0..=59).contains(&m)
+            || !(0..=59).contains(&s)
+            || !(0..=999).contains(&ms)
+        {
             return Err(SrtError::ParseTimeError(format!(
                 "invalid timestamp: {:02}:{:02}:{:02}.{:03}",
                 h, m, s, ms
@@ -379,6 +490,90 @@ mod tests {
         }
     }
 
+    fn create_entry_ms(beg_ms: i64, end_ms: i64) -> SubtitleEntry {
+        SubtitleEntry {
+            index: 1,
+            timestamp: SrtTime::new(
+                Duration::milliseconds(beg_ms),
+                Duration::milliseconds(end_ms),
+            ),
+            text: "".to_string(),
+        }
+    }
+
+    fn cuts(ms: &[u64]) -> Vec<std::time::Duration> {
+        ms.iter()
+            .map(|m| std::time::Duration::from_millis(*m))
+            .collect()
+    }
+
+    #[test]
+    fn test_snap_to_cuts() {
+        let mut srt_file = SrtFile {
+            entries: vec![
+                create_entry_ms(1_000, 3_000),
+                create_entry_ms(5_000, 7_000),
+                create_entry_ms(9_000, 10_000),
+            ],
+        };
+        let report =
+            srt_file.snap_to_cuts(&cuts(&[900, 3_100, 5_400, 6_850]), SnapOptions::default());
+
+        assert_eq!(srt_file.entries[0], create_entry_ms(900, 3_100));
+        // 5_400 超出了容差范围
+        assert_eq!(srt_file.entries[1], create_entry_ms(5_000, 6_850));
+        assert_eq!(srt_file.entries[2], create_entry_ms(9_000, 10_000));
+        assert_eq!(
+            report,
+            SnapReport {
+                moved: 2,
+                starts: 1,
+                ends: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_snap_respects_min_duration_and_neighbours() {
+        let mut srt_file = SrtFile {
+            entries: vec![
+                create_entry_ms(1_000, 1_800),
+                create_entry_ms(2_000, 2_500),
+                create_entry_ms(2_600, 4_000),
+                create_entry_ms(4_500, 5_000),
+            ],
+        };
+        let report = srt_file.snap_to_cuts(&cuts(&[1_200, 2_400, 5_100]), SnapOptions::default());
+
+        // 起点移到 1_200 会使条目短于 700ms
+        assert_eq!(srt_file.entries[0], create_entry_ms(1_000, 1_800));
+        // 原本就短于最短时长的条目不能再变短
+        assert_eq!(srt_file.entries[1], create_entry_ms(2_000, 2_500));
+        // 起点移到 2_400 会与上一条重叠
+        assert_eq!(srt_file.entries[2], create_entry_ms(2_600, 4_000));
+        // 但是允许变长
+        assert_eq!(srt_file.entries[3], create_entry_ms(4_500, 5_100));
+        assert_eq!(report.moved, 1);
+    }
+
+    #[test]
+    fn test_snap_without_cuts() {
+        let mut srt_file = SrtFile {
+            entries: vec![create_entry(1, 2)],
+        };
+        assert_eq!(
+            srt_file.snap_to_cuts(&[], SnapOptions::default()),
+            SnapReport::default()
+        );
+        // 已经在切换点上的时间不算移动
+        assert_eq!(
+            srt_file
+                .snap_to_cuts(&cuts(&[1_000, 2_000]), SnapOptions::default())
+                .moved,
+            0
+        );
+    }
+
     #[test]
     fn test_to_entry_str() {
         let entry = create_entry(100, 200);
@@ -0,0 +1,438 @@
+//! 使用本地大语言模型翻译字幕
+//!
+//! 字幕条目按批发送给模型，每批前后附带若干条相邻的字幕作为上下文，让译文前后连贯。
+//! 模型需要返回与本批条目一一对应的译文，数量或编号不符时重试，序号和时间保持不变
+
+use std::{
+    fs::File,
+    io::{self, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    result,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::srt::{SrtError, SrtFile, SubtitleEntry};
+use crate::ai::{AiError, OllamaModel, TextModel};
+
+#[derive(Debug, Error)]
+pub enum TranslateError {
+    #[error("Failed to read the subtitle file: {0}")]
+    Srt(#[from] SrtError),
+    #[error("{0}")]
+    Ai(#[from] AiError),
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("The model did not return one translation per cue for cues {first}-{last}: {reason}")]
+    Mismatch {
+        first: usize,
+        last: usize,
+        reason: String,
+    },
+    #[error("Output file {0:?} already exists")]
+    OutputExists(PathBuf),
+}
+
+pub type Result<T> = result::Result<T, TranslateError>;
+
+/// `sub translate` 的选项
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranslateOptions {
+    /// 目标语言，例如 `zh`、`Simplified Chinese`
+    pub to: String,
+    /// 源语言，为空时由模型判断
+    pub from: Option<String>,
+    /// 每批翻译的条目数量
+    pub batch_size: usize,
+    /// 每批前后附带的上下文条目数量
+    pub context: usize,
+    /// 译文不符合要求时的重试次数
+    pub retries: usize,
+}
+
+impl Default for TranslateOptions {
+    fn default() -> Self {
+        Self {
+            to: "zh".to_string(),
+            from: None,
+            batch_size: 20,
+            context: 3,
+            retries: 2,
+        }
+    }
+}
+
+const SYSTEM_PROMPT: &str = "You are a professional subtitle translator. \
+Translate the cues in `translate` and nothing else; `before` and `after` are the neighbouring \
+subtitle lines, given only as context. Keep the meaning, tone and register of spoken dialogue, \
+keep line breaks inside a cue where they make sense, and never merge or split cues. \
+Reply with a JSON object of the form {\"translations\": [{\"id\": 1, \"text\": \"...\"}]} \
+containing exactly one item for every id in `translate`, in the same order.";
+
+#[derive(Serialize)]
+struct Cue<'a> {
+    id: usize,
+    text: &'a str,
+}
+
+#[derive(Serialize)]
+struct BatchRequest<'a> {
+    before: Vec<&'a str>,
+    translate: Vec<Cue<'a>>,
+    after: Vec<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct Translation {
+    id: usize,
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct BatchResponse {
+    translations: Vec<Translation>,
+}
+
+/// 生成一批条目的提示，`batch` 中的条目编号从 1 开始，前后各附带 `context` 条上下文
+pub fn build_prompt(
+    entries: &[SubtitleEntry],
+    batch: Range<usize>,
+    options: &TranslateOptions,
+) -> String {
+    let before = batch.start.saturating_sub(options.context)..batch.start;
+    let after = batch.end..(batch.end + options.context).min(entries.len());
+    let request = BatchRequest {
+        before: entries[before].iter().map(|e| e.text.as_str()).collect(),
+        translate: entries[batch]
+            .iter()
+            .enumerate()
+            .map(|(i, e)| Cue {
+                id: i + 1,
+                text: &e.text,
+            })
+            .collect(),
+        after: entries[after].iter().map(|e| e.text.as_str()).collect(),
+    };
+    let from = match &options.from {
+        Some(from) => format!(" from {}", from),
+        None => String::new(),
+    };
+    format!(
+        "Translate the following subtitle cues{} into {}.\n\n{}",
+        from,
+        options.to,
+        serde_json::to_string_pretty(&request).expect("strings serialize")
+    )
+}
+
+/// 解析模型的输出，检查是否恰好为每个条目（编号 1 到 `sources.len()`）返回了一条译文
+///
+/// 原文不为空时译文也不能为空，返回按编号排列的译文
+pub fn parse_translations(
+    output: &str,
+    sources: &[SubtitleEntry],
+) -> result::Result<Vec<String>, String> {
+    // 有的模型会在 JSON 前后加上说明或者代码块标记
+    let json = match (output.find('{'), output.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &output[start..=end],
+        _ => return Err("the output is not a JSON object".to_string()),
+    };
+    let response: BatchResponse =
+        serde_json::from_str(json).map_err(|e| format!("invalid JSON: {}", e))?;
+    if response.translations.len() != sources.len() {
+        return Err(format!(
+            "expected {} translations, got {}",
+            sources.len(),
+            response.translations.len()
+        ));
+    }
+
+    let mut texts: Vec<Option<String>> = vec![None; sources.len()];
+    for Translation { id, text } in response.translations {
+        let slot = id
+            .checked_sub(1)
+            .and_then(|i| texts.get_mut(i))
+            .ok_or_else(|| format!("unexpected id {}", id))?;
+        if slot.is_some() {
+            return Err(format!("duplicate id {}", id));
+        }
+        let text = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if text.is_empty() && !sources[id - 1].text.trim().is_empty() {
+            return Err(format!("empty translation for id {}", id));
+        }
+        *slot = Some(text);
+    }
+    Ok(texts.into_iter().map(Option::unwrap_or_default).collect())
+}
+
+/// 分批翻译所有条目的文本，返回与 `entries` 一一对应的译文，并在标准错误输出中显示进度
+pub async fn translate_entries<M: TextModel>(
+    model: &M,
+    entries: &[SubtitleEntry],
+    options: &TranslateOptions,
+) -> Result<Vec<String>> {
+    let batch_size = options.batch_size.max(1);
+    let mut translations = Vec::with_capacity(entries.len());
+    let mut start = 0;
+    while start < entries.len() {
+        let batch = start..(start + batch_size).min(entries.len());
+        let label = format!(
+            "translating cues {}-{} of {}",
+            batch.start + 1,
+            batch.end,
+            entries.len()
+        );
+        let prompt = build_prompt(entries, batch.clone(), options);
+
+        let mut attempt = 0;
+        let texts = loop {
+            let mut received = 0;
+            let mut on_chunk = |chunk: &str| {
+                received += chunk.chars().count();
+                let mut stderr = io::stderr();
+                let _ = write!(stderr, "\r{} ({} characters received)  ", label, received);
+                let _ = stderr.flush();
+            };
+            let output = model.generate(SYSTEM_PROMPT, &prompt, &mut on_chunk).await;
+            eprintln!();
+            match parse_translations(&output?, &entries[batch.clone()]) {
+                Ok(texts) => break texts,
+                Err(reason) if attempt < options.retries => {
+                    attempt += 1;
+                    eprintln!(
+                        "warning: {}, retrying ({}/{})",
+                        reason, attempt, options.retries
+                    );
+                }
+                Err(reason) => {
+                    return Err(TranslateError::Mismatch {
+                        first: batch.start + 1,
+                        last: batch.end,
+                        reason,
+                    });
+                }
+            }
+        };
+        translations.extend(texts);
+        start = batch.end;
+    }
+    Ok(translations)
+}
+
+/// 译文默认的保存路径：原字幕旁边的 `<字幕名>.<目标语言>.srt`
+pub fn translated_path(input: &Path, to: &str) -> PathBuf {
+    let stem = input
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    input.with_file_name(format!("{}.{}.srt", stem, to))
+}
+
+/// 与 [`translate_srt`] 相同，但是使用指定的模型
+pub async fn translate_srt_with<M: TextModel>(
+    model: &M,
+    input: impl AsRef<Path>,
+    output: Option<&Path>,
+    options: &TranslateOptions,
+) -> Result<PathBuf> {
+    let input = input.as_ref();
+    let output = output
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| translated_path(input, &options.to));
+    if output.exists() {
+        return Err(TranslateError::OutputExists(output));
+    }
+
+    let mut srt = SrtFile::read(File::open(input)?)?;
+    eprintln!(
+        "translating {} cues into {} with {}",
+        srt.len(),
+        options.to,
+        model.name()
+    );
+    let translations = translate_entries(model, &srt, options).await?;
+    srt.replace_texts(translations);
+
+    let mut file = File::create_new(&output)?;
+    srt.write(&mut file)?;
+    eprintln!("saved to {}", output.display());
+    Ok(output)
+}
+
+/// 使用本地 Ollama 上的模型将 SRT 字幕翻译为 `options.to`，返回译文保存的路径
+///
+/// `model` 为空时使用配置中的 `ollama.model`，`output` 为空时保存为 [`translated_path`]
+pub async fn translate_srt(
+    input: impl AsRef<Path>,
+    output: Option<&Path>,
+    model: Option<String>,
+    options: &TranslateOptions,
+) -> Result<PathBuf> {
+    translate_srt_with(&OllamaModel::from_config(model), input, output, options).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, sync::Mutex};
+
+    use super::*;
+
+    /// 依次返回预先准备的输出，并记录收到的提示
+    struct ScriptedModel {
+        outputs: Mutex<VecDeque<String>>,
+        prompts: Mutex<Vec<String>>,
+    }
+
+    impl ScriptedModel {
+        fn new(outputs: &[&str]) -> Self {
+            Self {
+                outputs: Mutex::new(outputs.iter().map(|o| o.to_string()).collect()),
+                prompts: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    impl TextModel for ScriptedModel {
+        fn name(&self) -> &str {
+            "scripted"
+        }
+
+        async fn generate(
+            &self,
+            _system: &str,
+            prompt: &str,
+            on_chunk: &mut (dyn FnMut(&str) + Send),
+        ) -> crate::ai::Result<String> {
+            self.prompts.lock().unwrap().push(prompt.to_string());
+            let output = self.outputs.lock().unwrap().pop_front().unwrap();
+            on_chunk(&output);
+            Ok(output)
+        }
+    }
+
+    fn entries(texts: &[&str]) -> Vec<SubtitleEntry> {
+        let content: String = texts
+            .iter()
+            .enumerate()
+            .map(|(i, text)| {
+                format!(
+                    "{}\n00:00:{:02},000 --> 00:00:{:02},500\n{}\n\n",
+                    i + 1,
+                    i,
+                    i,
+                    text
+                )
+            })
+            .collect();
+        SrtFile::read(content.as_bytes()).unwrap().to_vec()
+    }
+
+    fn reply(texts: &[&str]) -> String {
+        let items: Vec<String> = texts
+            .iter()
+            .enumerate()
+            .map(|(i, t)| format!("{{\"id\": {}, \"text\": {:?}}}", i + 1, t))
+            .collect();
+        format!("{{\"translations\": [{}]}}", items.join(", "))
+    }
+
+    #[test]
+    fn test_build_prompt_with_context() {
+        let entries = entries(&["one", "two", "three", "four", "five", "six"]);
+        let options = TranslateOptions {
+            to: "zh".to_string(),
+            from: Some("en".to_string()),
+            context: 2,
+            ..TranslateOptions::default()
+        };
+        let prompt = build_prompt(&entries, 1..3, &options);
+        assert!(prompt.starts_with("Translate the following subtitle cues from en into zh."));
+        let json: serde_json::Value =
+            serde_json::from_str(&prompt[prompt.find('{').unwrap()..]).unwrap();
+        assert_eq!(json["before"], serde_json::json!(["one"]));
+        assert_eq!(
+            json["translate"],
+            serde_json::json!([{"id": 1, "text": "two"}, {"id": 2, "text": "three"}])
+        );
+        assert_eq!(json["after"], serde_json::json!(["four", "five"]));
+    }
+
+    #[test]
+    fn test_parse_translations() {
+        let sources = entries(&["Hello.", "", "Bye."]);
+        assert_eq!(
+            parse_translations(
+                "```json\n{\"translations\": [{\"id\": 3, \"text\": \" 再见。 \"}, {\"id\": 1, \"text\": \"你好。\\n \"}, {\"id\": 2, \"text\": \"\"}]}\n```",
+                &sources
+            )
+            .unwrap(),
+            vec!["你好。", "", "再见。"]
+        );
+
+        for (output, reason) in [
+            ("no json", "not a JSON object"),
+            ("{\"translations\": 1}", "invalid JSON"),
+            (
+                &reply(&["你好。", ""]) as &str,
+                "expected 3 translations, got 2",
+            ),
+            (
+                "{\"translations\": [{\"id\": 1, \"text\": \"a\"}, {\"id\": 1, \"text\": \"b\"}, {\"id\": 3, \"text\": \"c\"}]}",
+                "duplicate id 1",
+            ),
+            (
+                "{\"translations\": [{\"id\": 0, \"text\": \"a\"}, {\"id\": 2, \"text\": \"b\"}, {\"id\": 3, \"text\": \"c\"}]}",
+                "unexpected id 0",
+            ),
+            (&reply(&["你好。", "", " "]), "empty translation for id 3"),
+        ] {
+            let err = parse_translations(output, &sources).unwrap_err();
+            assert!(err.contains(reason), "{}: {}", output, err);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_translate_in_batches_with_retry() {
+        let entries = entries(&["one", "two", "three"]);
+        let options = TranslateOptions {
+            batch_size: 2,
+            context: 1,
+            retries: 1,
+            ..TranslateOptions::default()
+        };
+        let model = ScriptedModel::new(&[&reply(&["一"]), &reply(&["一", "二"]), &reply(&["三"])]);
+        assert_eq!(
+            translate_entries(&model, &entries, &options).await.unwrap(),
+            vec!["一", "二", "三"]
+        );
+        let prompts = model.prompts.lock().unwrap().clone();
+        assert_eq!(prompts.len(), 3);
+        assert_eq!(prompts[0], prompts[1]);
+        assert!(prompts[2].contains("\"before\": [\n    \"two\"\n  ]"));
+
+        let model = ScriptedModel::new(&[&reply(&["一"]), &reply(&["一"])]);
+        assert!(matches!(
+            translate_entries(&model, &entries, &options).await,
+            Err(TranslateError::Mismatch {
+                first: 1,
+                last: 2,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_translated_path() {
+        assert_eq!(
+            translated_path(Path::new("/movies/Blow-Up.en.srt"), "zh"),
+            PathBuf::from("/movies/Blow-Up.en.zh.srt")
+        );
+    }
+}
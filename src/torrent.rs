@@ -1,143 +1,143 @@
-use std::{io, path::Path};
+//! torrent module
+//!
+//! 获取并合并多个来源的 tracker 列表，规范化、去重、过滤后按协议排序，检测 tracker 是否可用，
+//! 并推送到 BT 客户端；解析、修改和创建 .torrent 文件
+
+pub mod bencode;
+pub mod cache;
+pub mod client;
+pub mod create;
+pub mod health;
+pub mod inject;
+pub mod magnet;
+pub mod metainfo;
+pub mod output;
+pub mod piece;
+pub mod source;
+pub mod tracker;
+pub mod verify;
+
+use std::{io, path::Path, result};
 
-use chrono::{DateTime, Local};
-use octocrab::models::repos::Content;
 use thiserror::Error;
-use tokio::{
-    fs::File,
-    io::{AsyncReadExt, AsyncWriteExt},
-};
 
-const TIME_FMT: &'static str = "%Y-%m-%d %H:%M:%S %z";
-const UPDATE_TIME_RECORD: &'static str = "update_record";
-const OWNER: &'static str = "ngosang";
-const REPO: &'static str = "trackerslist";
+use cache::TrackerCache;
+use output::{TrackerListFormat, TrackerOutput};
+use source::{TrackerSource, fetch_sources, merge_outcomes, print_outcomes};
+use tracker::TrackerPolicy;
+
+/// `tracker update` 默认的输出文件
+pub const DEFAULT_TRACKER_FILE: &str = "tracker_all.txt";
 
 #[derive(Debug, Error)]
 pub enum TorrentError {
-    #[error("Unexpected file type or file not exists")]
-    InvalidFile,
     #[error("Network transfer error.")]
     NetworkError,
-    #[error("Unknown last modify time error")]
-    UnknownLastModifyTimeError,
+    #[error("No trackers were fetched from any source")]
+    NoTrackers,
+    #[error("Invalid tracker url: {0}")]
+    InvalidTracker(String),
+    #[error("Invalid tracker pattern: {0}")]
+    PatternError(glob::PatternError),
+    #[error("Tracker timed out")]
+    TrackerTimeout,
+    #[error("Tracker protocol error: {0}")]
+    TrackerProtocol(String),
     #[error("IO error: {0}")]
     IoError(io::Error),
-    #[error("Octocrab error: {0}")]
-    OctocrabError(octocrab::Error),
-}
-
-impl From<octocrab::Error> for TorrentError {
-    fn from(value: octocrab::Error) -> Self {
-        Self::OctocrabError(value)
-    }
+    #[error("HTTP error: {0}")]
+    HttpError(#[from] reqwest::Error),
+    #[error("Failed to log in to {0}, check the username and password")]
+    LoginFailed(&'static str),
+    #[error("{client} returned {status}: {message}")]
+    ClientError {
+        client: &'static str,
+        status: u16,
+        message: String,
+    },
+    #[error("The {0} client is not configured, add a [clients.{0}] section to the config file")]
+    ClientNotConfigured(&'static str),
+    #[error(
+        "No BitTorrent client is configured, add a [clients.<name>] section to the config file"
+    )]
+    NoClientsConfigured,
+    #[error("Bencode error: {0}")]
+    Bencode(#[from] bencode::BencodeError),
+    #[error("Invalid torrent file: {0}")]
+    InvalidMetainfo(String),
+    #[error(
+        "Refusing to modify private torrent {0}, it may only use the trackers it was created with"
+    )]
+    PrivateTorrent(String),
+    #[error("Invalid magnet link: {0}")]
+    InvalidMagnet(String),
+    #[error("Nothing to share in {0}, it contains no data")]
+    NothingToShare(String),
+    #[error("Invalid piece size {0}, it must be a power of two of at least 16 KiB")]
+    InvalidPieceLength(u64),
+    #[error("{bad} of {total} pieces failed verification")]
+    VerifyFailed { bad: usize, total: usize },
 }
 
-pub async fn download_newest_tracker() -> Result<(), TorrentError> {
-    let req_path = format!("/repos/{}/{}/contents/trackers_all.txt", OWNER, REPO);
-    let github = octocrab::instance();
-    let content = github._get(&req_path).await?;
-    let last_modified = content
-        .headers()
-        .get("last-modified")
-        .ok_or(TorrentError::UnknownLastModifyTimeError)?
-        .to_str()
-        .map_err(|_| TorrentError::NetworkError)?;
-    let last_modified = DateTime::parse_from_rfc2822(last_modified)
-        .map_err(|_| TorrentError::NetworkError)?
-        .with_timezone(&Local);
-    match update_time_record().await {
-        Ok(last_update) => {
-            if last_modified <= last_update {
-                return Ok(());
-            } else {
-                File::options()
-                    .write(true)
-                    .append(true)
-                    .open(UPDATE_TIME_RECORD)
-                    .await
-                    .map_err(|e| TorrentError::IoError(e))?
-                    .write_all(format!("{}\n", last_modified.format(TIME_FMT)).as_bytes())
-                    .await
-                    .map_err(|e| TorrentError::IoError(e))?;
-            }
-        }
-        Err(err) => {
-            eprintln!("retrieve last update time error: {:?}", err);
-            File::create_new(UPDATE_TIME_RECORD)
-                .await
-                .map_err(|e| TorrentError::IoError(e))?
-                .write_all(format!("{}", last_modified.format(TIME_FMT)).as_bytes())
-                .await
-                .map_err(|e| TorrentError::IoError(e))?;
-        }
-    }
-
-    let content: Content = github.get(&req_path, None::<&()>).await.unwrap();
-    File::create("tracker_all.txt")
-        .await
-        .map_err(|e| TorrentError::IoError(e))?
-        .write_all(content.decoded_content().unwrap().as_bytes())
-        .await
-        .map_err(|e| TorrentError::IoError(e))?;
-    Ok(())
-}
+pub type Result<T> = result::Result<T, TorrentError>;
 
-/// 查找上一次tracker文件的更新时间
-/// # Error
-/// * 打开记录文件可能发生错误
-/// * 时间的反序列化可能产生错误
-async fn update_time_record() -> Result<DateTime<Local>, TorrentError> {
-    let path = Path::new(UPDATE_TIME_RECORD);
-    if path.is_file() {
-        let mut buf = String::new();
-        let _ = File::open(UPDATE_TIME_RECORD)
-            .await
-            .map_err(|e| TorrentError::IoError(e))?
-            .read_to_string(&mut buf)
-            .await
-            .map_err(|e| TorrentError::IoError(e))?;
-        let last_record = buf.rsplit("\n").next().ok_or(TorrentError::InvalidFile)?;
-        Ok(DateTime::parse_from_str(last_record, TIME_FMT)
-            .map_err(|_| TorrentError::InvalidFile)?
-            .with_timezone(&Local))
-    } else {
-        Err(TorrentError::IoError(io::Error::new(
-            io::ErrorKind::NotFound,
-            "the old trake file is not found",
-        )))
-    }
+/// 与 [`download_newest_tracker_with`] 相同，使用缓存目录下的 `trackers.json` 作为缓存
+pub async fn download_newest_tracker(
+    sources: &[TrackerSource],
+    policy: &TrackerPolicy,
+    output: &TrackerOutput,
+    format: TrackerListFormat,
+) -> Result<()> {
+    download_newest_tracker_with(
+        sources,
+        policy,
+        output,
+        format,
+        TrackerCache::default_path().as_deref(),
+    )
+    .await
 }
 
-#[cfg(test)]
-mod tests {
-
-    use chrono::DateTime;
-    use tokio::{
-        fs::{self, File},
-        io::AsyncWriteExt,
-    };
+/// 并发获取所有来源的 tracker 列表，合并后按照 `policy` 规范化、去重、过滤并排序，以 `format` 格式写入 `output`
+///
+/// 远程来源使用 `cache_file` 中记录的 ETag/Last-Modified 发送条件请求，网络不可用时使用缓存的列表。
+/// 单个来源失败只打印错误，所有来源都没有得到 tracker 时返回错误，此时不会修改输出文件
+pub async fn download_newest_tracker_with(
+    sources: &[TrackerSource],
+    policy: &TrackerPolicy,
+    output: &TrackerOutput,
+    format: TrackerListFormat,
+    cache_file: Option<&Path>,
+) -> Result<()> {
+    let mut cache = cache_file.map(TrackerCache::load).unwrap_or_default();
 
-    use super::*;
+    let outcomes = fetch_sources(sources, &mut cache).await;
+    print_outcomes(&outcomes);
+    if let Some(path) = cache_file
+        && let Err(e) = cache.store(path)
+    {
+        eprintln!(
+            "warning: failed to write the tracker cache {:?}: {}",
+            path, e
+        );
+    }
 
-    #[tokio::test]
-    async fn test_update_time_record() {
-        // 如果没有记录文件
-        assert!(update_time_record().await.is_err());
-        // 如果文件没有有效记录
-        let mut f = File::create(UPDATE_TIME_RECORD).await.unwrap();
-        f.write_all("2025-07-22 15:24:44".as_bytes()).await.unwrap();
-        assert!(update_time_record().await.is_err());
-        drop(f);
-        fs::remove_file(UPDATE_TIME_RECORD).await.unwrap();
-        // 文件正确，读取的记录也正确
-        let mut f = File::create(UPDATE_TIME_RECORD).await.unwrap();
-        f.write_all("2025-07-22 15:24:44 +0800".as_bytes())
-            .await
-            .unwrap();
-        let tt = DateTime::parse_from_str("2025-07-22 15:24:44 +0800", TIME_FMT).unwrap();
-        assert_eq!(update_time_record().await.unwrap(), tt);
-        drop(f);
-        fs::remove_file(UPDATE_TIME_RECORD).await.unwrap();
+    let normalized = policy.apply(merge_outcomes(&outcomes));
+    for invalid in &normalized.invalid {
+        eprintln!("skipped invalid tracker: {}", invalid);
+    }
+    eprintln!(
+        "{} duplicates removed, {} trackers blocked",
+        normalized.duplicates, normalized.blocked
+    );
+    let trackers: Vec<&str> = normalized.trackers.iter().map(|t| t.as_str()).collect();
+    if trackers.is_empty() {
+        return Err(TorrentError::NoTrackers);
     }
+
+    output
+        .write(&format.render(&trackers))
+        .map_err(TorrentError::IoError)?;
+    eprintln!("{} trackers written to {}", trackers.len(), output);
+    Ok(())
 }
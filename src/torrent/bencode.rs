@@ -0,0 +1,398 @@
+//! bencode 编解码
+//!
+//! 解码时记录字典中每个值在原始数据中的位置，计算 info-hash 以及修改种子时可以直接使用原始字节
+
+use std::{collections::BTreeMap, ops::Range, result};
+
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum BencodeError {
+    #[error("Unexpected end of data at byte {0}")]
+    UnexpectedEof(usize),
+    #[error("Unexpected byte {byte:#04x} at byte {pos}")]
+    UnexpectedByte { pos: usize, byte: u8 },
+    #[error("Invalid integer at byte {0}")]
+    InvalidInteger(usize),
+    #[error("Invalid string length at byte {0}")]
+    InvalidLength(usize),
+    #[error("Trailing data after byte {0}")]
+    TrailingData(usize),
+    #[error("Nesting is too deep at byte {0}")]
+    TooDeep(usize),
+}
+
+pub type Result<T> = result::Result<T, BencodeError>;
+
+/// 防止恶意构造的数据导致栈溢出
+const MAX_DEPTH: usize = 64;
+
+/// bencode 值，字典按照键的字节序排列，与规范的编码顺序一致
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Int(i64),
+    Bytes(Vec<u8>),
+    List(Vec<Value>),
+    Dict(BTreeMap<Vec<u8>, Value>),
+}
+
+impl Value {
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Self::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Self::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// 字节串是合法的 UTF-8 时返回字符串
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_bytes().and_then(|b| std::str::from_utf8(b).ok())
+    }
+
+    pub fn as_list(&self) -> Option<&[Value]> {
+        match self {
+            Self::List(l) => Some(l),
+            _ => None,
+        }
+    }
+
+    pub fn as_dict(&self) -> Option<&BTreeMap<Vec<u8>, Value>> {
+        match self {
+            Self::Dict(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    /// 字典中 `key` 对应的值，不是字典或者没有该键时返回 `None`
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.as_dict().and_then(|d| d.get(key.as_bytes()))
+    }
+
+    /// 编码为 bencode 字节
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_to(&mut out);
+        out
+    }
+
+    pub fn encode_to(&self, out: &mut Vec<u8>) {
+        match self {
+            Self::Int(i) => {
+                out.push(b'i');
+                out.extend(i.to_string().as_bytes());
+                out.push(b'e');
+            }
+            Self::Bytes(b) => encode_bytes(b, out),
+            Self::List(l) => {
+                out.push(b'l');
+                for v in l {
+                    v.encode_to(out);
+                }
+                out.push(b'e');
+            }
+            Self::Dict(d) => {
+                out.push(b'd');
+                for (k, v) in d {
+                    encode_bytes(k, out);
+                    v.encode_to(out);
+                }
+                out.push(b'e');
+            }
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Self::Bytes(s.as_bytes().to_vec())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Self::Bytes(s.into_bytes())
+    }
+}
+
+impl From<i64> for Value {
+    fn from(i: i64) -> Self {
+        Self::Int(i)
+    }
+}
+
+/// 编码字典，`raw` 中的键直接写入已经编码好的原始字节而不重新编码，
+/// 与 `dict` 中的键一起按字节序排列，同一个键以 `raw` 为准
+pub fn encode_dict_with_raw(dict: &BTreeMap<Vec<u8>, Value>, raw: &[(&[u8], &[u8])]) -> Vec<u8> {
+    enum Part<'a> {
+        Value(&'a Value),
+        Raw(&'a [u8]),
+    }
+    let mut parts: BTreeMap<&[u8], Part> = dict
+        .iter()
+        .map(|(k, v)| (k.as_slice(), Part::Value(v)))
+        .collect();
+    for (k, v) in raw {
+        parts.insert(k, Part::Raw(v));
+    }
+
+    let mut out = vec![b'd'];
+    for (k, part) in parts {
+        encode_bytes(k, &mut out);
+        match part {
+            Part::Value(v) => v.encode_to(&mut out),
+            Part::Raw(v) => out.extend(v),
+        }
+    }
+    out.push(b'e');
+    out
+}
+
+fn encode_bytes(b: &[u8], out: &mut Vec<u8>) {
+    out.extend(b.len().to_string().as_bytes());
+    out.push(b':');
+    out.extend(b);
+}
+
+/// 解码完整的 bencode 数据，末尾不能有多余的字节
+pub fn decode(data: &[u8]) -> Result<Value> {
+    let mut parser = Parser { data, pos: 0 };
+    let value = parser.value(0)?;
+    parser.finish()?;
+    Ok(value)
+}
+
+/// 字典中每个键的值在原始数据中的字节范围
+pub type Spans = BTreeMap<Vec<u8>, Range<usize>>;
+
+/// 解码顶层的字典，同时返回每个键的值在 `data` 中的字节范围
+pub fn decode_dict_spans(data: &[u8]) -> Result<(Value, Spans)> {
+    let mut parser = Parser { data, pos: 0 };
+    parser.expect(b'd')?;
+    let mut dict = BTreeMap::new();
+    let mut spans = BTreeMap::new();
+    while parser.peek()? != b'e' {
+        let key = parser.bytes()?;
+        let start = parser.pos;
+        let value = parser.value(1)?;
+        spans.insert(key.clone(), start..parser.pos);
+        dict.insert(key, value);
+    }
+    parser.pos += 1;
+    parser.finish()?;
+    Ok((Value::Dict(dict), spans))
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Result<u8> {
+        self.data
+            .get(self.pos)
+            .copied()
+            .ok_or(BencodeError::UnexpectedEof(self.pos))
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<()> {
+        match self.peek()? {
+            b if b == byte => {
+                self.pos += 1;
+                Ok(())
+            }
+            b => Err(BencodeError::UnexpectedByte {
+                pos: self.pos,
+                byte: b,
+            }),
+        }
+    }
+
+    fn finish(&self) -> Result<()> {
+        if self.pos == self.data.len() {
+            Ok(())
+        } else {
+            Err(BencodeError::TrailingData(self.pos))
+        }
+    }
+
+    /// 读取到 `end` 之前的数字，不允许前导 0 和 `-0`
+    fn digits(&mut self, end: u8, signed: bool) -> Result<&[u8]> {
+        let start = self.pos;
+        let len = self.data[start..]
+            .iter()
+            .position(|&b| b == end)
+            .ok_or(BencodeError::UnexpectedEof(self.data.len()))?;
+        let digits = &self.data[start..start + len];
+        let unsigned = match digits {
+            [b'-', rest @ ..] if signed => rest,
+            _ => digits,
+        };
+        let valid = !unsigned.is_empty()
+            && unsigned.iter().all(u8::is_ascii_digit)
+            && (unsigned == b"0" || unsigned[0] != b'0')
+            && digits != b"-0";
+        if !valid {
+            return Err(if signed {
+                BencodeError::InvalidInteger(start)
+            } else {
+                BencodeError::InvalidLength(start)
+            });
+        }
+        self.pos += len + 1;
+        Ok(digits)
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>> {
+        let start = self.pos;
+        let len: usize = std::str::from_utf8(self.digits(b':', false)?)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or(BencodeError::InvalidLength(start))?;
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or(BencodeError::UnexpectedEof(self.data.len()))?;
+        let bytes = self.data[self.pos..end].to_vec();
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value> {
+        if depth > MAX_DEPTH {
+            return Err(BencodeError::TooDeep(self.pos));
+        }
+        match self.peek()? {
+            b'i' => {
+                self.pos += 1;
+                let start = self.pos;
+                let i = std::str::from_utf8(self.digits(b'e', true)?)
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .ok_or(BencodeError::InvalidInteger(start))?;
+                Ok(Value::Int(i))
+            }
+            b'0'..=b'9' => Ok(Value::Bytes(self.bytes()?)),
+            b'l' => {
+                self.pos += 1;
+                let mut list = Vec::new();
+                while self.peek()? != b'e' {
+                    list.push(self.value(depth + 1)?);
+                }
+                self.pos += 1;
+                Ok(Value::List(list))
+            }
+            b'd' => {
+                self.pos += 1;
+                let mut dict = BTreeMap::new();
+                while self.peek()? != b'e' {
+                    if !self.peek()?.is_ascii_digit() {
+                        return Err(BencodeError::UnexpectedByte {
+                            pos: self.pos,
+                            byte: self.peek()?,
+                        });
+                    }
+                    let key = self.bytes()?;
+                    dict.insert(key, self.value(depth + 1)?);
+                }
+                self.pos += 1;
+                Ok(Value::Dict(dict))
+            }
+            byte => Err(BencodeError::UnexpectedByte {
+                pos: self.pos,
+                byte,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_values() {
+        assert_eq!(decode(b"i42e"), Ok(Value::Int(42)));
+        assert_eq!(decode(b"i-7e"), Ok(Value::Int(-7)));
+        assert_eq!(decode(b"i0e"), Ok(Value::Int(0)));
+        assert_eq!(decode(b"4:spam"), Ok(Value::from("spam")));
+        assert_eq!(decode(b"0:"), Ok(Value::Bytes(vec![])));
+        assert_eq!(
+            decode(b"l4:spami3ee"),
+            Ok(Value::List(vec![Value::from("spam"), Value::Int(3)]))
+        );
+        let dict = decode(b"d3:cow3:moo4:spaml1:a1:bee").unwrap();
+        assert_eq!(dict.get("cow").and_then(Value::as_str), Some("moo"));
+        assert_eq!(dict.get("spam").and_then(Value::as_list).unwrap().len(), 2);
+        assert_eq!(dict.get("missing"), None);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert_eq!(decode(b"i03e"), Err(BencodeError::InvalidInteger(1)));
+        assert_eq!(decode(b"i-0e"), Err(BencodeError::InvalidInteger(1)));
+        assert_eq!(decode(b"ie"), Err(BencodeError::InvalidInteger(1)));
+        assert_eq!(decode(b"i12"), Err(BencodeError::UnexpectedEof(3)));
+        assert_eq!(decode(b"5:spam"), Err(BencodeError::UnexpectedEof(6)));
+        assert_eq!(decode(b"01:a"), Err(BencodeError::InvalidLength(0)));
+        assert_eq!(decode(b"l4:spam"), Err(BencodeError::UnexpectedEof(7)));
+        assert_eq!(decode(b"i1ei2e"), Err(BencodeError::TrailingData(3)));
+        assert_eq!(
+            decode(b"di1ei2ee"),
+            Err(BencodeError::UnexpectedByte { pos: 1, byte: b'i' })
+        );
+        assert_eq!(
+            decode(b"x"),
+            Err(BencodeError::UnexpectedByte { pos: 0, byte: b'x' })
+        );
+        let deep = format!("{}{}", "l".repeat(100), "e".repeat(100));
+        assert!(matches!(
+            decode(deep.as_bytes()),
+            Err(BencodeError::TooDeep(_))
+        ));
+    }
+
+    #[test]
+    fn test_encode_roundtrip() {
+        let data: &[u8] = b"d8:announce9:udp://a:14:infod6:lengthi1024e4:name5:a.mkv6:pieces0:ee";
+        let value = decode(data).unwrap();
+        assert_eq!(value.encode(), data);
+
+        // 编码时按照键的字节序排列
+        let mut dict = BTreeMap::new();
+        dict.insert(b"z".to_vec(), Value::Int(1));
+        dict.insert(b"a".to_vec(), Value::List(vec![Value::from("x")]));
+        assert_eq!(Value::Dict(dict).encode(), b"d1:al1:xe1:zi1ee");
+    }
+
+    #[test]
+    fn test_encode_dict_with_raw() {
+        let mut dict = BTreeMap::new();
+        dict.insert(b"announce".to_vec(), Value::from("url"));
+        dict.insert(b"info".to_vec(), Value::Int(0));
+        // 原始字节中的键没有排序，重新编码会改变内容
+        let raw: &[u8] = b"d4:name1:a6:lengthi1ee";
+        assert_eq!(
+            encode_dict_with_raw(&dict, &[(b"info", raw)]),
+            b"d8:announce3:url4:infod4:name1:a6:lengthi1eee"
+        );
+    }
+
+    #[test]
+    fn test_decode_dict_spans() {
+        let data: &[u8] = b"d8:announce3:url4:infod4:name1:aee";
+        let (value, spans) = decode_dict_spans(data).unwrap();
+        assert_eq!(&data[spans[&b"info".to_vec()].clone()], b"d4:name1:ae");
+        assert_eq!(&data[spans[&b"announce".to_vec()].clone()], b"3:url");
+        assert_eq!(value.get("announce").and_then(Value::as_str), Some("url"));
+        assert!(decode_dict_spans(b"l1:ae").is_err());
+    }
+}
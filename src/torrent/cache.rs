@@ -0,0 +1,90 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::output::write_atomic;
+use crate::config::cache_dir;
+
+/// 缓存目录中 tracker 列表缓存文件的名称
+pub const CACHE_FILE: &str = "trackers.json";
+
+/// 所有远程来源的 tracker 列表缓存，保存在同一个 JSON 文件中
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackerCache {
+    /// 以来源的字符串表示为键
+    #[serde(default)]
+    pub sources: BTreeMap<String, CachedSource>,
+}
+
+/// 单个来源上一次成功获取的内容以及用于条件请求的校验信息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedSource {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// RFC 3339 格式的获取时间
+    pub fetched_at: String,
+    pub trackers: Vec<String>,
+}
+
+impl TrackerCache {
+    /// 默认的缓存文件，为缓存目录下的 `trackers.json`
+    pub fn default_path() -> Option<PathBuf> {
+        cache_dir().map(|dir| dir.join(CACHE_FILE))
+    }
+
+    /// 读取缓存文件，文件不存在或者损坏时返回空的缓存
+    pub fn load(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                eprintln!("warning: ignoring corrupt tracker cache {:?}: {}", path, e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// 通过 [`write_atomic`] 写入缓存文件，避免中断时留下不完整的文件
+    pub fn store(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        write_atomic(path, serde_json::to_string_pretty(self)?.as_bytes())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&CachedSource> {
+        self.sources.get(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_and_store_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join(CACHE_FILE);
+        assert_eq!(TrackerCache::load(&path), TrackerCache::default());
+
+        let mut cache = TrackerCache::default();
+        cache.sources.insert(
+            "https://example.org/trackers.txt".to_string(),
+            CachedSource {
+                etag: Some("\"abc\"".to_string()),
+                last_modified: None,
+                fetched_at: "2026-10-18T12:00:00+08:00".to_string(),
+                trackers: vec!["udp://a.example:1337/announce".to_string()],
+            },
+        );
+        cache.store(&path).unwrap();
+        assert_eq!(TrackerCache::load(&path), cache);
+        assert!(!path.with_extension("json.tmp").exists());
+
+        fs::write(&path, "{ not json").unwrap();
+        assert_eq!(TrackerCache::load(&path), TrackerCache::default());
+    }
+}
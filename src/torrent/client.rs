@@ -0,0 +1,208 @@
+//! 将 tracker 列表推送到 BT 客户端
+
+pub mod aria2;
+pub mod qbittorrent;
+pub mod transmission;
+
+use std::{future::Future, path::Path, time::Duration};
+
+use clap::ValueEnum;
+use reqwest::Client;
+use serde::Deserialize;
+
+use super::{Result, TorrentError, source::parse_tracker_list};
+use crate::config::ClientsConfig;
+use aria2::Aria2Client;
+use qbittorrent::QbittorrentClient;
+use transmission::TransmissionClient;
+
+/// 单个请求的超时时间，避免无响应的客户端阻塞推送
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 各个客户端共用的 HTTP 客户端
+fn http_client() -> Result<Client> {
+    Ok(Client::builder().timeout(REQUEST_TIMEOUT).build()?)
+}
+
+/// 支持的 BT 客户端
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientKind {
+    Qbittorrent,
+    Transmission,
+    Aria2,
+}
+
+impl ClientKind {
+    /// 配置文件中的名称，即 `[clients.<name>]`
+    pub fn config_name(&self) -> &'static str {
+        match self {
+            Self::Qbittorrent => "qbittorrent",
+            Self::Transmission => "transmission",
+            Self::Aria2 => "aria2",
+        }
+    }
+}
+
+/// 一次推送的结果
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PushReport {
+    /// 添加了 tracker 的已有种子数量
+    pub updated: usize,
+    /// 跳过的私有种子数量
+    pub skipped_private: usize,
+}
+
+/// 可以接收 tracker 列表的 BT 客户端
+pub trait TrackerClient: Sync {
+    /// 用于输出的客户端名称
+    fn name(&self) -> &'static str;
+
+    /// 将 tracker 设置为新种子的默认 tracker，`update_existing` 时同时添加到已有的非私有种子
+    fn push(
+        &self,
+        trackers: &[String],
+        update_existing: bool,
+    ) -> impl Future<Output = Result<PushReport>> + Send;
+}
+
+/// 配置文件中已经配置的客户端
+pub fn configured_clients(config: &ClientsConfig) -> Vec<ClientKind> {
+    let mut kinds = Vec::new();
+    if config.qbittorrent.is_some() {
+        kinds.push(ClientKind::Qbittorrent);
+    }
+    if config.transmission.is_some() {
+        kinds.push(ClientKind::Transmission);
+    }
+    if config.aria2.is_some() {
+        kinds.push(ClientKind::Aria2);
+    }
+    kinds
+}
+
+/// 推送到单个客户端并打印结果
+pub async fn push_to<C: TrackerClient>(
+    client: &C,
+    trackers: &[String],
+    update_existing: bool,
+) -> Result<PushReport> {
+    let result = client.push(trackers, update_existing).await;
+    match &result {
+        Ok(report) => eprintln!(
+            "[ok]     {}: {} trackers set as default, {} torrents updated, {} private torrents skipped",
+            client.name(),
+            trackers.len(),
+            report.updated,
+            report.skipped_private
+        ),
+        Err(e) => eprintln!("[failed] {}: {}", client.name(), e),
+    }
+    result
+}
+
+/// 读取 `input` 中的 tracker 列表并推送到指定的客户端，`kinds` 为空时推送到所有已配置的客户端
+///
+/// 单个客户端失败不影响其它客户端，有任何客户端失败时返回最后一个错误
+pub async fn push_trackers(
+    config: &ClientsConfig,
+    kinds: &[ClientKind],
+    input: impl AsRef<Path>,
+    update_existing: bool,
+) -> Result<()> {
+    let kinds = if kinds.is_empty() {
+        configured_clients(config)
+    } else {
+        kinds.to_vec()
+    };
+    if kinds.is_empty() {
+        return Err(TorrentError::NoClientsConfigured);
+    }
+
+    let content = tokio::fs::read_to_string(input)
+        .await
+        .map_err(TorrentError::IoError)?;
+    let trackers = parse_tracker_list(&content);
+    if trackers.is_empty() {
+        return Err(TorrentError::NoTrackers);
+    }
+
+    let mut last_error = None;
+    for kind in kinds {
+        let not_configured = || TorrentError::ClientNotConfigured(kind.config_name());
+        let result = match kind {
+            ClientKind::Qbittorrent => match &config.qbittorrent {
+                Some(c) => match QbittorrentClient::from_config(c) {
+                    Ok(client) => push_to(&client, &trackers, update_existing).await,
+                    Err(e) => Err(e),
+                },
+                None => Err(not_configured()),
+            },
+            ClientKind::Transmission => match &config.transmission {
+                Some(c) => match TransmissionClient::from_config(c) {
+                    Ok(client) => push_to(&client, &trackers, update_existing).await,
+                    Err(e) => Err(e),
+                },
+                None => Err(not_configured()),
+            },
+            ClientKind::Aria2 => match &config.aria2 {
+                Some(c) => match Aria2Client::from_config(c) {
+                    Ok(client) => push_to(&client, &trackers, update_existing).await,
+                    Err(e) => Err(e),
+                },
+                None => Err(not_configured()),
+            },
+        };
+        if let Err(e) = result {
+            last_error = Some(e);
+        }
+    }
+    match last_error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Aria2Config, QbittorrentConfig};
+
+    #[test]
+    fn test_configured_clients() {
+        let mut config = ClientsConfig::default();
+        assert!(configured_clients(&config).is_empty());
+
+        config.aria2 = Some(Aria2Config {
+            url: "http://localhost:6800/jsonrpc".to_string(),
+            secret: None,
+        });
+        config.qbittorrent = Some(QbittorrentConfig {
+            url: "http://localhost:8080".to_string(),
+            username: None,
+            password: None,
+        });
+        assert_eq!(
+            configured_clients(&config),
+            vec![ClientKind::Qbittorrent, ClientKind::Aria2]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_push_trackers_without_clients() {
+        assert!(matches!(
+            push_trackers(&ClientsConfig::default(), &[], "missing.txt", true).await,
+            Err(TorrentError::NoClientsConfigured)
+        ));
+        assert!(matches!(
+            push_trackers(
+                &ClientsConfig::default(),
+                &[ClientKind::Transmission],
+                "missing.txt",
+                true
+            )
+            .await,
+            Err(TorrentError::IoError(_))
+        ));
+    }
+}
@@ -0,0 +1,96 @@
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::{PushReport, TrackerClient, http_client};
+use crate::{
+    config::Aria2Config,
+    torrent::{Result, TorrentError},
+};
+
+const CLIENT: &str = "aria2";
+
+/// aria2 JSON-RPC 的客户端
+#[derive(Debug, Clone)]
+pub struct Aria2Client {
+    http: Client,
+    url: String,
+    secret: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcResponse {
+    #[serde(default)]
+    error: Option<RpcError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl Aria2Client {
+    /// `url` 为 RPC 的完整地址，例如 `http://localhost:6800/jsonrpc`，`secret` 为 `--rpc-secret`
+    pub fn new(url: impl Into<String>, secret: Option<String>) -> Result<Self> {
+        Ok(Self {
+            http: http_client()?,
+            url: url.into(),
+            secret,
+        })
+    }
+
+    pub fn from_config(config: &Aria2Config) -> Result<Self> {
+        Self::new(config.url.clone(), config.secret.clone())
+    }
+
+    async fn call(&self, method: &str, mut params: Vec<Value>) -> Result<()> {
+        if let Some(secret) = &self.secret {
+            params.insert(0, json!(format!("token:{}", secret)));
+        }
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": "blowup",
+            "method": method,
+            "params": params,
+        });
+        let response = self.http.post(&self.url).json(&body).send().await?;
+        let status = response.status();
+        // aria2 在出错时同样返回 JSON-RPC 的错误对象，但 HTTP 状态码不一定是 200
+        let text = response.text().await?;
+        match serde_json::from_str::<RpcResponse>(&text) {
+            Ok(RpcResponse { error: Some(error) }) if error.message == "Unauthorized" => {
+                Err(TorrentError::LoginFailed(CLIENT))
+            }
+            Ok(RpcResponse { error: Some(error) }) => Err(TorrentError::ClientError {
+                client: CLIENT,
+                status: status.as_u16(),
+                message: format!("{} (code {})", error.message, error.code),
+            }),
+            Ok(_) if status.is_success() => Ok(()),
+            _ => Err(TorrentError::ClientError {
+                client: CLIENT,
+                status: status.as_u16(),
+                message: text,
+            }),
+        }
+    }
+}
+
+impl TrackerClient for Aria2Client {
+    fn name(&self) -> &'static str {
+        CLIENT
+    }
+
+    /// 设置全局的 `bt-tracker` 选项
+    ///
+    /// aria2 不能修改已经开始的下载的 tracker，只对之后添加的种子生效
+    async fn push(&self, trackers: &[String], _update_existing: bool) -> Result<PushReport> {
+        self.call(
+            "aria2.changeGlobalOption",
+            vec![json!({ "bt-tracker": trackers.join(",") })],
+        )
+        .await?;
+        Ok(PushReport::default())
+    }
+}
@@ -0,0 +1,194 @@
+use reqwest::{
+    Client, RequestBuilder, Response,
+    header::{COOKIE, REFERER, SET_COOKIE},
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{PushReport, TrackerClient, http_client};
+use crate::{
+    config::QbittorrentConfig,
+    torrent::{Result, TorrentError},
+};
+
+const CLIENT: &str = "qBittorrent";
+
+/// qBittorrent WebUI API（v2）的客户端
+#[derive(Debug, Clone)]
+pub struct QbittorrentClient {
+    http: Client,
+    base_url: String,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TorrentInfo {
+    hash: String,
+    /// 旧版本的 WebUI 没有这个字段
+    #[serde(default)]
+    private: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TorrentProperties {
+    /// 同样只有较新的 WebUI 才会返回
+    #[serde(default)]
+    is_private: Option<bool>,
+}
+
+impl QbittorrentClient {
+    /// `base_url` 为 WebUI 的地址，例如 `http://localhost:8080`；没有用户名时不登录（本机免认证）
+    pub fn new(
+        base_url: impl Into<String>,
+        username: Option<String>,
+        password: Option<String>,
+    ) -> Result<Self> {
+        Ok(Self {
+            http: http_client()?,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            username,
+            password,
+        })
+    }
+
+    pub fn from_config(config: &QbittorrentConfig) -> Result<Self> {
+        Self::new(
+            config.url.clone(),
+            config.username.clone(),
+            config.password.clone(),
+        )
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/api/v2/{}", self.base_url, path)
+    }
+
+    /// WebUI 会拒绝 Referer 与其地址不一致的请求
+    fn request(&self, builder: RequestBuilder, cookie: Option<&str>) -> RequestBuilder {
+        let builder = builder.header(REFERER, &self.base_url);
+        match cookie {
+            Some(cookie) => builder.header(COOKIE, cookie),
+            None => builder,
+        }
+    }
+
+    /// 登录并返回会话的 cookie
+    async fn login(&self) -> Result<Option<String>> {
+        let Some(username) = &self.username else {
+            return Ok(None);
+        };
+        let response = self
+            .request(self.http.post(self.url("auth/login")), None)
+            .form(&[
+                ("username", username.as_str()),
+                ("password", self.password.as_deref().unwrap_or_default()),
+            ])
+            .send()
+            .await?;
+        let response = check_status(response).await?;
+        let cookie = response
+            .headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .filter_map(|v| v.split(';').next())
+            .find(|v| v.starts_with("SID="))
+            .map(str::to_string);
+        let body = response.text().await?;
+        match cookie {
+            Some(cookie) if body.trim() == "Ok." => Ok(Some(cookie)),
+            _ => Err(TorrentError::LoginFailed(CLIENT)),
+        }
+    }
+
+    /// 设置新添加的种子自动附加的 tracker
+    async fn set_default_trackers(&self, cookie: Option<&str>, trackers: &[String]) -> Result<()> {
+        let preferences = json!({
+            "add_trackers_enabled": true,
+            "add_trackers": trackers.join("\n"),
+        });
+        let response = self
+            .request(self.http.post(self.url("app/setPreferences")), cookie)
+            .form(&[("json", preferences.to_string())])
+            .send()
+            .await?;
+        check_status(response).await?;
+        Ok(())
+    }
+
+    async fn list_torrents(&self, cookie: Option<&str>) -> Result<Vec<TorrentInfo>> {
+        let response = self
+            .request(self.http.get(self.url("torrents/info")), cookie)
+            .send()
+            .await?;
+        Ok(check_status(response).await?.json().await?)
+    }
+
+    /// 种子是否为私有种子，WebUI 不返回时当作私有种子，以免泄露私有种子的信息
+    async fn is_private(&self, cookie: Option<&str>, torrent: &TorrentInfo) -> Result<bool> {
+        if let Some(private) = torrent.private {
+            return Ok(private);
+        }
+        let response = self
+            .request(self.http.get(self.url("torrents/properties")), cookie)
+            .query(&[("hash", &torrent.hash)])
+            .send()
+            .await?;
+        let properties: TorrentProperties = check_status(response).await?.json().await?;
+        Ok(properties.is_private.unwrap_or(true))
+    }
+
+    async fn add_trackers(
+        &self,
+        cookie: Option<&str>,
+        hash: &str,
+        trackers: &[String],
+    ) -> Result<()> {
+        let response = self
+            .request(self.http.post(self.url("torrents/addTrackers")), cookie)
+            .form(&[("hash", hash), ("urls", &trackers.join("\n"))])
+            .send()
+            .await?;
+        check_status(response).await?;
+        Ok(())
+    }
+}
+
+impl TrackerClient for QbittorrentClient {
+    fn name(&self) -> &'static str {
+        CLIENT
+    }
+
+    /// 将 tracker 设置为新种子的默认 tracker，`update_existing` 时同时添加到已有的非私有种子
+    async fn push(&self, trackers: &[String], update_existing: bool) -> Result<PushReport> {
+        let cookie = self.login().await?;
+        let cookie = cookie.as_deref();
+        self.set_default_trackers(cookie, trackers).await?;
+
+        let mut report = PushReport::default();
+        if update_existing {
+            for torrent in self.list_torrents(cookie).await? {
+                if self.is_private(cookie, &torrent).await? {
+                    report.skipped_private += 1;
+                    continue;
+                }
+                self.add_trackers(cookie, &torrent.hash, trackers).await?;
+                report.updated += 1;
+            }
+        }
+        Ok(report)
+    }
+}
+
+async fn check_status(response: Response) -> Result<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    Err(TorrentError::ClientError {
+        client: CLIENT,
+        status: status.as_u16(),
+        message: response.text().await.unwrap_or_default(),
+    })
+}
@@ -0,0 +1,178 @@
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::{PushReport, TrackerClient, http_client};
+use crate::{
+    config::TransmissionConfig,
+    torrent::{Result, TorrentError, tracker::missing_trackers},
+};
+
+const CLIENT: &str = "Transmission";
+
+/// Transmission 用来防止 CSRF 的会话头
+const SESSION_HEADER: &str = "X-Transmission-Session-Id";
+
+/// Transmission RPC 的客户端
+#[derive(Debug, Clone)]
+pub struct TransmissionClient {
+    http: Client,
+    url: String,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcResponse {
+    result: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TorrentInfo {
+    id: u64,
+    #[serde(default)]
+    is_private: bool,
+    #[serde(default)]
+    trackers: Vec<TrackerInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TrackerInfo {
+    announce: String,
+}
+
+impl TransmissionClient {
+    /// `url` 为 RPC 的完整地址，例如 `http://localhost:9091/transmission/rpc`
+    pub fn new(
+        url: impl Into<String>,
+        username: Option<String>,
+        password: Option<String>,
+    ) -> Result<Self> {
+        Ok(Self {
+            http: http_client()?,
+            url: url.into(),
+            username,
+            password,
+        })
+    }
+
+    pub fn from_config(config: &TransmissionConfig) -> Result<Self> {
+        Self::new(
+            config.url.clone(),
+            config.username.clone(),
+            config.password.clone(),
+        )
+    }
+
+    async fn send(&self, session: Option<&str>, body: &Value) -> Result<reqwest::Response> {
+        let mut request = self.http.post(&self.url).json(body);
+        if let Some(username) = &self.username {
+            request = request.basic_auth(username, self.password.as_deref());
+        }
+        if let Some(session) = session {
+            request = request.header(SESSION_HEADER, session);
+        }
+        Ok(request.send().await?)
+    }
+
+    /// 调用 RPC 方法，`session` 过期时服务器返回 409 和新的会话 id，更新后重试一次
+    async fn call(
+        &self,
+        session: &mut Option<String>,
+        method: &str,
+        arguments: Value,
+    ) -> Result<Value> {
+        let body = json!({ "method": method, "arguments": arguments });
+        let mut response = self.send(session.as_deref(), &body).await?;
+        if response.status() == StatusCode::CONFLICT {
+            *session = response
+                .headers()
+                .get(SESSION_HEADER)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+            response = self.send(session.as_deref(), &body).await?;
+        }
+
+        let status = response.status();
+        if status == StatusCode::UNAUTHORIZED {
+            return Err(TorrentError::LoginFailed(CLIENT));
+        }
+        if !status.is_success() {
+            return Err(TorrentError::ClientError {
+                client: CLIENT,
+                status: status.as_u16(),
+                message: response.text().await.unwrap_or_default(),
+            });
+        }
+        let response: RpcResponse = response.json().await?;
+        if response.result != "success" {
+            return Err(TorrentError::ClientError {
+                client: CLIENT,
+                status: status.as_u16(),
+                message: response.result,
+            });
+        }
+        Ok(response.arguments)
+    }
+}
+
+impl TrackerClient for TransmissionClient {
+    fn name(&self) -> &'static str {
+        CLIENT
+    }
+
+    /// 设置 `default-trackers`（Transmission 4.0 起支持），并向已有的非私有种子添加缺少的 tracker
+    async fn push(&self, trackers: &[String], update_existing: bool) -> Result<PushReport> {
+        let mut session = None;
+        // 空行分隔 tier，每个 tracker 单独作为一个 tier
+        self.call(
+            &mut session,
+            "session-set",
+            json!({ "default-trackers": trackers.join("\n\n") }),
+        )
+        .await?;
+
+        let mut report = PushReport::default();
+        if !update_existing {
+            return Ok(report);
+        }
+        let arguments = self
+            .call(
+                &mut session,
+                "torrent-get",
+                json!({ "fields": ["id", "isPrivate", "trackers"] }),
+            )
+            .await?;
+        let torrents: Vec<TorrentInfo> = serde_json::from_value(arguments["torrents"].clone())
+            .map_err(|e| TorrentError::ClientError {
+                client: CLIENT,
+                status: StatusCode::OK.as_u16(),
+                message: format!("invalid torrent-get response: {}", e),
+            })?;
+        for torrent in torrents {
+            if torrent.is_private {
+                report.skipped_private += 1;
+                continue;
+            }
+            // 重复添加已有的 tracker 会导致整个请求失败，比较前先规范化
+            let missing = missing_trackers(
+                torrent.trackers.iter().map(|t| t.announce.as_str()),
+                trackers,
+            );
+            if missing.is_empty() {
+                continue;
+            }
+            self.call(
+                &mut session,
+                "torrent-set",
+                json!({ "ids": [torrent.id], "trackerAdd": missing }),
+            )
+            .await?;
+            report.updated += 1;
+        }
+        Ok(report)
+    }
+}
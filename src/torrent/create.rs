@@ -0,0 +1,532 @@
+//! 由本地文件或目录创建 .torrent 文件
+
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+    result,
+};
+
+use clap::ValueEnum;
+use walkdir::WalkDir;
+
+use super::{
+    Result, TorrentError,
+    bencode::Value,
+    metainfo::{Metainfo, format_size},
+    piece::{BLOCK_SIZE, Chunk, PieceSpec, file_root, hash_pieces},
+    source::parse_tracker_list,
+};
+
+/// 自动选择时 piece 大小的范围
+pub const MIN_PIECE_LENGTH: u64 = 16 * 1024;
+pub const MAX_PIECE_LENGTH: u64 = 16 * 1024 * 1024;
+
+/// 自动选择 piece 大小时期望的 piece 数量
+const TARGET_PIECES: u64 = 1500;
+
+/// 生成的种子格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum TorrentVersion {
+    /// 只有 v1 的 `pieces`，兼容所有客户端
+    V1,
+    /// 只有 BEP 52 的 `file tree` 和 `piece layers`
+    V2,
+    /// 同时包含 v1 和 v2 信息，文件按 piece 对齐
+    #[default]
+    Hybrid,
+}
+
+impl TorrentVersion {
+    fn v1(&self) -> bool {
+        matches!(self, Self::V1 | Self::Hybrid)
+    }
+
+    fn v2(&self) -> bool {
+        matches!(self, Self::V2 | Self::Hybrid)
+    }
+}
+
+/// 创建种子的选项
+#[derive(Debug, Clone, Default)]
+pub struct CreateOptions {
+    pub version: TorrentVersion,
+    /// piece 大小，为空时根据总大小自动选择
+    pub piece_length: Option<u64>,
+    pub private: bool,
+    pub comment: Option<String>,
+    /// info 字典中的 `source`，私有 tracker 常用来区分站点
+    pub source: Option<String>,
+    pub web_seeds: Vec<String>,
+    /// 每个 tracker 单独一层
+    pub trackers: Vec<String>,
+    /// 追加在 `trackers` 之后的 tracker 列表文件，私有种子不使用，无法读取时跳过
+    pub tracker_file: Option<PathBuf>,
+    /// 并发计算哈希的线程数
+    pub jobs: usize,
+}
+
+/// 种子中的一个源文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SourceFile {
+    path: PathBuf,
+    /// 相对于种子根目录的路径，单文件种子为空
+    components: Vec<String>,
+    length: u64,
+}
+
+/// 使 piece 数量接近 [`TARGET_PIECES`] 的 2 的幂，限制在 16KiB 到 16MiB 之间
+pub fn auto_piece_length(total_size: u64) -> u64 {
+    (total_size / TARGET_PIECES)
+        .next_power_of_two()
+        .clamp(MIN_PIECE_LENGTH, MAX_PIECE_LENGTH)
+}
+
+/// 解析命令行中以 KiB 为单位的 piece 大小，返回字节数
+pub fn parse_piece_size(s: &str) -> result::Result<u64, String> {
+    let invalid = || {
+        format!(
+            "invalid piece size {}, it must be a power of two of at least {} KiB",
+            s,
+            MIN_PIECE_LENGTH / 1024
+        )
+    };
+    let kib: u64 = s.trim().parse().map_err(|_| invalid())?;
+    kib.checked_mul(1024)
+        .filter(|&bytes| bytes.is_power_of_two() && bytes >= MIN_PIECE_LENGTH)
+        .ok_or_else(invalid)
+}
+
+/// 种子中的 tracker：`trackers` 之后是 `tracker_file` 中的列表，私有种子只使用明确指定的 tracker
+async fn announce_trackers(options: &CreateOptions) -> Vec<String> {
+    let mut trackers = options.trackers.clone();
+    let Some(file) = options.tracker_file.as_ref().filter(|_| !options.private) else {
+        return trackers;
+    };
+    match tokio::fs::read_to_string(file).await {
+        Ok(content) => trackers.extend(parse_tracker_list(&content)),
+        Err(e) => eprintln!("warning: tracker list {} not used: {}", file.display(), e),
+    }
+    trackers
+}
+
+fn non_utf8(path: &Path) -> TorrentError {
+    TorrentError::IoError(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("path is not valid UTF-8: {:?}", path),
+    ))
+}
+
+/// 收集 `root` 下的所有文件，按路径排序，与 v2 文件树的顺序一致；不跟随符号链接
+fn collect_files(root: &Path) -> Result<(String, Vec<SourceFile>, bool)> {
+    let root = root.canonicalize().map_err(TorrentError::IoError)?;
+    let name = root
+        .file_name()
+        .ok_or_else(|| TorrentError::NothingToShare(root.display().to_string()))?
+        .to_str()
+        .ok_or_else(|| non_utf8(&root))?
+        .to_string();
+
+    if root.is_file() {
+        let length = root.metadata().map_err(TorrentError::IoError)?.len();
+        let file = SourceFile {
+            path: root,
+            components: Vec::new(),
+            length,
+        };
+        return Ok((name, vec![file], false));
+    }
+
+    let mut files = Vec::new();
+    for entry in WalkDir::new(&root) {
+        let entry = entry.map_err(|e| TorrentError::IoError(e.into()))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let components = entry
+            .path()
+            .strip_prefix(&root)
+            .expect("walkdir yields paths under the root")
+            .iter()
+            .map(|c| c.to_str().map(str::to_string))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| non_utf8(entry.path()))?;
+        let length = entry
+            .metadata()
+            .map_err(|e| TorrentError::IoError(e.into()))?
+            .len();
+        files.push(SourceFile {
+            path: entry.into_path(),
+            components,
+            length,
+        });
+    }
+    files.sort_by(|a, b| a.components.cmp(&b.components));
+    Ok((name, files, true))
+}
+
+/// v1 种子把所有文件看作连续的数据流，piece 可以跨越文件
+fn v1_specs(files: &[SourceFile], piece_length: u64) -> Vec<PieceSpec> {
+    let mut specs = Vec::new();
+    let mut current = PieceSpec {
+        v1: true,
+        ..PieceSpec::default()
+    };
+    let mut filled = 0;
+    for file in files {
+        let mut offset = 0;
+        while offset < file.length {
+            let len = (file.length - offset).min(piece_length - filled);
+            current.chunks.push(Chunk {
+                path: file.path.clone(),
+                offset,
+                len,
+            });
+            offset += len;
+            filled += len;
+            if filled == piece_length {
+                specs.push(std::mem::take(&mut current));
+                current.v1 = true;
+                filled = 0;
+            }
+        }
+    }
+    if filled > 0 {
+        specs.push(current);
+    }
+    specs
+}
+
+/// v2 和混合种子中每个 piece 只属于一个文件；混合种子中除最后一个文件外，
+/// 不足一个 piece 的部分在 v1 中用填充文件补齐
+fn v2_specs(files: &[SourceFile], piece_length: u64, v1: bool) -> Vec<(usize, PieceSpec)> {
+    let leaves_per_piece = piece_length / BLOCK_SIZE;
+    let mut specs = Vec::new();
+    for (idx, file) in files.iter().enumerate() {
+        let last_file = idx == files.len() - 1;
+        let pieces = file.length.div_ceil(piece_length);
+        let v2_leaves = if pieces > 1 {
+            leaves_per_piece
+        } else {
+            file.length.div_ceil(BLOCK_SIZE).next_power_of_two()
+        };
+        for piece in 0..pieces {
+            let offset = piece * piece_length;
+            let len = (file.length - offset).min(piece_length);
+            specs.push((
+                idx,
+                PieceSpec {
+                    chunks: vec![Chunk {
+                        path: file.path.clone(),
+                        offset,
+                        len,
+                    }],
+                    pad: if v1 && !last_file {
+                        piece_length - len
+                    } else {
+                        0
+                    },
+                    v1,
+                    v2_leaves: Some(v2_leaves),
+                },
+            ));
+        }
+    }
+    specs
+}
+
+fn dict(pairs: Vec<(&str, Value)>) -> Value {
+    Value::Dict(
+        pairs
+            .into_iter()
+            .map(|(k, v)| (k.as_bytes().to_vec(), v))
+            .collect(),
+    )
+}
+
+fn path_list(components: &[String]) -> Value {
+    Value::List(components.iter().map(|c| c.as_str().into()).collect())
+}
+
+/// 在 v2 文件树中插入一个文件，`leaf` 放在空字符串键下
+fn insert_file_tree(tree: &mut BTreeMap<Vec<u8>, Value>, components: &[String], leaf: Value) {
+    match components {
+        [] => {
+            tree.insert(Vec::new(), leaf);
+        }
+        [first, rest @ ..] => {
+            let node = tree
+                .entry(first.as_bytes().to_vec())
+                .or_insert_with(|| Value::Dict(BTreeMap::new()));
+            if let Value::Dict(node) = node {
+                insert_file_tree(node, rest, leaf);
+            }
+        }
+    }
+}
+
+/// 计算 `path` 中所有文件的哈希并生成种子的内容
+pub async fn create_torrent(path: impl AsRef<Path>, options: &CreateOptions) -> Result<Vec<u8>> {
+    let (name, files, multi_file) = collect_files(path.as_ref())?;
+    let total_size: u64 = files.iter().map(|f| f.length).sum();
+    if total_size == 0 {
+        return Err(TorrentError::NothingToShare(
+            path.as_ref().display().to_string(),
+        ));
+    }
+    let piece_length = options
+        .piece_length
+        .unwrap_or_else(|| auto_piece_length(total_size));
+    if !piece_length.is_power_of_two()
+        || piece_length < MIN_PIECE_LENGTH
+        || i64::try_from(piece_length).is_err()
+    {
+        return Err(TorrentError::InvalidPieceLength(piece_length));
+    }
+    let version = options.version;
+
+    let mut info = BTreeMap::new();
+    let mut piece_layers = BTreeMap::new();
+    if version.v2() {
+        let specs = v2_specs(&files, piece_length, version.v1());
+        let owners: Vec<usize> = specs.iter().map(|(idx, _)| *idx).collect();
+        let hashes = hash_pieces(specs.into_iter().map(|(_, s)| s).collect(), options.jobs)
+            .await
+            .into_iter()
+            .collect::<io::Result<Vec<_>>>()
+            .map_err(TorrentError::IoError)?;
+
+        let mut v1_pieces = Vec::new();
+        let mut file_pieces: Vec<Vec<[u8; 32]>> = vec![Vec::new(); files.len()];
+        for (owner, hash) in owners.into_iter().zip(hashes) {
+            v1_pieces.extend(hash.v1.unwrap_or_default());
+            file_pieces[owner].push(hash.v2.expect("v2 hash requested"));
+        }
+
+        let mut tree = BTreeMap::new();
+        for (file, pieces) in files.iter().zip(&file_pieces) {
+            let mut leaf = vec![("length", Value::Int(file.length as i64))];
+            if !pieces.is_empty() {
+                let root = file_root(pieces, piece_length / BLOCK_SIZE);
+                if pieces.len() > 1 {
+                    piece_layers.insert(root.to_vec(), Value::Bytes(pieces.concat()));
+                }
+                leaf.push(("pieces root", Value::Bytes(root.to_vec())));
+            }
+            let components = if multi_file {
+                file.components.clone()
+            } else {
+                vec![name.clone()]
+            };
+            insert_file_tree(&mut tree, &components, dict(leaf));
+        }
+        info.insert(b"file tree".to_vec(), Value::Dict(tree));
+        info.insert(b"meta version".to_vec(), Value::Int(2));
+        if version.v1() {
+            info.insert(b"pieces".to_vec(), Value::Bytes(v1_pieces));
+        }
+    } else {
+        let hashes = hash_pieces(v1_specs(&files, piece_length), options.jobs)
+            .await
+            .into_iter()
+            .collect::<io::Result<Vec<_>>>()
+            .map_err(TorrentError::IoError)?;
+        let pieces: Vec<u8> = hashes
+            .into_iter()
+            .flat_map(|h| h.v1.unwrap_or_default())
+            .collect();
+        info.insert(b"pieces".to_vec(), Value::Bytes(pieces));
+    }
+
+    if version.v1() {
+        if multi_file {
+            let mut list = Vec::new();
+            for (idx, file) in files.iter().enumerate() {
+                list.push(dict(vec![
+                    ("length", Value::Int(file.length as i64)),
+                    ("path", path_list(&file.components)),
+                ]));
+                let pad = (piece_length - file.length % piece_length) % piece_length;
+                if version.v2() && idx < files.len() - 1 && pad > 0 {
+                    list.push(dict(vec![
+                        ("attr", "p".into()),
+                        ("length", Value::Int(pad as i64)),
+                        ("path", path_list(&[".pad".to_string(), pad.to_string()])),
+                    ]));
+                }
+            }
+            info.insert(b"files".to_vec(), Value::List(list));
+        } else {
+            info.insert(b"length".to_vec(), Value::Int(total_size as i64));
+        }
+    }
+    info.insert(b"name".to_vec(), name.into());
+    info.insert(b"piece length".to_vec(), Value::Int(piece_length as i64));
+    if options.private {
+        info.insert(b"private".to_vec(), Value::Int(1));
+    }
+    if let Some(source) = &options.source {
+        info.insert(b"source".to_vec(), source.as_str().into());
+    }
+
+    let trackers = announce_trackers(options).await;
+    let mut torrent = BTreeMap::new();
+    if let Some(first) = trackers.first() {
+        torrent.insert(b"announce".to_vec(), first.as_str().into());
+        torrent.insert(
+            b"announce-list".to_vec(),
+            Value::List(
+                trackers
+                    .iter()
+                    .map(|t| Value::List(vec![t.as_str().into()]))
+                    .collect(),
+            ),
+        );
+    }
+    if let Some(comment) = &options.comment {
+        torrent.insert(b"comment".to_vec(), comment.as_str().into());
+    }
+    torrent.insert(
+        b"created by".to_vec(),
+        format!("blowup {}", env!("CARGO_PKG_VERSION")).into(),
+    );
+    torrent.insert(
+        b"creation date".to_vec(),
+        Value::Int(chrono::Utc::now().timestamp()),
+    );
+    torrent.insert(b"info".to_vec(), Value::Dict(info));
+    if !piece_layers.is_empty() {
+        torrent.insert(b"piece layers".to_vec(), Value::Dict(piece_layers));
+    }
+    if !options.web_seeds.is_empty() {
+        torrent.insert(
+            b"url-list".to_vec(),
+            Value::List(
+                options
+                    .web_seeds
+                    .iter()
+                    .map(|w| w.as_str().into())
+                    .collect(),
+            ),
+        );
+    }
+    Ok(Value::Dict(torrent).encode())
+}
+
+/// 创建种子并写入 `output`，没有指定时写入当前目录下的 `<name>.torrent`，不覆盖已有的文件
+pub async fn create_torrent_file(
+    path: impl AsRef<Path>,
+    output: Option<&Path>,
+    options: &CreateOptions,
+) -> Result<PathBuf> {
+    let data = create_torrent(&path, options).await?;
+    let info = Metainfo::parse(&data)?;
+    let output = output
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from(format!("{}.torrent", info.name)));
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&output)
+        .await
+        .map_err(TorrentError::IoError)?;
+    tokio::io::AsyncWriteExt::write_all(&mut file, &data)
+        .await
+        .map_err(TorrentError::IoError)?;
+
+    eprintln!(
+        "created {}: {} files, {}, {} pieces of {}",
+        output.display(),
+        info.files.len(),
+        format_size(info.total_size),
+        info.piece_count,
+        format_size(info.piece_length)
+    );
+    if let Some(hash) = &info.info_hash_v1 {
+        eprintln!("info-hash v1: {}", hash);
+    }
+    if let Some(hash) = &info.info_hash_v2 {
+        eprintln!("info-hash v2: {}", hash);
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(name: &str, length: u64) -> SourceFile {
+        SourceFile {
+            path: PathBuf::from(name),
+            components: vec![name.to_string()],
+            length,
+        }
+    }
+
+    #[test]
+    fn test_parse_piece_size() {
+        assert_eq!(parse_piece_size("16"), Ok(16 * 1024));
+        assert_eq!(parse_piece_size("4096"), Ok(4 * 1024 * 1024));
+        for s in ["", "8", "48", "-16", "18014398509481984"] {
+            assert!(parse_piece_size(s).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_auto_piece_length() {
+        assert_eq!(auto_piece_length(1), MIN_PIECE_LENGTH);
+        assert_eq!(auto_piece_length(700 * 1024 * 1024), 512 * 1024);
+        assert_eq!(auto_piece_length(8 * 1024 * 1024 * 1024), 8 * 1024 * 1024);
+        assert_eq!(auto_piece_length(u64::MAX / 2), MAX_PIECE_LENGTH);
+    }
+
+    #[test]
+    fn test_v1_pieces_span_files() {
+        let files = [
+            source("a", 40 * 1024),
+            source("b", 0),
+            source("c", 10 * 1024),
+        ];
+        let specs = v1_specs(&files, 32 * 1024);
+        assert_eq!(specs.len(), 2);
+        assert_eq!(specs[0].chunks.len(), 1);
+        assert_eq!(
+            specs[1]
+                .chunks
+                .iter()
+                .map(|c| (c.path.to_str().unwrap(), c.offset, c.len))
+                .collect::<Vec<_>>(),
+            vec![("a", 32 * 1024, 8 * 1024), ("c", 0, 10 * 1024)]
+        );
+        assert!(
+            specs
+                .iter()
+                .all(|s| s.v1 && s.pad == 0 && s.v2_leaves.is_none())
+        );
+    }
+
+    #[test]
+    fn test_hybrid_pieces_are_aligned_to_files() {
+        let files = [source("a", 40 * 1024), source("b", 20 * 1024)];
+        let specs = v2_specs(&files, 32 * 1024, true);
+        let summary: Vec<(usize, u64, u64, Option<u64>)> = specs
+            .iter()
+            .map(|(idx, s)| (*idx, s.chunks[0].len, s.pad, s.v2_leaves))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (0, 32 * 1024, 0, Some(2)),
+                (0, 8 * 1024, 24 * 1024, Some(2)),
+                // 最后一个文件不填充，只有一个 piece 时叶子数量按文件大小计算
+                (1, 20 * 1024, 0, Some(2)),
+            ]
+        );
+        assert!(
+            v2_specs(&files, 32 * 1024, false)
+                .iter()
+                .all(|(_, s)| s.pad == 0 && !s.v1)
+        );
+    }
+}
@@ -0,0 +1,345 @@
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hasher},
+    net::SocketAddr,
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tokio::{
+    net::{UdpSocket, lookup_host},
+    sync::Semaphore,
+    task::JoinSet,
+    time::timeout,
+};
+
+use super::{
+    Result, TorrentError,
+    output::write_atomic,
+    source::parse_tracker_list,
+    tracker::{Tracker, TrackerScheme},
+};
+
+/// BEP 15 connect 请求中固定的协议标识
+const UDP_PROTOCOL_ID: u64 = 0x41727101980;
+const ACTION_CONNECT: u32 = 0;
+const ACTION_ERROR: u32 = 3;
+
+/// `tracker check` 默认的输出文件
+pub const DEFAULT_ALIVE_FILE: &str = "tracker_alive.txt";
+
+/// 检测的选项
+#[derive(Debug, Clone, Copy)]
+pub struct CheckOptions {
+    /// 单个 tracker 的超时时间
+    pub timeout: Duration,
+    /// 同时检测的 tracker 数量
+    pub jobs: usize,
+}
+
+impl Default for CheckOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(5),
+            jobs: 32,
+        }
+    }
+}
+
+/// 单个 tracker 的检测结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HealthStatus {
+    /// 正常响应，记录延迟
+    Alive(Duration),
+    Dead(String),
+    /// 不支持检测的协议（wss）
+    Skipped,
+}
+
+impl HealthStatus {
+    pub fn is_alive(&self) -> bool {
+        matches!(self, Self::Alive(_))
+    }
+}
+
+impl fmt::Display for HealthStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Alive(latency) => write!(f, "alive {}ms", latency.as_millis()),
+            Self::Dead(reason) => write!(f, "dead: {}", reason),
+            Self::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthReport {
+    pub tracker: Tracker,
+    pub status: HealthStatus,
+}
+
+/// 随机的 transaction id / info_hash，不需要密码学强度，每个 `RandomState` 的密钥都不同
+fn random_u64() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+/// BEP 15 connect 请求
+fn connect_request(transaction_id: u32) -> [u8; 16] {
+    let mut packet = [0u8; 16];
+    packet[..8].copy_from_slice(&UDP_PROTOCOL_ID.to_be_bytes());
+    packet[8..12].copy_from_slice(&ACTION_CONNECT.to_be_bytes());
+    packet[12..].copy_from_slice(&transaction_id.to_be_bytes());
+    packet
+}
+
+/// 解析 connect 响应，返回 connection id
+fn parse_connect_response(packet: &[u8], transaction_id: u32) -> Result<u64> {
+    let protocol_error = |msg: &str| TorrentError::TrackerProtocol(msg.to_string());
+    if packet.len() < 8 {
+        return Err(protocol_error("response too short"));
+    }
+    let action = u32::from_be_bytes(packet[..4].try_into().unwrap());
+    let received_id = u32::from_be_bytes(packet[4..8].try_into().unwrap());
+    if received_id != transaction_id {
+        return Err(protocol_error("transaction id mismatch"));
+    }
+    match action {
+        ACTION_CONNECT if packet.len() >= 16 => {
+            Ok(u64::from_be_bytes(packet[8..16].try_into().unwrap()))
+        }
+        ACTION_ERROR => Err(TorrentError::TrackerProtocol(
+            String::from_utf8_lossy(&packet[8..]).into_owned(),
+        )),
+        _ => Err(protocol_error("unexpected response")),
+    }
+}
+
+async fn resolve(tracker: &Tracker) -> Result<SocketAddr> {
+    let host = tracker.host().trim_start_matches('[').trim_end_matches(']');
+    let port = tracker
+        .port()
+        .ok_or(TorrentError::InvalidTracker(tracker.to_string()))?;
+    lookup_host((host, port))
+        .await
+        .map_err(TorrentError::IoError)?
+        .next()
+        .ok_or_else(|| TorrentError::TrackerProtocol("host not found".to_string()))
+}
+
+/// 向 udp tracker 发送 BEP 15 connect 请求，返回往返延迟
+pub async fn probe_udp(tracker: &Tracker, limit: Duration) -> Result<Duration> {
+    let probe = async {
+        let addr = resolve(tracker).await?;
+        let bind: SocketAddr = if addr.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket = UdpSocket::bind(bind).await.map_err(TorrentError::IoError)?;
+        socket.connect(addr).await.map_err(TorrentError::IoError)?;
+
+        let transaction_id = random_u64() as u32;
+        let start = Instant::now();
+        socket
+            .send(&connect_request(transaction_id))
+            .await
+            .map_err(TorrentError::IoError)?;
+        let mut buf = [0u8; 512];
+        let len = socket.recv(&mut buf).await.map_err(TorrentError::IoError)?;
+        let latency = start.elapsed();
+        parse_connect_response(&buf[..len], transaction_id)?;
+        Ok(latency)
+    };
+    timeout(limit, probe)
+        .await
+        .map_err(|_| TorrentError::TrackerTimeout)?
+}
+
+/// 由 announce 地址得到 scrape 地址（BEP 48），路径最后一段不是 announce 时不支持 scrape
+fn scrape_url(tracker: &Tracker) -> Option<String> {
+    let url = tracker.as_str();
+    let (base, query) = match url.split_once('?') {
+        Some((base, query)) => (base, Some(query)),
+        None => (url, None),
+    };
+    let base = base.strip_suffix("/announce")?;
+    Some(match query {
+        Some(query) => format!("{}/scrape?{}", base, query),
+        None => format!("{}/scrape", base),
+    })
+}
+
+/// 不会命中任何种子的 announce 请求，只用来确认 tracker 能够响应
+fn announce_url(tracker: &Tracker) -> String {
+    let info_hash: String = random_u64()
+        .to_be_bytes()
+        .iter()
+        .chain(random_u64().to_be_bytes().iter())
+        .chain([0u8; 4].iter())
+        .map(|b| format!("%{:02X}", b))
+        .collect();
+    let separator = if tracker.as_str().contains('?') {
+        '&'
+    } else {
+        '?'
+    };
+    format!(
+        "{}{}info_hash={}&peer_id=-BL0001-000000000000&port=6881&uploaded=0&downloaded=0&left=0&compact=1&numwant=0",
+        tracker, separator, info_hash
+    )
+}
+
+/// 向 http(s) tracker 发送 scrape 请求（不支持时发送 announce 请求），返回往返延迟
+///
+/// 响应为 bencode 字典即认为 tracker 可用，即使其中是 `failure reason`
+pub async fn probe_http(
+    client: &reqwest::Client,
+    tracker: &Tracker,
+    limit: Duration,
+) -> Result<Duration> {
+    let url = scrape_url(tracker).unwrap_or_else(|| announce_url(tracker));
+    let probe = async {
+        let start = Instant::now();
+        let body = client
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        let latency = start.elapsed();
+        if body.first() != Some(&b'd') {
+            return Err(TorrentError::TrackerProtocol(
+                "response is not a bencoded dictionary".to_string(),
+            ));
+        }
+        Ok(latency)
+    };
+    timeout(limit, probe)
+        .await
+        .map_err(|_| TorrentError::TrackerTimeout)?
+}
+
+async fn check_one(client: &reqwest::Client, tracker: &Tracker, limit: Duration) -> HealthStatus {
+    let result = match tracker.scheme() {
+        TrackerScheme::Udp => probe_udp(tracker, limit).await,
+        TrackerScheme::Http | TrackerScheme::Https => probe_http(client, tracker, limit).await,
+        TrackerScheme::Wss => return HealthStatus::Skipped,
+    };
+    match result {
+        Ok(latency) => HealthStatus::Alive(latency),
+        Err(e) => HealthStatus::Dead(e.to_string()),
+    }
+}
+
+/// 并发检测所有 tracker，结果的顺序与输入的顺序相同
+pub async fn check_trackers(trackers: Vec<Tracker>, options: CheckOptions) -> Vec<HealthReport> {
+    let client = reqwest::Client::builder()
+        .timeout(options.timeout)
+        .build()
+        .unwrap_or_default();
+    let semaphore = Arc::new(Semaphore::new(options.jobs.max(1)));
+    let mut set = JoinSet::new();
+    for (idx, tracker) in trackers.iter().cloned().enumerate() {
+        let (client, semaphore) = (client.clone(), semaphore.clone());
+        set.spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            (idx, check_one(&client, &tracker, options.timeout).await)
+        });
+    }
+
+    let mut statuses: Vec<Option<HealthStatus>> = trackers.iter().map(|_| None).collect();
+    while let Some(joined) = set.join_next().await {
+        if let Ok((idx, status)) = joined {
+            statuses[idx] = Some(status);
+        }
+    }
+    trackers
+        .into_iter()
+        .zip(statuses)
+        .map(|(tracker, status)| HealthReport {
+            tracker,
+            status: status.unwrap_or_else(|| HealthStatus::Dead("task panicked".to_string())),
+        })
+        .collect()
+}
+
+/// 检测 `input` 中的所有 tracker，将可用的（以及无法检测的 wss）tracker 按原顺序写入 `output`
+pub async fn check_tracker_file(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    options: CheckOptions,
+) -> Result<()> {
+    let content = tokio::fs::read_to_string(input)
+        .await
+        .map_err(TorrentError::IoError)?;
+    let trackers: Vec<Tracker> = parse_tracker_list(&content)
+        .iter()
+        .filter_map(|line| match Tracker::parse(line) {
+            Ok(tracker) => Some(tracker),
+            Err(e) => {
+                eprintln!("skipped {}", e);
+                None
+            }
+        })
+        .collect();
+
+    let reports = check_trackers(trackers, options).await;
+    for report in &reports {
+        eprintln!("[{}] {}", report.status, report.tracker);
+    }
+    let kept: Vec<&str> = reports
+        .iter()
+        .filter(|r| !matches!(r.status, HealthStatus::Dead(_)))
+        .map(|r| r.tracker.as_str())
+        .collect();
+    let alive = reports.iter().filter(|r| r.status.is_alive()).count();
+    eprintln!("{} of {} trackers alive", alive, reports.len());
+
+    let mut content = kept.join("\n");
+    content.push('\n');
+    // 输出可以是输入的列表本身，写入失败时不能留下不完整的文件
+    write_atomic(output.as_ref(), content.as_bytes()).map_err(TorrentError::IoError)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connect_packets() {
+        let packet = connect_request(0x01020304);
+        assert_eq!(&packet[..8], &[0, 0, 0x04, 0x17, 0x27, 0x10, 0x19, 0x80]);
+        assert_eq!(&packet[8..], &[0, 0, 0, 0, 1, 2, 3, 4]);
+
+        let mut response = vec![0, 0, 0, 0, 1, 2, 3, 4];
+        response.extend(42u64.to_be_bytes());
+        assert_eq!(parse_connect_response(&response, 0x01020304).unwrap(), 42);
+        assert!(parse_connect_response(&response, 0x01020305).is_err());
+        assert!(parse_connect_response(&response[..12], 0x01020304).is_err());
+
+        let mut error = vec![0, 0, 0, 3, 1, 2, 3, 4];
+        error.extend(b"banned");
+        match parse_connect_response(&error, 0x01020304) {
+            Err(TorrentError::TrackerProtocol(msg)) => assert_eq!(msg, "banned"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_probe_urls() {
+        let tracker = Tracker::parse("http://t.example:8080/announce?passkey=abc").unwrap();
+        assert_eq!(
+            scrape_url(&tracker).as_deref(),
+            Some("http://t.example:8080/scrape?passkey=abc")
+        );
+        let tracker = Tracker::parse("http://t.example/tracker.php").unwrap();
+        assert_eq!(scrape_url(&tracker), None);
+        let announce = announce_url(&tracker);
+        assert!(announce.starts_with("http://t.example/tracker.php?info_hash=%"));
+        assert!(announce.ends_with("&numwant=0"));
+    }
+}
@@ -0,0 +1,222 @@
+//! 将 tracker 列表添加到 .torrent 文件和磁力链接中
+
+use std::path::Path;
+
+use super::{
+    Result, TorrentError,
+    bencode::{Value, encode_dict_with_raw},
+    magnet::Magnet,
+    metainfo::decode_torrent,
+    output::write_atomic,
+    source::parse_tracker_list,
+    tracker::missing_trackers,
+};
+
+/// 将 `trackers` 添加到种子的 `announce-list` 中，返回新的种子内容以及添加的数量
+///
+/// 每个新 tracker 单独作为一层放在已有的层之后。info 字典按原始字节写回，info-hash 不变。
+/// 私有种子只能使用私有 tracker，拒绝修改
+pub fn add_trackers_to_torrent(data: &[u8], trackers: &[String]) -> Result<(Vec<u8>, usize)> {
+    let (torrent, span) = decode_torrent(data)?;
+    let info = torrent.get("info").expect("checked by decode_torrent");
+    if info.get("private").and_then(Value::as_int) == Some(1) {
+        let name = info.get("name").and_then(Value::as_str).unwrap_or("");
+        return Err(TorrentError::PrivateTorrent(name.to_string()));
+    }
+
+    let announce = torrent.get("announce").and_then(Value::as_str);
+    let mut tiers: Vec<Vec<String>> = torrent
+        .get("announce-list")
+        .and_then(Value::as_list)
+        .map(|tiers| {
+            tiers
+                .iter()
+                .filter_map(Value::as_list)
+                .map(|tier| {
+                    tier.iter()
+                        .filter_map(Value::as_str)
+                        .map(str::to_string)
+                        .collect::<Vec<_>>()
+                })
+                .filter(|tier| !tier.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    // 有 announce-list 时客户端会忽略 announce，需要把它放进第一层
+    if let Some(announce) = announce
+        && !tiers.iter().flatten().any(|t| t == announce)
+    {
+        tiers.insert(0, vec![announce.to_string()]);
+    }
+
+    let missing: Vec<Vec<String>> =
+        missing_trackers(tiers.iter().flatten().map(String::as_str), trackers)
+            .into_iter()
+            .map(|t| vec![t.to_string()])
+            .collect();
+    let added = missing.len();
+    tiers.extend(missing);
+
+    let mut dict = torrent
+        .as_dict()
+        .expect("top level is a dictionary")
+        .clone();
+    if announce.is_none()
+        && let Some(first) = tiers.first().and_then(|tier| tier.first())
+    {
+        dict.insert(b"announce".to_vec(), first.as_str().into());
+    }
+    dict.insert(
+        b"announce-list".to_vec(),
+        Value::List(
+            tiers
+                .into_iter()
+                .map(|tier| Value::List(tier.into_iter().map(Value::from).collect()))
+                .collect(),
+        ),
+    );
+    Ok((
+        encode_dict_with_raw(&dict, &[(b"info", &data[span])]),
+        added,
+    ))
+}
+
+/// 在磁力链接中添加还没有的 tracker
+pub fn add_trackers_to_magnet(uri: &str, trackers: &[String]) -> Result<String> {
+    let mut magnet = Magnet::parse(uri)?;
+    magnet.add_trackers(trackers);
+    Ok(magnet.to_string())
+}
+
+/// 读取 `tracker_file` 中的 tracker 列表，添加到磁力链接或 .torrent 文件中
+///
+/// 磁力链接直接打印新的链接；.torrent 文件写入 `output`，没有指定时覆盖原文件
+pub async fn add_trackers(
+    input: &str,
+    tracker_file: impl AsRef<Path>,
+    output: Option<&Path>,
+) -> Result<()> {
+    let content = tokio::fs::read_to_string(tracker_file)
+        .await
+        .map_err(TorrentError::IoError)?;
+    let trackers = parse_tracker_list(&content);
+    if trackers.is_empty() {
+        return Err(TorrentError::NoTrackers);
+    }
+
+    if input.starts_with("magnet:") {
+        println!("{}", add_trackers_to_magnet(input, &trackers)?);
+        return Ok(());
+    }
+
+    let data = tokio::fs::read(input)
+        .await
+        .map_err(TorrentError::IoError)?;
+    let (torrent, added) = add_trackers_to_torrent(&data, &trackers)?;
+    let output = output.unwrap_or(Path::new(input));
+    // 默认覆盖输入的种子，写入失败时不能留下不完整的文件
+    write_atomic(output, &torrent).map_err(TorrentError::IoError)?;
+    eprintln!("added {} trackers to {}", added, output.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::torrent::{bencode::decode, metainfo::Metainfo};
+
+    const INFO: &[u8] =
+        b"d6:lengthi5e4:name5:a.mkv12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaae";
+
+    fn torrent() -> Vec<u8> {
+        [
+            b"d8:announce26:udp://a.example:1/announce".as_slice(),
+            b"4:info",
+            INFO,
+            b"e",
+        ]
+        .concat()
+    }
+
+    fn trackers(list: &[&str]) -> Vec<String> {
+        list.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn test_add_trackers_to_torrent_keeps_info_hash() {
+        let data = torrent();
+        let before = Metainfo::parse(&data).unwrap();
+        let (new, added) = add_trackers_to_torrent(
+            &data,
+            &trackers(&[
+                "UDP://A.example:1/announce",
+                "http://b.example/announce",
+                "http://b.example/announce",
+                "udp://c.example:6969/announce",
+            ]),
+        )
+        .unwrap();
+        assert_eq!(added, 2);
+
+        let after = Metainfo::parse(&new).unwrap();
+        assert_eq!(after.info_hash_v1, before.info_hash_v1);
+        assert!(new.windows(INFO.len()).any(|w| w == INFO));
+        assert_eq!(
+            after.announce.as_deref(),
+            Some("udp://a.example:1/announce")
+        );
+        assert_eq!(
+            after.tiers(),
+            vec![
+                vec!["udp://a.example:1/announce"],
+                vec!["http://b.example/announce"],
+                vec!["udp://c.example:6969/announce"],
+            ]
+        );
+
+        // 再次添加时没有变化
+        let (again, added) =
+            add_trackers_to_torrent(&new, &trackers(&["http://b.example/announce"])).unwrap();
+        assert_eq!(added, 0);
+        assert_eq!(again, new);
+    }
+
+    #[test]
+    fn test_add_trackers_without_announce() {
+        let data = [b"d4:info".as_slice(), INFO, b"e"].concat();
+        let (new, added) =
+            add_trackers_to_torrent(&data, &trackers(&["http://b.example/announce"])).unwrap();
+        assert_eq!(added, 1);
+        let value = decode(&new).unwrap();
+        assert_eq!(
+            value.get("announce").and_then(Value::as_str),
+            Some("http://b.example/announce")
+        );
+    }
+
+    #[test]
+    fn test_refuse_private_torrent() {
+        let data = b"d4:infod6:lengthi5e4:name5:a.mkv12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaa7:privatei1eee";
+        assert!(matches!(
+            add_trackers_to_torrent(data, &trackers(&["http://b.example/announce"])),
+            Err(TorrentError::PrivateTorrent(name)) if name == "a.mkv"
+        ));
+    }
+
+    #[test]
+    fn test_add_trackers_to_magnet() {
+        let magnet = "magnet:?xt=urn:btih:c884071d5230f969da785f45bfd13a3659c5a11b&dn=test&tr=udp%3A%2F%2Fa.example%3A1%2Fannounce";
+        assert_eq!(
+            add_trackers_to_magnet(
+                magnet,
+                &trackers(&["udp://a.example:1/announce", "http://b.example/announce"])
+            )
+            .unwrap(),
+            format!("{}&tr=http%3A%2F%2Fb.example%2Fannounce", magnet)
+        );
+        assert!(matches!(
+            add_trackers_to_magnet("http://example.org", &[]),
+            Err(TorrentError::InvalidMagnet(_))
+        ));
+    }
+}
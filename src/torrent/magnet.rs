@@ -0,0 +1,301 @@
+//! 磁力链接的解析与生成
+
+use std::{fmt, path::Path, str::FromStr};
+
+use url::form_urlencoded;
+
+use super::{
+    Result, TorrentError, metainfo::Metainfo, source::parse_tracker_list, tracker::missing_trackers,
+};
+
+/// SHA-256 multihash 的前缀：算法 0x12，长度 0x20
+const SHA256_MULTIHASH: &str = "1220";
+
+/// 磁力链接中 BitTorrent 相关的参数
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Magnet {
+    /// `xt=urn:btih:`，小写十六进制的 v1 info-hash
+    pub btih: Option<String>,
+    /// `xt=urn:btmh:`，小写十六进制的 v2 info-hash（不含 multihash 前缀）
+    pub btmh: Option<String>,
+    /// `dn`，显示名称
+    pub name: Option<String>,
+    /// `xl`，总大小
+    pub length: Option<u64>,
+    /// `tr`
+    pub trackers: Vec<String>,
+    /// `ws`，BEP 19 的 web seed
+    pub web_seeds: Vec<String>,
+    /// `x.pe`，直接连接的 peer 地址
+    pub peers: Vec<String>,
+    /// 其它参数，生成时原样写回
+    pub extra: Vec<(String, String)>,
+}
+
+fn invalid(uri: &str) -> TorrentError {
+    TorrentError::InvalidMagnet(uri.to_string())
+}
+
+/// RFC 4648 base32 解码，btih 的 32 个字符的形式使用
+fn decode_base32(s: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let (mut buffer, mut bits) = (0u64, 0);
+    for c in s.trim_end_matches('=').bytes() {
+        let value = match c.to_ascii_uppercase() {
+            c @ b'A'..=b'Z' => c - b'A',
+            c @ b'2'..=b'7' => c - b'2' + 26,
+            _ => return None,
+        };
+        buffer = (buffer << 5) | value as u64;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+fn is_hex(s: &str, len: usize) -> bool {
+    s.len() == len && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// 百分号编码，只保留 RFC 3986 的非保留字符
+fn encode_component(s: &str) -> String {
+    let mut out = String::new();
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
+impl Magnet {
+    /// 解析磁力链接，至少需要一个 btih 或 btmh
+    pub fn parse(uri: &str) -> Result<Self> {
+        let query = uri.strip_prefix("magnet:?").ok_or_else(|| invalid(uri))?;
+        let mut magnet = Self::default();
+        for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+            let value = value.into_owned();
+            match key.as_ref() {
+                "xt" => {
+                    if let Some(hash) = value.strip_prefix("urn:btih:") {
+                        let hash = if is_hex(hash, 40) {
+                            hash.to_ascii_lowercase()
+                        } else {
+                            match decode_base32(hash) {
+                                Some(bytes) if hash.len() == 32 && bytes.len() == 20 => {
+                                    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+                                }
+                                _ => return Err(invalid(uri)),
+                            }
+                        };
+                        magnet.btih = Some(hash);
+                    } else if let Some(hash) = value.strip_prefix("urn:btmh:") {
+                        match hash.strip_prefix(SHA256_MULTIHASH) {
+                            Some(digest) if is_hex(digest, 64) => {
+                                magnet.btmh = Some(digest.to_ascii_lowercase())
+                            }
+                            _ => return Err(invalid(uri)),
+                        }
+                    } else {
+                        magnet.extra.push((key.into_owned(), value));
+                    }
+                }
+                "dn" => magnet.name = Some(value),
+                "xl" => magnet.length = Some(value.parse().map_err(|_| invalid(uri))?),
+                "tr" => magnet.trackers.push(value),
+                "ws" => magnet.web_seeds.push(value),
+                "x.pe" => magnet.peers.push(value),
+                _ => magnet.extra.push((key.into_owned(), value)),
+            }
+        }
+        if magnet.btih.is_none() && magnet.btmh.is_none() {
+            return Err(invalid(uri));
+        }
+        Ok(magnet)
+    }
+
+    /// 由种子的元信息生成，包括种子中所有的 tracker 和 web seed
+    pub fn from_metainfo(info: &Metainfo) -> Self {
+        Self {
+            btih: info.info_hash_v1.clone(),
+            btmh: info.info_hash_v2.clone(),
+            name: Some(info.name.clone()),
+            length: Some(info.total_size),
+            trackers: info.tiers().into_iter().flatten().collect(),
+            web_seeds: info.web_seeds.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// 添加链接中还没有的 tracker，能解析的 tracker 规范化后比较，返回添加的数量
+    pub fn add_trackers<S: AsRef<str>>(&mut self, trackers: &[S]) -> usize {
+        let missing: Vec<String> =
+            missing_trackers(self.trackers.iter().map(String::as_str), trackers)
+                .into_iter()
+                .map(str::to_string)
+                .collect();
+        let added = missing.len();
+        self.trackers.extend(missing);
+        added
+    }
+}
+
+impl FromStr for Magnet {
+    type Err = TorrentError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for Magnet {
+    /// 按照 xt、dn、xl、tr、ws、x.pe 的顺序生成，其它参数放在最后
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut params = Vec::new();
+        if let Some(btih) = &self.btih {
+            params.push(format!("xt=urn:btih:{}", btih));
+        }
+        if let Some(btmh) = &self.btmh {
+            params.push(format!("xt=urn:btmh:{}{}", SHA256_MULTIHASH, btmh));
+        }
+        if let Some(name) = &self.name {
+            params.push(format!("dn={}", encode_component(name)));
+        }
+        if let Some(length) = self.length {
+            params.push(format!("xl={}", length));
+        }
+        let lists = [
+            ("tr", &self.trackers),
+            ("ws", &self.web_seeds),
+            ("x.pe", &self.peers),
+        ];
+        for (key, values) in lists {
+            for value in values {
+                params.push(format!("{}={}", key, encode_component(value)));
+            }
+        }
+        for (key, value) in &self.extra {
+            params.push(format!(
+                "{}={}",
+                encode_component(key),
+                encode_component(value)
+            ));
+        }
+        write!(f, "magnet:?{}", params.join("&"))
+    }
+}
+
+/// 由 .torrent 文件生成磁力链接，并追加 `tracker_file` 中的 tracker
+///
+/// 私有种子只能使用自己的 tracker，不追加
+pub async fn torrent_to_magnet(
+    file: impl AsRef<Path>,
+    tracker_file: Option<&Path>,
+) -> Result<Magnet> {
+    let info = Metainfo::from_file(file).await?;
+    let mut magnet = Magnet::from_metainfo(&info);
+    if let Some(tracker_file) = tracker_file {
+        if info.private {
+            eprintln!(
+                "warning: {} is a private torrent, the tracker list is not appended",
+                info.name
+            );
+        } else {
+            let content = tokio::fs::read_to_string(tracker_file)
+                .await
+                .map_err(TorrentError::IoError)?;
+            magnet.add_trackers(&parse_tracker_list(&content));
+        }
+    }
+    Ok(magnet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "c884071d5230f969da785f45bfd13a3659c5a11b";
+
+    #[test]
+    fn test_parse_magnet() {
+        let magnet = Magnet::parse(&format!(
+            "magnet:?xt=urn:btih:{}&dn=Blow-Up+(1966)&xl=1024&tr=udp%3A%2F%2Fa.example%3A1337%2Fannounce&tr=http://b.example/announce&ws=https%3A%2F%2Fseed.example%2F&x.pe=10.0.0.1:6881&so=0-2",
+            HASH.to_uppercase()
+        ))
+        .unwrap();
+        assert_eq!(magnet.btih.as_deref(), Some(HASH));
+        assert_eq!(magnet.btmh, None);
+        assert_eq!(magnet.name.as_deref(), Some("Blow-Up (1966)"));
+        assert_eq!(magnet.length, Some(1024));
+        assert_eq!(
+            magnet.trackers,
+            vec!["udp://a.example:1337/announce", "http://b.example/announce"]
+        );
+        assert_eq!(magnet.web_seeds, vec!["https://seed.example/"]);
+        assert_eq!(magnet.peers, vec!["10.0.0.1:6881"]);
+        assert_eq!(magnet.extra, vec![("so".to_string(), "0-2".to_string())]);
+    }
+
+    #[test]
+    fn test_parse_base32_and_btmh() {
+        // 与 HASH 相同的 info-hash
+        let magnet: Magnet = "magnet:?xt=urn:btih:ZCCAOHKSGD4WTWTYL5C37UJ2GZM4LII3"
+            .parse()
+            .unwrap();
+        assert_eq!(magnet.btih.as_deref(), Some(HASH));
+
+        let digest = "ab".repeat(32);
+        let magnet = Magnet::parse(&format!("magnet:?xt=urn:btmh:1220{}", digest)).unwrap();
+        assert_eq!(magnet.btih, None);
+        assert_eq!(magnet.btmh.as_deref(), Some(digest.as_str()));
+    }
+
+    #[test]
+    fn test_parse_invalid_magnet() {
+        for uri in [
+            "http://example.org",
+            "magnet:?dn=no-hash",
+            "magnet:?xt=urn:btih:1234",
+            "magnet:?xt=urn:btih:ZCCAOHKSGD4WTWTYL5C37UJ2GZM4LII1",
+            "magnet:?xt=urn:btmh:1114abcd",
+            &format!("magnet:?xt=urn:btih:{}&xl=big", HASH),
+        ] {
+            assert!(
+                matches!(Magnet::parse(uri), Err(TorrentError::InvalidMagnet(_))),
+                "{}",
+                uri
+            );
+        }
+    }
+
+    #[test]
+    fn test_build_magnet() {
+        let mut magnet = Magnet {
+            btih: Some(HASH.to_string()),
+            btmh: Some("ab".repeat(32)),
+            name: Some("Blow-Up (1966)".to_string()),
+            length: Some(1024),
+            trackers: vec!["udp://a.example:1337/announce".to_string()],
+            ..Magnet::default()
+        };
+        assert_eq!(
+            magnet.add_trackers(&["UDP://a.example:1337/announce", "http://b.example/announce"]),
+            1
+        );
+        let uri = magnet.to_string();
+        assert_eq!(
+            uri,
+            format!(
+                "magnet:?xt=urn:btih:{}&xt=urn:btmh:1220{}&dn=Blow-Up%20%281966%29&xl=1024&tr=udp%3A%2F%2Fa.example%3A1337%2Fannounce&tr=http%3A%2F%2Fb.example%2Fannounce",
+                HASH,
+                "ab".repeat(32)
+            )
+        );
+        assert_eq!(Magnet::parse(&uri).unwrap(), magnet);
+    }
+}
@@ -0,0 +1,647 @@
+//! .torrent 文件（metainfo）的解析与展示，支持 v1、v2（BEP 52）以及混合种子
+
+use std::{collections::BTreeMap, fmt::Write as _, ops::Range, path::Path};
+
+use chrono::DateTime;
+use prettytable::{Cell, Row, Table};
+use serde::Serialize;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use super::{
+    Result, TorrentError,
+    bencode::{Value, decode_dict_spans},
+};
+use crate::common::OutputFormat;
+
+/// 种子中的单个文件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TorrentFile {
+    /// 相对于种子根目录的路径，以 `/` 分隔；单文件种子为文件名
+    pub path: String,
+    pub length: u64,
+}
+
+/// 种子的元信息，用于展示
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Metainfo {
+    pub name: String,
+    /// v1 info-hash（SHA-1），纯 v2 种子没有
+    pub info_hash_v1: Option<String>,
+    /// v2 info-hash（SHA-256），v1 种子没有
+    pub info_hash_v2: Option<String>,
+    pub piece_length: u64,
+    pub piece_count: u64,
+    pub private: bool,
+    /// 多文件种子的文件放在以 `name` 命名的目录中
+    pub multi_file: bool,
+    /// RFC 3339 格式的创建时间
+    pub creation_date: Option<String>,
+    pub created_by: Option<String>,
+    pub comment: Option<String>,
+    pub announce: Option<String>,
+    /// BEP 12 的 announce-list，每个元素为一层
+    pub announce_list: Vec<Vec<String>>,
+    /// BEP 19 的 web seed（`url-list`）
+    pub web_seeds: Vec<String>,
+    /// 不包括 BEP 47 的填充文件
+    pub files: Vec<TorrentFile>,
+    pub total_size: u64,
+}
+
+fn invalid(reason: impl Into<String>) -> TorrentError {
+    TorrentError::InvalidMetainfo(reason.into())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{:02x}", b);
+        s
+    })
+}
+
+/// 解码 .torrent 文件，返回顶层字典以及 info 字典在 `data` 中的字节范围
+pub fn decode_torrent(data: &[u8]) -> Result<(Value, Range<usize>)> {
+    let (value, spans) = decode_dict_spans(data)?;
+    let span = spans
+        .get(b"info".as_slice())
+        .cloned()
+        .ok_or_else(|| invalid("missing info dictionary"))?;
+    if value.get("info").and_then(Value::as_dict).is_none() {
+        return Err(invalid("info is not a dictionary"));
+    }
+    Ok((value, span))
+}
+
+fn string(value: &Value, key: &str) -> Option<String> {
+    value
+        .get(key)
+        .and_then(Value::as_bytes)
+        .map(|b| String::from_utf8_lossy(b).into_owned())
+}
+
+fn length(value: &Value) -> Result<u64> {
+    value
+        .get("length")
+        .and_then(Value::as_int)
+        .and_then(|l| u64::try_from(l).ok())
+        .ok_or_else(|| invalid("missing or negative file length"))
+}
+
+fn path_components(list: &[Value]) -> Result<Vec<String>> {
+    list.iter()
+        .map(|c| {
+            c.as_bytes()
+                .map(|b| String::from_utf8_lossy(b).into_owned())
+                .ok_or_else(|| invalid("path component is not a string"))
+        })
+        .collect()
+}
+
+/// v1 的文件列表，跳过 `attr` 中带 `p` 的填充文件
+fn v1_files(info: &Value, name: &str) -> Result<Option<Vec<TorrentFile>>> {
+    if info.get("length").is_some() {
+        return Ok(Some(vec![TorrentFile {
+            path: name.to_string(),
+            length: length(info)?,
+        }]));
+    }
+    let Some(list) = info.get("files").and_then(Value::as_list) else {
+        return Ok(None);
+    };
+    let mut files = Vec::new();
+    for file in list {
+        if file
+            .get("attr")
+            .and_then(Value::as_str)
+            .unwrap_or("")
+            .contains('p')
+        {
+            continue;
+        }
+        let path = file
+            .get("path")
+            .and_then(Value::as_list)
+            .ok_or_else(|| invalid("file without path"))?;
+        files.push(TorrentFile {
+            path: path_components(path)?.join("/"),
+            length: length(file)?,
+        });
+    }
+    Ok(Some(files))
+}
+
+/// v2 的文件树，叶子节点为空字符串键下的 `{length, pieces root}`
+fn v2_files(tree: &Value, prefix: &mut Vec<String>, files: &mut Vec<TorrentFile>) -> Result<()> {
+    let dict = tree
+        .as_dict()
+        .ok_or_else(|| invalid("file tree node is not a dictionary"))?;
+    for (key, node) in dict {
+        if key.is_empty() {
+            files.push(TorrentFile {
+                path: prefix.join("/"),
+                length: length(node)?,
+            });
+            continue;
+        }
+        prefix.push(String::from_utf8_lossy(key).into_owned());
+        v2_files(node, prefix, files)?;
+        prefix.pop();
+    }
+    Ok(())
+}
+
+impl Metainfo {
+    /// 从 .torrent 文件的内容解析
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let (torrent, span) = decode_torrent(data)?;
+        let info_bytes = &data[span];
+        let info = torrent.get("info").expect("checked by decode_torrent");
+
+        let name = string(info, "name").ok_or_else(|| invalid("missing name"))?;
+        let piece_length = info
+            .get("piece length")
+            .and_then(Value::as_int)
+            .and_then(|l| u64::try_from(l).ok())
+            .filter(|&l| l > 0)
+            .ok_or_else(|| invalid("missing piece length"))?;
+        let v2 = info.get("meta version").and_then(Value::as_int) == Some(2);
+        let pieces = info.get("pieces").and_then(Value::as_bytes);
+        if !v2 && pieces.is_none() {
+            return Err(invalid("missing pieces"));
+        }
+
+        let (files, multi_file) = match v1_files(info, &name)? {
+            Some(files) => (files, info.get("files").is_some()),
+            None if v2 => {
+                let tree = info
+                    .get("file tree")
+                    .ok_or_else(|| invalid("missing file tree"))?;
+                let mut files = Vec::new();
+                v2_files(tree, &mut Vec::new(), &mut files)?;
+                // 单文件的 v2 种子文件树中只有以 name 命名的文件
+                let single = files.len() == 1 && files[0].path == name;
+                (files, !single)
+            }
+            None => return Err(invalid("missing length or files")),
+        };
+        let total_size: u64 = files.iter().map(|f| f.length).sum();
+        // v2 中每个文件单独分块，不同文件不会共用同一块
+        let piece_count = match pieces {
+            Some(p) => (p.len() / 20) as u64,
+            None => files.iter().map(|f| f.length.div_ceil(piece_length)).sum(),
+        };
+
+        let announce_list = torrent
+            .get("announce-list")
+            .and_then(Value::as_list)
+            .map(|tiers| {
+                tiers
+                    .iter()
+                    .filter_map(Value::as_list)
+                    .map(|tier| {
+                        tier.iter()
+                            .filter_map(Value::as_str)
+                            .map(str::to_string)
+                            .collect::<Vec<_>>()
+                    })
+                    .filter(|tier| !tier.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // url-list 可以是单个地址或者地址列表
+        let web_seeds = match torrent.get("url-list") {
+            Some(Value::List(urls)) => urls
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect(),
+            Some(url) => url.as_str().map(str::to_string).into_iter().collect(),
+            None => Vec::new(),
+        };
+
+        Ok(Self {
+            name,
+            info_hash_v1: pieces.map(|_| hex(&Sha1::digest(info_bytes))),
+            info_hash_v2: v2.then(|| hex(&Sha256::digest(info_bytes))),
+            piece_length,
+            piece_count,
+            private: info.get("private").and_then(Value::as_int) == Some(1),
+            multi_file,
+            creation_date: torrent
+                .get("creation date")
+                .and_then(Value::as_int)
+                .and_then(|t| DateTime::from_timestamp(t, 0))
+                .map(|t| t.to_rfc3339()),
+            created_by: string(&torrent, "created by"),
+            comment: string(&torrent, "comment"),
+            announce: string(&torrent, "announce"),
+            announce_list,
+            web_seeds,
+            files,
+            total_size,
+        })
+    }
+
+    /// 读取并解析 .torrent 文件
+    pub async fn from_file(file: impl AsRef<Path>) -> Result<Self> {
+        let data = tokio::fs::read(file).await.map_err(TorrentError::IoError)?;
+        Self::parse(&data)
+    }
+
+    /// 实际使用的 tracker 分层：有 announce-list 时忽略 announce
+    pub fn tiers(&self) -> Vec<Vec<String>> {
+        if !self.announce_list.is_empty() {
+            self.announce_list.clone()
+        } else {
+            self.announce.iter().map(|a| vec![a.clone()]).collect()
+        }
+    }
+
+    /// 以缩进的目录树展示文件，目录以 `/` 结尾
+    pub fn file_tree(&self) -> Vec<String> {
+        #[derive(Default)]
+        struct Node {
+            children: BTreeMap<String, Node>,
+            length: Option<u64>,
+        }
+        fn render(node: &Node, depth: usize, lines: &mut Vec<String>) {
+            for (name, child) in &node.children {
+                let indent = "  ".repeat(depth);
+                match child.length {
+                    Some(length) if child.children.is_empty() => {
+                        lines.push(format!("{}{} ({})", indent, name, format_size(length)))
+                    }
+                    _ => {
+                        lines.push(format!("{}{}/", indent, name));
+                        render(child, depth + 1, lines);
+                    }
+                }
+            }
+        }
+
+        let mut root = Node::default();
+        for file in &self.files {
+            let mut node = &mut root;
+            if self.multi_file {
+                node = node.children.entry(self.name.clone()).or_default();
+            }
+            for component in file.path.split('/') {
+                node = node.children.entry(component.to_string()).or_default();
+            }
+            node.length = Some(file.length);
+        }
+        let mut lines = Vec::new();
+        render(&root, 0, &mut lines);
+        lines
+    }
+}
+
+/// 以 1024 为进制的可读大小
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.2} {}", size, UNITS[unit])
+}
+
+fn or_na(value: &Option<String>) -> &str {
+    value.as_deref().unwrap_or("N/A")
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value { "yes" } else { "no" }
+}
+
+/// 按照指定格式输出 .torrent 文件的信息
+pub async fn show_torrent_info(file: impl AsRef<Path>, format: OutputFormat) -> Result<()> {
+    let info = Metainfo::from_file(file).await?;
+    match format {
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&info).expect("metainfo is serializable")
+        ),
+        OutputFormat::Table => print_table(&info),
+        OutputFormat::List => print_list(&info),
+    }
+    Ok(())
+}
+
+fn print_list(info: &Metainfo) {
+    println!(
+        "Name({}) Size({}) Files({}) PieceSize({}) Pieces({}) Private({})",
+        info.name,
+        format_size(info.total_size),
+        info.files.len(),
+        format_size(info.piece_length),
+        info.piece_count,
+        yes_no(info.private)
+    );
+    println!(
+        "InfoHashV1({}) InfoHashV2({})",
+        or_na(&info.info_hash_v1),
+        or_na(&info.info_hash_v2)
+    );
+    println!(
+        "Created({}) CreatedBy({}) Comment({})",
+        or_na(&info.creation_date),
+        or_na(&info.created_by),
+        or_na(&info.comment)
+    );
+    for (i, tier) in info.tiers().iter().enumerate() {
+        println!("Tier {}: {}", i + 1, tier.join(" "));
+    }
+    for seed in &info.web_seeds {
+        println!("WebSeed: {}", seed);
+    }
+    for line in info.file_tree() {
+        println!("{}", line);
+    }
+}
+
+fn print_table(info: &Metainfo) {
+    let mut table = Table::new();
+    let rows = [
+        ("Name", info.name.clone()),
+        ("Info Hash v1", or_na(&info.info_hash_v1).to_string()),
+        ("Info Hash v2", or_na(&info.info_hash_v2).to_string()),
+        ("Size", format_size(info.total_size)),
+        ("Piece Size", format_size(info.piece_length)),
+        ("Pieces", info.piece_count.to_string()),
+        ("Private", yes_no(info.private).to_string()),
+        ("Created", or_na(&info.creation_date).to_string()),
+        ("Created By", or_na(&info.created_by).to_string()),
+        ("Comment", or_na(&info.comment).to_string()),
+        ("Web Seeds", info.web_seeds.join("\n")),
+    ];
+    for (key, value) in rows {
+        table.add_row(Row::new(vec![Cell::new(key), Cell::new(&value)]));
+    }
+    table.printstd();
+
+    let tiers = info.tiers();
+    if !tiers.is_empty() {
+        let mut table = Table::new();
+        table.add_row(Row::new(vec![Cell::new("Tier"), Cell::new("Tracker")]));
+        for (i, tier) in tiers.iter().enumerate() {
+            for tracker in tier {
+                table.add_row(Row::new(vec![
+                    Cell::new(&(i + 1).to_string()),
+                    Cell::new(tracker),
+                ]));
+            }
+        }
+        table.printstd();
+    }
+
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![Cell::new("Path"), Cell::new("Size")]));
+    for file in &info.files {
+        table.add_row(Row::new(vec![
+            Cell::new(&file.path),
+            Cell::new(&format_size(file.length)),
+        ]));
+    }
+    table.printstd();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dict(pairs: Vec<(&str, Value)>) -> Value {
+        Value::Dict(
+            pairs
+                .into_iter()
+                .map(|(k, v)| (k.as_bytes().to_vec(), v))
+                .collect(),
+        )
+    }
+
+    fn list(items: Vec<Value>) -> Value {
+        Value::List(items)
+    }
+
+    fn multi_file_torrent() -> Vec<u8> {
+        let file = |path: Vec<&str>, length: i64| {
+            dict(vec![
+                ("length", length.into()),
+                ("path", list(path.into_iter().map(Value::from).collect())),
+            ])
+        };
+        let mut padding = file(vec![".pad", "28"], 28);
+        if let Value::Dict(d) = &mut padding {
+            d.insert(b"attr".to_vec(), "p".into());
+        }
+        dict(vec![
+            ("announce", "udp://a.example:1337/announce".into()),
+            (
+                "announce-list",
+                list(vec![
+                    list(vec![
+                        "udp://a.example:1337/announce".into(),
+                        "http://b.example/announce".into(),
+                    ]),
+                    list(vec!["udp://c.example:80/announce".into()]),
+                ]),
+            ),
+            ("comment", "Blow-Up".into()),
+            ("created by", "blowup".into()),
+            ("creation date", 1792281600.into()),
+            ("url-list", "https://seed.example/".into()),
+            (
+                "info",
+                dict(vec![
+                    (
+                        "files",
+                        list(vec![
+                            file(vec!["a.mkv"], 100),
+                            padding,
+                            file(vec!["subs", "b.srt"], 30),
+                        ]),
+                    ),
+                    ("name", "Blow-Up".into()),
+                    ("piece length", 16384.into()),
+                    ("pieces", Value::Bytes(vec![0; 20])),
+                    ("private", 1.into()),
+                ]),
+            ),
+        ])
+        .encode()
+    }
+
+    #[test]
+    fn test_parse_v1_multi_file() {
+        let info = Metainfo::parse(&multi_file_torrent()).unwrap();
+        assert_eq!(info.name, "Blow-Up");
+        assert!(info.multi_file);
+        assert!(info.private);
+        assert_eq!(info.piece_length, 16384);
+        assert_eq!(info.piece_count, 1);
+        assert_eq!(info.total_size, 130);
+        assert_eq!(info.info_hash_v2, None);
+        assert_eq!(info.info_hash_v1.as_ref().unwrap().len(), 40);
+        assert_eq!(
+            info.creation_date.as_deref(),
+            Some("2026-10-18T00:00:00+00:00")
+        );
+        assert_eq!(info.created_by.as_deref(), Some("blowup"));
+        assert_eq!(info.web_seeds, vec!["https://seed.example/"]);
+        assert_eq!(
+            info.tiers(),
+            vec![
+                vec!["udp://a.example:1337/announce", "http://b.example/announce"],
+                vec!["udp://c.example:80/announce"],
+            ]
+        );
+        // 填充文件不显示
+        assert_eq!(
+            info.file_tree(),
+            vec!["Blow-Up/", "  a.mkv (100 B)", "  subs/", "    b.srt (30 B)"]
+        );
+    }
+
+    #[test]
+    fn test_info_hash_covers_raw_info_bytes() {
+        let raw = dict(vec![
+            ("length", 5.into()),
+            ("name", "a.txt".into()),
+            ("piece length", 16384.into()),
+            ("pieces", Value::Bytes(vec![1; 20])),
+        ])
+        .encode();
+        let data = [b"d4:info".as_slice(), &raw, b"e"].concat();
+        let info = Metainfo::parse(&data).unwrap();
+        assert_eq!(info.info_hash_v1, Some(hex(&Sha1::digest(&raw))));
+        assert!(!info.multi_file);
+        assert!(!info.private);
+        assert_eq!(info.tiers(), Vec::<Vec<String>>::new());
+        assert_eq!(info.file_tree(), vec!["a.txt (5 B)"]);
+    }
+
+    #[test]
+    fn test_parse_v2_and_hybrid() {
+        let leaf = |length: i64| {
+            dict(vec![(
+                "",
+                dict(vec![
+                    ("length", length.into()),
+                    ("pieces root", Value::Bytes(vec![2; 32])),
+                ]),
+            )])
+        };
+        let v2 = dict(vec![(
+            "info",
+            dict(vec![
+                ("file tree", dict(vec![("a.mkv", leaf(40000))])),
+                ("meta version", 2.into()),
+                ("name", "a.mkv".into()),
+                ("piece length", 16384.into()),
+            ]),
+        )]);
+        let info = Metainfo::parse(&v2.encode()).unwrap();
+        assert_eq!(info.info_hash_v1, None);
+        assert_eq!(info.info_hash_v2.as_ref().unwrap().len(), 64);
+        assert!(!info.multi_file);
+        assert_eq!(info.piece_count, 3);
+        assert_eq!(info.files[0].path, "a.mkv");
+
+        let v2_multi = dict(vec![(
+            "info",
+            dict(vec![
+                (
+                    "file tree",
+                    dict(vec![("dir", dict(vec![("x", leaf(1)), ("y", leaf(2))]))]),
+                ),
+                ("meta version", 2.into()),
+                ("name", "root".into()),
+                ("piece length", 16384.into()),
+            ]),
+        )]);
+        let info = Metainfo::parse(&v2_multi.encode()).unwrap();
+        assert!(info.multi_file);
+        assert_eq!(
+            info.file_tree(),
+            vec!["root/", "  dir/", "    x (1 B)", "    y (2 B)"]
+        );
+
+        let hybrid = dict(vec![(
+            "info",
+            dict(vec![
+                ("file tree", dict(vec![("a.mkv", leaf(5))])),
+                ("length", 5.into()),
+                ("meta version", 2.into()),
+                ("name", "a.mkv".into()),
+                ("piece length", 16384.into()),
+                ("pieces", Value::Bytes(vec![3; 20])),
+            ]),
+        )]);
+        let info = Metainfo::parse(&hybrid.encode()).unwrap();
+        assert!(info.info_hash_v1.is_some());
+        assert!(info.info_hash_v2.is_some());
+    }
+
+    #[test]
+    fn test_v2_multi_file_piece_count() {
+        let leaf = |length: i64| {
+            dict(vec![(
+                "",
+                dict(vec![
+                    ("length", length.into()),
+                    ("pieces root", Value::Bytes(vec![2; 32])),
+                ]),
+            )])
+        };
+        let v2 = dict(vec![(
+            "info",
+            dict(vec![
+                (
+                    "file tree",
+                    dict(vec![
+                        ("a.mkv", leaf(20000)),
+                        ("b.srt", leaf(1)),
+                        ("c.nfo", leaf(16384)),
+                    ]),
+                ),
+                ("meta version", 2.into()),
+                ("name", "root".into()),
+                ("piece length", 16384.into()),
+            ]),
+        )]);
+        let info = Metainfo::parse(&v2.encode()).unwrap();
+        assert_eq!(info.total_size, 36385);
+        // 2 + 1 + 1，而不是按总大小计算的 3
+        assert_eq!(info.piece_count, 4);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(matches!(
+            Metainfo::parse(b"d8:announce3:urle"),
+            Err(TorrentError::InvalidMetainfo(_))
+        ));
+        assert!(matches!(
+            Metainfo::parse(b"d4:infod4:name1:aee"),
+            Err(TorrentError::InvalidMetainfo(_))
+        ));
+        assert!(matches!(
+            Metainfo::parse(b"not bencode"),
+            Err(TorrentError::Bencode(_))
+        ));
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(16384), "16.00 KiB");
+        assert_eq!(format_size(1536 * 1024 * 1024), "1.50 GiB");
+    }
+}
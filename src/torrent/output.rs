@@ -0,0 +1,160 @@
+//! 合并后的 tracker 列表的输出位置和格式
+
+use std::{
+    fmt, fs, io,
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use clap::ValueEnum;
+use serde::Deserialize;
+
+/// tracker 列表的格式，对应不同的使用方
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum TrackerListFormat {
+    /// 每行一个 tracker
+    #[default]
+    Lines,
+    /// 逗号分隔的一行，即 aria2 的 `bt-tracker` 选项
+    Aria2,
+    /// 以空行分隔，qBittorrent 中每个 tracker 单独作为一层
+    Qbittorrent,
+    /// JSON 字符串数组
+    Json,
+}
+
+impl TrackerListFormat {
+    /// 按照格式生成列表的内容，以换行结尾
+    pub fn render<S: AsRef<str>>(&self, trackers: &[S]) -> String {
+        let trackers: Vec<&str> = trackers.iter().map(AsRef::as_ref).collect();
+        let mut content = match self {
+            Self::Lines => trackers.join("\n"),
+            Self::Aria2 => trackers.join(","),
+            Self::Qbittorrent => trackers.join("\n\n"),
+            Self::Json => serde_json::to_string_pretty(&trackers).expect("strings serialize"),
+        };
+        content.push('\n');
+        content
+    }
+}
+
+/// tracker 列表的输出位置，命令行中的 `-` 表示标准输出
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrackerOutput {
+    Stdout,
+    File(PathBuf),
+}
+
+impl TrackerOutput {
+    /// 写入列表，文件通过 [`write_atomic`] 整体替换
+    pub fn write(&self, content: &str) -> io::Result<()> {
+        match self {
+            Self::Stdout => {
+                let mut stdout = io::stdout().lock();
+                stdout.write_all(content.as_bytes())?;
+                stdout.flush()
+            }
+            Self::File(path) => write_atomic(path, content.as_bytes()),
+        }
+    }
+}
+
+impl FromStr for TrackerOutput {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" => Err("output must not be empty".to_string()),
+            "-" => Ok(Self::Stdout),
+            _ => Ok(Self::File(PathBuf::from(s))),
+        }
+    }
+}
+
+impl fmt::Display for TrackerOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stdout => f.write_str("stdout"),
+            Self::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// 先写入同一目录下的临时文件再重命名，读者只会看到旧文件或者完整的新文件
+pub fn write_atomic(path: &Path, content: &[u8]) -> io::Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let mut tmp_name = name.to_os_string();
+    tmp_name.push(".tmp");
+    let tmp = path.with_file_name(tmp_name);
+
+    let result = (|| {
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::torrent::source::parse_tracker_list;
+
+    const TRACKERS: [&str; 2] = ["udp://a.example:1337/announce", "http://b.example/announce"];
+
+    #[test]
+    fn test_render_formats() {
+        assert_eq!(
+            TrackerListFormat::Lines.render(&TRACKERS),
+            "udp://a.example:1337/announce\nhttp://b.example/announce\n"
+        );
+        assert_eq!(
+            TrackerListFormat::Aria2.render(&TRACKERS),
+            "udp://a.example:1337/announce,http://b.example/announce\n"
+        );
+        assert_eq!(
+            TrackerListFormat::Qbittorrent.render(&TRACKERS),
+            "udp://a.example:1337/announce\n\nhttp://b.example/announce\n"
+        );
+        assert_eq!(
+            TrackerListFormat::Json.render(&TRACKERS),
+            "[\n  \"udp://a.example:1337/announce\",\n  \"http://b.example/announce\"\n]\n"
+        );
+        // 所有格式都可以被其它命令重新读取
+        for format in TrackerListFormat::value_variants() {
+            assert_eq!(parse_tracker_list(&format.render(&TRACKERS)), TRACKERS);
+        }
+    }
+
+    #[test]
+    fn test_parse_output() {
+        assert_eq!("-".parse(), Ok(TrackerOutput::Stdout));
+        assert_eq!(
+            "out/trackers.txt".parse(),
+            Ok(TrackerOutput::File(PathBuf::from("out/trackers.txt")))
+        );
+        assert!("".parse::<TrackerOutput>().is_err());
+    }
+
+    #[test]
+    fn test_write_atomic() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tracker_all.txt");
+        write_atomic(&path, b"old\n").unwrap();
+        write_atomic(&path, b"new\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        let missing = dir.path().join("missing").join("tracker_all.txt");
+        assert!(write_atomic(&missing, b"new\n").is_err());
+        assert!(write_atomic(Path::new("/"), b"new\n").is_err());
+    }
+}
@@ -0,0 +1,224 @@
+//! piece 的哈希计算：v1 为整个 piece 的 SHA-1，v2（BEP 52）为 16KiB 块组成的 SHA-256 merkle 树
+
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use tokio::{sync::Semaphore, task::JoinSet};
+
+/// v2 merkle 树叶子节点对应的块大小
+pub const BLOCK_SIZE: u64 = 16 * 1024;
+
+/// 文件中的一段连续数据
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    pub path: PathBuf,
+    pub offset: u64,
+    pub len: u64,
+}
+
+/// 一个 piece 的数据来源以及需要计算的哈希
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PieceSpec {
+    /// v1 的 piece 可以跨越多个文件
+    pub chunks: Vec<Chunk>,
+    /// 数据之后的填充字节数（混合种子中的填充文件），只参与 v1 哈希
+    pub pad: u64,
+    /// 是否计算 v1 哈希
+    pub v1: bool,
+    /// 计算 v2 哈希时 merkle 树的叶子数量（2 的幂），此时 piece 只能来自一个文件
+    pub v2_leaves: Option<u64>,
+}
+
+/// 一个 piece 的哈希
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PieceHash {
+    pub v1: Option<[u8; 20]>,
+    pub v2: Option<[u8; 32]>,
+}
+
+fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// merkle 树的根，叶子不足 `width`（2 的幂）个时用 `pad` 补齐
+pub fn merkle_root(leaves: &[[u8; 32]], width: u64, pad: [u8; 32]) -> [u8; 32] {
+    let mut layer = leaves.to_vec();
+    layer.resize(width.max(1) as usize, pad);
+    while layer.len() > 1 {
+        layer = layer
+            .chunks(2)
+            .map(|pair| hash_pair(&pair[0], &pair[1]))
+            .collect();
+    }
+    layer[0]
+}
+
+/// 由 `leaves` 个全零叶子组成的子树的根，用于补齐 piece 层
+pub fn pad_hash(leaves: u64) -> [u8; 32] {
+    let mut hash = [0; 32];
+    let mut width = 1;
+    while width < leaves {
+        hash = hash_pair(&hash, &hash);
+        width *= 2;
+    }
+    hash
+}
+
+/// 文件的 `pieces root`：`pieces` 为文件每个 piece 的 v2 哈希，每个 piece 有 `leaves_per_piece` 个叶子
+pub fn file_root(pieces: &[[u8; 32]], leaves_per_piece: u64) -> [u8; 32] {
+    match pieces {
+        [single] => *single,
+        _ => merkle_root(
+            pieces,
+            (pieces.len() as u64).next_power_of_two(),
+            pad_hash(leaves_per_piece),
+        ),
+    }
+}
+
+/// 读取并计算一个 piece 的哈希，在阻塞线程中调用
+pub fn hash_piece(spec: &PieceSpec) -> io::Result<PieceHash> {
+    let mut sha1 = spec.v1.then(Sha1::new);
+    let mut leaves = Vec::new();
+    let mut block = vec![0; BLOCK_SIZE as usize];
+    for chunk in &spec.chunks {
+        let mut file = File::open(&chunk.path)?;
+        file.seek(SeekFrom::Start(chunk.offset))?;
+        let mut remaining = chunk.len;
+        while remaining > 0 {
+            let len = remaining.min(BLOCK_SIZE) as usize;
+            file.read_exact(&mut block[..len])?;
+            if let Some(sha1) = &mut sha1 {
+                sha1.update(&block[..len]);
+            }
+            if spec.v2_leaves.is_some() {
+                leaves.push(Sha256::digest(&block[..len]).into());
+            }
+            remaining -= len as u64;
+        }
+    }
+    if let Some(sha1) = &mut sha1 {
+        let zeros = [0; BLOCK_SIZE as usize];
+        let mut pad = spec.pad;
+        while pad > 0 {
+            let len = pad.min(BLOCK_SIZE) as usize;
+            sha1.update(&zeros[..len]);
+            pad -= len as u64;
+        }
+    }
+    Ok(PieceHash {
+        v1: sha1.map(|h| h.finalize().into()),
+        v2: spec
+            .v2_leaves
+            .map(|width| merkle_root(&leaves, width, [0; 32])),
+    })
+}
+
+/// 以最多 `jobs` 个阻塞线程并发计算所有 piece 的哈希，结果的顺序与输入的顺序相同
+pub async fn hash_pieces(specs: Vec<PieceSpec>, jobs: usize) -> Vec<io::Result<PieceHash>> {
+    let semaphore = Arc::new(Semaphore::new(jobs.max(1)));
+    let mut set = JoinSet::new();
+    let count = specs.len();
+    for (idx, spec) in specs.into_iter().enumerate() {
+        let semaphore = semaphore.clone();
+        set.spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            let result = tokio::task::spawn_blocking(move || hash_piece(&spec))
+                .await
+                .unwrap_or_else(|e| Err(io::Error::other(e)));
+            (idx, result)
+        });
+    }
+
+    let mut results: Vec<Option<io::Result<PieceHash>>> = (0..count).map(|_| None).collect();
+    while let Some(joined) = set.join_next().await {
+        if let Ok((idx, result)) = joined {
+            results[idx] = Some(result);
+        }
+    }
+    results
+        .into_iter()
+        .map(|r| r.unwrap_or_else(|| Err(io::Error::other("task panicked"))))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merkle_root_and_pad_hash() {
+        let a = [1; 32];
+        let b = [2; 32];
+        assert_eq!(merkle_root(&[a], 1, [0; 32]), a);
+        assert_eq!(merkle_root(&[a, b], 2, [0; 32]), hash_pair(&a, &b));
+        assert_eq!(
+            merkle_root(&[a], 4, [0; 32]),
+            hash_pair(&hash_pair(&a, &[0; 32]), &pad_hash(2))
+        );
+        assert_eq!(pad_hash(1), [0; 32]);
+        assert_eq!(pad_hash(4), merkle_root(&[], 4, [0; 32]));
+        // piece 层用零子树补齐，与直接补零叶子的结果相同
+        assert_eq!(
+            file_root(&[merkle_root(&[a, b], 2, [0; 32])], 2),
+            merkle_root(&[a, b], 2, [0; 32])
+        );
+        assert_eq!(
+            file_root(&[merkle_root(&[a, b], 2, [0; 32]); 3], 2),
+            merkle_root(&[a, b, a, b, a, b], 8, [0; 32])
+        );
+    }
+
+    #[test]
+    fn test_hash_piece_across_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("a");
+        let second = dir.path().join("b");
+        std::fs::write(&first, b"hello ").unwrap();
+        std::fs::write(&second, b"world!").unwrap();
+
+        let spec = PieceSpec {
+            chunks: vec![
+                Chunk {
+                    path: first,
+                    offset: 0,
+                    len: 6,
+                },
+                Chunk {
+                    path: second.clone(),
+                    offset: 0,
+                    len: 5,
+                },
+            ],
+            pad: 3,
+            v1: true,
+            v2_leaves: None,
+        };
+        let hash = hash_piece(&spec).unwrap();
+        assert_eq!(hash.v1, Some(Sha1::digest(b"hello world\0\0\0").into()));
+        assert_eq!(hash.v2, None);
+
+        let spec = PieceSpec {
+            chunks: vec![Chunk {
+                path: second,
+                offset: 1,
+                len: 5,
+            }],
+            pad: 0,
+            v1: false,
+            v2_leaves: Some(1),
+        };
+        let hash = hash_piece(&spec).unwrap();
+        assert_eq!(hash.v1, None);
+        assert_eq!(hash.v2, Some(Sha256::digest(b"orld!").into()));
+    }
+}
@@ -0,0 +1,502 @@
+//! tracker 列表的来源：GitHub 仓库中的文件、HTTP(S) 地址和本地文件
+//!
+//! 远程来源使用条件请求并缓存上一次的结果，所有来源的列表合并去重后使用
+
+use std::{collections::HashSet, fmt, str::FromStr, time::Duration};
+
+use chrono::Local;
+use reqwest::{
+    StatusCode,
+    header::{ACCEPT, ETAG, HeaderValue, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+};
+use serde::Deserialize;
+use tokio::task::JoinSet;
+use url::Url;
+
+use super::{
+    Result, TorrentError,
+    cache::{CachedSource, TrackerCache},
+};
+use crate::config::expand_path;
+
+/// 默认的 tracker 列表来源
+pub const DEFAULT_OWNER: &str = "ngosang";
+pub const DEFAULT_REPO: &str = "trackerslist";
+pub const DEFAULT_PATH: &str = "trackers_all.txt";
+
+/// tracker 列表的来源
+///
+/// 配置文件中的写法：
+///
+/// ```toml
+/// [[tracker.sources]]
+/// github = { repo = "ngosang/trackerslist", path = "trackers_best.txt" }
+///
+/// [[tracker.sources]]
+/// url = "https://newtrackon.com/api/stable"
+///
+/// [[tracker.sources]]
+/// file = "~/trackers/private.txt"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
+pub enum TrackerSource {
+    /// GitHub 仓库中的文件，通过 GitHub API 获取
+    #[serde(rename = "github")]
+    GitHub(GitHubFile),
+    /// 普通的 HTTP(S) 地址
+    Url(String),
+    /// 本地文件，支持 `~`
+    File(String),
+}
+
+/// GitHub 仓库中的一个文件
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GitHubFile {
+    /// `owner/repo` 形式的仓库名
+    pub repo: String,
+    pub path: String,
+    /// 分支、标签或者提交，默认为仓库的默认分支
+    #[serde(default)]
+    pub branch: Option<String>,
+}
+
+impl Default for TrackerSource {
+    fn default() -> Self {
+        Self::GitHub(GitHubFile {
+            repo: format!("{}/{}", DEFAULT_OWNER, DEFAULT_REPO),
+            path: DEFAULT_PATH.to_string(),
+            branch: None,
+        })
+    }
+}
+
+impl fmt::Display for TrackerSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::GitHub(file) => {
+                write!(f, "github:{}/{}", file.repo, file.path)?;
+                match &file.branch {
+                    Some(branch) => write!(f, "@{}", branch),
+                    None => Ok(()),
+                }
+            }
+            Self::Url(url) => write!(f, "{}", url),
+            Self::File(path) => write!(f, "{}", path),
+        }
+    }
+}
+
+/// 命令行中的写法：`github:owner/repo/path[@branch]`、`http(s)://...`，其它的都视为本地文件
+impl FromStr for TrackerSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if let Some(spec) = s.strip_prefix("github:") {
+            let (spec, branch) = match spec.rsplit_once('@') {
+                Some((spec, branch)) => (spec, Some(branch.to_string())),
+                None => (spec, None),
+            };
+            let mut parts = spec.splitn(3, '/');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(owner), Some(repo), Some(path))
+                    if !owner.is_empty() && !repo.is_empty() && !path.is_empty() =>
+                {
+                    Ok(Self::GitHub(GitHubFile {
+                        repo: format!("{}/{}", owner, repo),
+                        path: path.to_string(),
+                        branch,
+                    }))
+                }
+                _ => Err(format!(
+                    "invalid GitHub source {}, expected github:owner/repo/path[@branch]",
+                    s
+                )),
+            }
+        } else if s.starts_with("http://") || s.starts_with("https://") {
+            Ok(Self::Url(s.to_string()))
+        } else if s.is_empty() {
+            Err("empty tracker source".to_string())
+        } else {
+            Ok(Self::File(s.to_string()))
+        }
+    }
+}
+
+/// GitHub REST API 的地址
+const GITHUB_API: &str = "https://api.github.com";
+
+/// GitHub API 要求每个请求都带有 User-Agent
+const USER_AGENT: &str = concat!("blowup/", env!("CARGO_PKG_VERSION"));
+
+/// 远程来源的请求超时时间
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 条件请求的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fetched {
+    Modified {
+        content: String,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+    /// 服务器返回 304，缓存中的内容仍然是最新的
+    NotModified,
+}
+
+impl TrackerSource {
+    /// 远程来源的地址，本地文件返回 `None`
+    fn remote_url(&self) -> Option<String> {
+        match self {
+            Self::GitHub(file) => {
+                let url = format!("{}/repos/{}/contents/{}", GITHUB_API, file.repo, file.path);
+                let Ok(mut parsed) = Url::parse(&url) else {
+                    return Some(url);
+                };
+                if let Some(branch) = &file.branch {
+                    parsed.query_pairs_mut().append_pair("ref", branch);
+                }
+                Some(parsed.into())
+            }
+            Self::Url(url) => Some(url.clone()),
+            Self::File(_) => None,
+        }
+    }
+
+    /// 获取来源的内容
+    ///
+    /// 远程来源有缓存时发送带 `If-None-Match`/`If-Modified-Since` 的条件请求，
+    /// 本地文件每次都直接读取
+    pub async fn fetch(
+        &self,
+        client: &reqwest::Client,
+        cached: Option<&CachedSource>,
+    ) -> Result<Fetched> {
+        let Some(url) = self.remote_url() else {
+            let Self::File(path) = self else {
+                unreachable!("only local files have no remote url")
+            };
+            let content = tokio::fs::read_to_string(expand_path(path))
+                .await
+                .map_err(TorrentError::IoError)?;
+            return Ok(Fetched::Modified {
+                content,
+                etag: None,
+                last_modified: None,
+            });
+        };
+
+        let mut request = client.get(url);
+        if let Self::GitHub(_) = self {
+            // 直接返回文件的原始内容，而不是 base64 编码的 JSON
+            request = request.header(ACCEPT, "application/vnd.github.raw+json");
+        }
+        if let Some(cached) = cached {
+            if let Some(etag) = &cached.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let response = request.send().await?;
+        if response.status() == StatusCode::NOT_MODIFIED && cached.is_some() {
+            return Ok(Fetched::NotModified);
+        }
+        let response = response.error_for_status()?;
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v: &HeaderValue| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let (etag, last_modified) = (header(ETAG), header(LAST_MODIFIED));
+        Ok(Fetched::Modified {
+            content: response.text().await?,
+            etag,
+            last_modified,
+        })
+    }
+}
+
+/// 从 tracker 列表的内容中取出所有 tracker，忽略空行和 `#` 开头的注释
+///
+/// 支持 `tracker update` 输出的所有格式：每行一个或以空行分隔、逗号分隔以及 JSON 字符串数组
+pub fn parse_tracker_list(content: &str) -> Vec<String> {
+    if content.trim_start().starts_with('[')
+        && let Ok(trackers) = serde_json::from_str::<Vec<String>>(content)
+    {
+        return trackers
+            .into_iter()
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .collect();
+    }
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .flat_map(|line| line.split(','))
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// 来源的内容从哪里得到
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Freshness {
+    /// 新获取的内容
+    Fetched,
+    /// 服务器确认缓存仍然是最新的
+    NotModified,
+    /// 获取失败，使用之前缓存的内容
+    Offline { reason: String, fetched_at: String },
+}
+
+/// 单个来源的获取结果
+#[derive(Debug)]
+pub struct SourceOutcome {
+    pub source: TrackerSource,
+    pub result: Result<(Vec<String>, Freshness)>,
+}
+
+/// 获取单个来源，返回 tracker 以及需要写入缓存的新内容
+async fn fetch_one(
+    client: &reqwest::Client,
+    source: &TrackerSource,
+    cached: Option<CachedSource>,
+) -> Result<(Vec<String>, Freshness, Option<CachedSource>)> {
+    match (source.fetch(client, cached.as_ref()).await, cached) {
+        (
+            Ok(Fetched::Modified {
+                content,
+                etag,
+                last_modified,
+            }),
+            _,
+        ) => {
+            let trackers = parse_tracker_list(&content);
+            let entry = source.remote_url().map(|_| CachedSource {
+                etag,
+                last_modified,
+                fetched_at: Local::now().to_rfc3339(),
+                trackers: trackers.clone(),
+            });
+            Ok((trackers, Freshness::Fetched, entry))
+        }
+        (Ok(Fetched::NotModified), Some(cached)) => {
+            Ok((cached.trackers, Freshness::NotModified, None))
+        }
+        (Ok(Fetched::NotModified), None) => Err(TorrentError::NetworkError),
+        (Err(e), Some(cached)) => Ok((
+            cached.trackers,
+            Freshness::Offline {
+                reason: e.to_string(),
+                fetched_at: cached.fetched_at,
+            },
+            None,
+        )),
+        (Err(e), None) => Err(e),
+    }
+}
+
+/// 并发获取所有来源，结果的顺序与来源的顺序相同
+///
+/// 远程来源使用 `cache` 中的内容发送条件请求，获取失败时退回到缓存的内容，新获取的内容会写回 `cache`
+pub async fn fetch_sources(
+    sources: &[TrackerSource],
+    cache: &mut TrackerCache,
+) -> Vec<SourceOutcome> {
+    let client = reqwest::Client::builder()
+        .user_agent(USER_AGENT)
+        .timeout(FETCH_TIMEOUT)
+        .build()
+        .unwrap_or_default();
+    let mut set = JoinSet::new();
+    for (idx, source) in sources.iter().cloned().enumerate() {
+        let client = client.clone();
+        let cached = cache.get(&source.to_string()).cloned();
+        set.spawn(async move { (idx, fetch_one(&client, &source, cached).await) });
+    }
+
+    let mut results: Vec<Option<Result<_>>> = sources.iter().map(|_| None).collect();
+    while let Some(joined) = set.join_next().await {
+        if let Ok((idx, result)) = joined {
+            results[idx] = Some(result);
+        }
+    }
+    sources
+        .iter()
+        .cloned()
+        .zip(results)
+        .map(|(source, result)| {
+            let result = result.unwrap_or(Err(TorrentError::NetworkError)).map(
+                |(trackers, freshness, entry)| {
+                    if let Some(entry) = entry {
+                        cache.sources.insert(source.to_string(), entry);
+                    }
+                    (trackers, freshness)
+                },
+            );
+            SourceOutcome { source, result }
+        })
+        .collect()
+}
+
+/// 合并所有成功的来源并去除重复的 tracker，保留第一次出现的顺序
+pub fn merge_outcomes(outcomes: &[SourceOutcome]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    outcomes
+        .iter()
+        .filter_map(|o| o.result.as_ref().ok())
+        .flat_map(|(trackers, _)| trackers)
+        .filter(|tracker| seen.insert(tracker.as_str()))
+        .cloned()
+        .collect()
+}
+
+/// 在标准错误中打印每个来源的结果
+pub fn print_outcomes(outcomes: &[SourceOutcome]) {
+    for outcome in outcomes {
+        match &outcome.result {
+            Ok((trackers, Freshness::Fetched)) => {
+                eprintln!(
+                    "[ok]        {} ({} trackers)",
+                    outcome.source,
+                    trackers.len()
+                )
+            }
+            Ok((trackers, Freshness::NotModified)) => eprintln!(
+                "[unchanged] {} ({} trackers)",
+                outcome.source,
+                trackers.len()
+            ),
+            Ok((trackers, Freshness::Offline { reason, fetched_at })) => eprintln!(
+                "[offline]   {}: {}, using the list cached at {} ({} trackers)",
+                outcome.source,
+                reason,
+                fetched_at,
+                trackers.len()
+            ),
+            Err(e) => eprintln!("[failed]    {}: {}", outcome.source, e),
+        }
+    }
+}
+
+/// 配置文件中没有指定来源时使用默认的来源
+pub fn configured_sources(configured: &[TrackerSource]) -> Vec<TrackerSource> {
+    if configured.is_empty() {
+        vec![TrackerSource::default()]
+    } else {
+        configured.to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_source() {
+        assert_eq!(
+            "github:ngosang/trackerslist/trackers_best.txt@master".parse(),
+            Ok(TrackerSource::GitHub(GitHubFile {
+                repo: "ngosang/trackerslist".to_string(),
+                path: "trackers_best.txt".to_string(),
+                branch: Some("master".to_string()),
+            }))
+        );
+        assert_eq!(
+            "github:XIU2/TrackersListCollection/blob/all.txt"
+                .parse::<TrackerSource>()
+                .unwrap()
+                .to_string(),
+            "github:XIU2/TrackersListCollection/blob/all.txt"
+        );
+        assert!(
+            "github:ngosang/trackerslist"
+                .parse::<TrackerSource>()
+                .is_err()
+        );
+        assert_eq!(
+            "https://newtrackon.com/api/stable".parse(),
+            Ok(TrackerSource::Url(
+                "https://newtrackon.com/api/stable".to_string()
+            ))
+        );
+        assert_eq!(
+            "~/trackers.txt".parse(),
+            Ok(TrackerSource::File("~/trackers.txt".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_github_url_encodes_branch() {
+        let source: TrackerSource = "github:ngosang/trackerslist/trackers_best.txt@fix/a b&c"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            source.remote_url().as_deref(),
+            Some(
+                "https://api.github.com/repos/ngosang/trackerslist/contents/trackers_best.txt?ref=fix%2Fa+b%26c"
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_tracker_list() {
+        let content = "udp://a.example:1337/announce\n\n# comment\n  http://b.example/announce  \n";
+        assert_eq!(
+            parse_tracker_list(content),
+            vec!["udp://a.example:1337/announce", "http://b.example/announce"]
+        );
+        assert_eq!(
+            parse_tracker_list("udp://a.example:1337/announce, http://b.example/announce,\n"),
+            vec!["udp://a.example:1337/announce", "http://b.example/announce"]
+        );
+        assert_eq!(
+            parse_tracker_list("[\"udp://a.example:1337/announce\", \"\"]"),
+            vec!["udp://a.example:1337/announce"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_and_merge_local_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first.txt");
+        let second = dir.path().join("second.txt");
+        fs::write(
+            &first,
+            "udp://a.example:1337/announce\nudp://b.example:80/announce\n",
+        )
+        .unwrap();
+        fs::write(
+            &second,
+            "udp://b.example:80/announce\nhttp://c.example/announce\n",
+        )
+        .unwrap();
+        let sources = vec![
+            TrackerSource::File(first.display().to_string()),
+            TrackerSource::File(dir.path().join("missing.txt").display().to_string()),
+            TrackerSource::File(second.display().to_string()),
+        ];
+
+        let outcomes = fetch_sources(&sources, &mut TrackerCache::default()).await;
+        assert_eq!(outcomes.len(), 3);
+        assert!(outcomes[1].result.is_err());
+        assert_eq!(
+            merge_outcomes(&outcomes),
+            vec![
+                "udp://a.example:1337/announce",
+                "udp://b.example:80/announce",
+                "http://c.example/announce",
+            ]
+        );
+    }
+}
@@ -0,0 +1,383 @@
+use std::{collections::HashSet, fmt, str::FromStr};
+
+use clap::ValueEnum;
+use glob::{MatchOptions, Pattern};
+use serde::Deserialize;
+use url::Url;
+
+use super::{Result, TorrentError};
+
+/// tracker 使用的协议
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum TrackerScheme {
+    Udp,
+    Http,
+    Https,
+    Wss,
+}
+
+impl TrackerScheme {
+    fn parse(scheme: &str) -> Option<Self> {
+        match scheme {
+            "udp" => Some(Self::Udp),
+            "http" => Some(Self::Http),
+            "https" => Some(Self::Https),
+            "wss" => Some(Self::Wss),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Udp => "udp",
+            Self::Http => "http",
+            Self::Https => "https",
+            Self::Wss => "wss",
+        }
+    }
+
+    /// 协议的默认端口，udp 没有默认端口
+    pub fn default_port(&self) -> Option<u16> {
+        match self {
+            Self::Udp => None,
+            Self::Http => Some(80),
+            Self::Https | Self::Wss => Some(443),
+        }
+    }
+}
+
+impl fmt::Display for TrackerScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// 默认的排序：udp 最快，优先使用
+pub const DEFAULT_ORDER: &[TrackerScheme] = &[
+    TrackerScheme::Udp,
+    TrackerScheme::Https,
+    TrackerScheme::Http,
+    TrackerScheme::Wss,
+];
+
+/// 规范化后的 tracker announce 地址
+///
+/// * 协议和主机名转为小写，去掉协议的默认端口
+/// * 去掉路径末尾的 `/`，`announce` 统一为小写，udp 没有路径时补上 `/announce`
+/// * 去掉 fragment，保留 query（私有 tracker 的 passkey）
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Tracker {
+    scheme: TrackerScheme,
+    host: String,
+    port: Option<u16>,
+    url: String,
+}
+
+impl Tracker {
+    pub fn parse(s: &str) -> Result<Self> {
+        let invalid = || TorrentError::InvalidTracker(s.to_string());
+        let parsed = Url::parse(s.trim()).map_err(|_| invalid())?;
+        let scheme = TrackerScheme::parse(parsed.scheme()).ok_or_else(invalid)?;
+        let host = parsed
+            .host_str()
+            .filter(|h| !h.is_empty())
+            .ok_or_else(invalid)?
+            .to_ascii_lowercase();
+        // 非特殊协议（udp）的默认端口不会被 url 去掉，这里统一处理
+        let port = parsed.port().filter(|p| Some(*p) != scheme.default_port());
+        if scheme == TrackerScheme::Udp && port.is_none() {
+            return Err(invalid());
+        }
+
+        let mut path = parsed.path().trim_end_matches('/').to_string();
+        if let Some((prefix, last)) = path.rsplit_once('/')
+            && last.eq_ignore_ascii_case("announce")
+        {
+            path = format!("{}/announce", prefix);
+        }
+        if scheme == TrackerScheme::Udp && path.is_empty() {
+            path = "/announce".to_string();
+        }
+
+        let mut url = format!("{}://{}", scheme, host);
+        if let Some(port) = port {
+            url.push_str(&format!(":{}", port));
+        }
+        url.push_str(&path);
+        if let Some(query) = parsed.query().filter(|q| !q.is_empty()) {
+            url.push('?');
+            url.push_str(query);
+        }
+        Ok(Self {
+            scheme,
+            host,
+            port,
+            url,
+        })
+    }
+
+    pub fn scheme(&self) -> TrackerScheme {
+        self.scheme
+    }
+
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// 实际使用的端口，没有写明时为协议的默认端口
+    pub fn port(&self) -> Option<u16> {
+        self.port.or(self.scheme.default_port())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.url
+    }
+}
+
+impl FromStr for Tracker {
+    type Err = TorrentError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for Tracker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.url)
+    }
+}
+
+/// 比较 tracker 时使用的形式，能解析的 tracker 先规范化
+fn tracker_key(tracker: &str) -> String {
+    Tracker::parse(tracker)
+        .map(|t| t.url)
+        .unwrap_or_else(|_| tracker.to_string())
+}
+
+/// `trackers` 中不在 `existing` 里的 tracker，保持原来的顺序并去重
+pub fn missing_trackers<'a, S: AsRef<str>>(
+    existing: impl IntoIterator<Item = &'a str>,
+    trackers: &'a [S],
+) -> Vec<&'a str> {
+    let mut seen: HashSet<String> = existing.into_iter().map(tracker_key).collect();
+    trackers
+        .iter()
+        .map(AsRef::as_ref)
+        .filter(|t| seen.insert(tracker_key(t)))
+        .collect()
+}
+
+/// tracker 的过滤和排序规则
+///
+/// 模式为 glob 通配符，不区分大小写，与主机名或者规范化后的完整地址匹配即可。
+/// 黑名单优先于白名单，白名单为空时不限制
+#[derive(Debug, Clone, Default)]
+pub struct TrackerPolicy {
+    pub allow: Vec<Pattern>,
+    pub deny: Vec<Pattern>,
+    /// 协议的排列顺序，没有列出的协议排在最后
+    pub order: Vec<TrackerScheme>,
+}
+
+/// 规范化一组 tracker 的结果
+#[derive(Debug, Default)]
+pub struct NormalizedTrackers {
+    pub trackers: Vec<Tracker>,
+    /// 无法解析或者不支持的地址
+    pub invalid: Vec<String>,
+    pub duplicates: usize,
+    /// 被黑名单或者白名单过滤掉的数量
+    pub blocked: usize,
+}
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: false,
+    require_literal_separator: false,
+    require_literal_leading_dot: false,
+};
+
+fn compile(patterns: &[String]) -> Result<Vec<Pattern>> {
+    patterns
+        .iter()
+        .map(|p| Pattern::new(p).map_err(TorrentError::PatternError))
+        .collect()
+}
+
+impl TrackerPolicy {
+    /// 由配置中的模式字符串创建规则，`order` 为空时使用 [`DEFAULT_ORDER`]
+    pub fn new(allow: &[String], deny: &[String], order: &[TrackerScheme]) -> Result<Self> {
+        Ok(Self {
+            allow: compile(allow)?,
+            deny: compile(deny)?,
+            order: if order.is_empty() {
+                DEFAULT_ORDER.to_vec()
+            } else {
+                order.to_vec()
+            },
+        })
+    }
+
+    fn matches(patterns: &[Pattern], tracker: &Tracker) -> bool {
+        patterns.iter().any(|p| {
+            p.matches_with(tracker.host(), MATCH_OPTIONS)
+                || p.matches_with(tracker.as_str(), MATCH_OPTIONS)
+        })
+    }
+
+    /// 是否保留该 tracker
+    pub fn permits(&self, tracker: &Tracker) -> bool {
+        !Self::matches(&self.deny, tracker)
+            && (self.allow.is_empty() || Self::matches(&self.allow, tracker))
+    }
+
+    fn rank(&self, scheme: TrackerScheme) -> usize {
+        self.order
+            .iter()
+            .position(|s| *s == scheme)
+            .unwrap_or(self.order.len())
+    }
+
+    /// 解析、去重、过滤并排序，同一协议内保持输入的顺序
+    pub fn apply<S: AsRef<str>>(&self, raw: impl IntoIterator<Item = S>) -> NormalizedTrackers {
+        let mut result = NormalizedTrackers::default();
+        let mut seen = HashSet::new();
+        for line in raw {
+            let tracker = match Tracker::parse(line.as_ref()) {
+                Ok(tracker) => tracker,
+                Err(_) => {
+                    result.invalid.push(line.as_ref().to_string());
+                    continue;
+                }
+            };
+            if !seen.insert(tracker.as_str().to_string()) {
+                result.duplicates += 1;
+            } else if !self.permits(&tracker) {
+                result.blocked += 1;
+            } else {
+                result.trackers.push(tracker);
+            }
+        }
+        result
+            .trackers
+            .sort_by_key(|tracker| self.rank(tracker.scheme()));
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(s: &str) -> String {
+        Tracker::parse(s).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_missing_trackers() {
+        let trackers = [
+            "UDP://A.example:1337/announce",
+            "http://b.example:80/announce",
+            "http://b.example/announce",
+            "not a tracker",
+        ];
+        assert_eq!(
+            missing_trackers(["udp://a.example:1337/announce"], &trackers),
+            vec!["http://b.example:80/announce", "not a tracker"]
+        );
+    }
+
+    #[test]
+    fn test_normalize_tracker() {
+        assert_eq!(
+            normalize("UDP://Tracker.Example.ORG:1337/Announce/"),
+            "udp://tracker.example.org:1337/announce"
+        );
+        assert_eq!(
+            normalize("udp://tracker.example.org:6969"),
+            "udp://tracker.example.org:6969/announce"
+        );
+        assert_eq!(
+            normalize("http://tracker.example.org:80/announce"),
+            "http://tracker.example.org/announce"
+        );
+        assert_eq!(
+            normalize("https://tracker.example.org:443/ANNOUNCE#frag"),
+            "https://tracker.example.org/announce"
+        );
+        assert_eq!(
+            normalize("wss://tracker.example.org:443/"),
+            "wss://tracker.example.org"
+        );
+        assert_eq!(
+            normalize("http://tracker.example.org:8080/a1b2c3/announce?passkey=XY"),
+            "http://tracker.example.org:8080/a1b2c3/announce?passkey=XY"
+        );
+
+        let tracker = Tracker::parse("https://tracker.example.org/announce").unwrap();
+        assert_eq!(tracker.scheme(), TrackerScheme::Https);
+        assert_eq!(tracker.port(), Some(443));
+
+        // udp 必须写明端口，不支持的协议和无效的地址都返回错误
+        assert!(Tracker::parse("udp://tracker.example.org/announce").is_err());
+        assert!(Tracker::parse("ftp://tracker.example.org/announce").is_err());
+        assert!(Tracker::parse("not a url").is_err());
+    }
+
+    #[test]
+    fn test_policy_dedup_filter_and_order() {
+        let raw = [
+            "http://a.example:80/announce",
+            "udp://b.example:1337/announce",
+            "wss://c.example/announce",
+            "http://a.example/Announce/",
+            "udp://bad.example:1337/announce",
+            "https://d.example/announce",
+            "udp://e.example:6969/announce",
+            "garbage",
+        ];
+
+        let policy =
+            TrackerPolicy::new(&[], &["*.bad.example".into(), "bad.*".into()], &[]).unwrap();
+        let result = policy.apply(raw);
+        let urls: Vec<&str> = result.trackers.iter().map(|t| t.as_str()).collect();
+        assert_eq!(
+            urls,
+            vec![
+                "udp://b.example:1337/announce",
+                "udp://e.example:6969/announce",
+                "https://d.example/announce",
+                "http://a.example/announce",
+                "wss://c.example/announce",
+            ]
+        );
+        assert_eq!(result.duplicates, 1);
+        assert_eq!(result.blocked, 1);
+        assert_eq!(result.invalid, vec!["garbage"]);
+
+        // 白名单可以匹配完整地址，没有列出的协议排在最后
+        let policy = TrackerPolicy::new(
+            &["udp://*".into(), "WSS://C.EXAMPLE*".into()],
+            &["e.example".into()],
+            &[TrackerScheme::Wss],
+        )
+        .unwrap();
+        let urls: Vec<String> = policy
+            .apply(raw)
+            .trackers
+            .iter()
+            .map(|t| t.to_string())
+            .collect();
+        assert_eq!(
+            urls,
+            vec![
+                "wss://c.example/announce",
+                "udp://b.example:1337/announce",
+                "udp://bad.example:1337/announce",
+            ]
+        );
+
+        assert!(TrackerPolicy::new(&["[".into()], &[], &[]).is_err());
+    }
+}
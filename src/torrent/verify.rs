@@ -0,0 +1,477 @@
+//! 根据种子中的 piece 哈希校验本地文件是否完整
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    Result, TorrentError,
+    bencode::Value,
+    metainfo::{decode_torrent, format_size},
+    piece::{BLOCK_SIZE, Chunk, PieceSpec, hash_pieces},
+};
+
+/// 种子中按顺序排列的一个文件，包括填充文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LayoutFile {
+    /// 相对于种子根目录的路径，单文件种子为空
+    components: Vec<String>,
+    length: u64,
+    pad: bool,
+    /// v2 的 `pieces root`
+    pieces_root: Option<[u8; 32]>,
+}
+
+/// 需要校验的 piece 以及它涉及的文件
+struct Piece {
+    spec: PieceSpec,
+    files: Vec<usize>,
+    expected: Vec<u8>,
+}
+
+/// 单个文件的校验结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileStatus {
+    Ok,
+    Missing,
+    /// 本地文件比种子中的短
+    Truncated {
+        actual: u64,
+    },
+    /// 有 piece 的哈希不匹配
+    Corrupt {
+        bad_pieces: usize,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileReport {
+    pub path: String,
+    pub length: u64,
+    pub status: FileStatus,
+}
+
+/// 整个种子的校验结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// 不包括填充文件
+    pub files: Vec<FileReport>,
+    pub pieces: usize,
+    pub good_pieces: usize,
+}
+
+impl VerifyReport {
+    /// 完成的百分比，按照通过校验的 piece 计算
+    pub fn completion(&self) -> f64 {
+        if self.pieces == 0 {
+            100.0
+        } else {
+            self.good_pieces as f64 * 100.0 / self.pieces as f64
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.good_pieces == self.pieces
+    }
+}
+
+fn invalid(reason: impl Into<String>) -> TorrentError {
+    TorrentError::InvalidMetainfo(reason.into())
+}
+
+/// 路径中的一段，拒绝 `..` 等可能指向目录之外的名称
+fn component(value: &Value) -> Result<String> {
+    let name = value
+        .as_str()
+        .ok_or_else(|| invalid("path component is not a UTF-8 string"))?;
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+        return Err(invalid(format!("unsafe path component {:?}", name)));
+    }
+    Ok(name.to_string())
+}
+
+fn length(value: &Value) -> Result<u64> {
+    value
+        .get("length")
+        .and_then(Value::as_int)
+        .and_then(|l| u64::try_from(l).ok())
+        .ok_or_else(|| invalid("missing or negative file length"))
+}
+
+/// v1 的文件列表，单文件种子只有一个路径为空的文件
+fn v1_layout(info: &Value) -> Result<Vec<LayoutFile>> {
+    let Some(files) = info.get("files").and_then(Value::as_list) else {
+        return Ok(vec![LayoutFile {
+            components: Vec::new(),
+            length: length(info)?,
+            pad: false,
+            pieces_root: None,
+        }]);
+    };
+    files
+        .iter()
+        .map(|file| {
+            let components = file
+                .get("path")
+                .and_then(Value::as_list)
+                .ok_or_else(|| invalid("file without path"))?
+                .iter()
+                .map(component)
+                .collect::<Result<Vec<_>>>()?;
+            Ok(LayoutFile {
+                components,
+                length: length(file)?,
+                pad: file
+                    .get("attr")
+                    .and_then(Value::as_str)
+                    .is_some_and(|a| a.contains('p')),
+                pieces_root: None,
+            })
+        })
+        .collect()
+}
+
+/// v2 文件树中的文件，按照文件树的顺序
+fn v2_layout(tree: &Value, prefix: &mut Vec<String>, files: &mut Vec<LayoutFile>) -> Result<()> {
+    let dict = tree
+        .as_dict()
+        .ok_or_else(|| invalid("file tree node is not a dictionary"))?;
+    for (key, node) in dict {
+        if key.is_empty() {
+            let pieces_root = match node.get("pieces root").and_then(Value::as_bytes) {
+                Some(root) => Some(
+                    root.try_into()
+                        .map_err(|_| invalid("pieces root is not 32 bytes"))?,
+                ),
+                None => None,
+            };
+            files.push(LayoutFile {
+                components: prefix.clone(),
+                length: length(node)?,
+                pad: false,
+                pieces_root,
+            });
+            continue;
+        }
+        prefix.push(component(&Value::Bytes(key.clone()))?);
+        v2_layout(node, prefix, files)?;
+        prefix.pop();
+    }
+    Ok(())
+}
+
+fn local_path(root: &Path, file: &LayoutFile) -> PathBuf {
+    file.components
+        .iter()
+        .fold(root.to_path_buf(), |p, c| p.join(c))
+}
+
+/// v1 的 piece 跨越文件，填充文件只会出现在 piece 的末尾，按零字节计算
+fn v1_pieces(
+    root: &Path,
+    files: &[LayoutFile],
+    piece_length: u64,
+    hashes: &[u8],
+) -> Result<Vec<Piece>> {
+    let mut pieces = Vec::new();
+    let mut current = PieceSpec {
+        v1: true,
+        ..PieceSpec::default()
+    };
+    let mut touched = Vec::new();
+    let mut filled = 0;
+    for (idx, file) in files.iter().enumerate() {
+        let mut offset = 0;
+        while offset < file.length {
+            let len = (file.length - offset).min(piece_length - filled);
+            if file.pad {
+                current.pad += len;
+            } else {
+                current.chunks.push(Chunk {
+                    path: local_path(root, file),
+                    offset,
+                    len,
+                });
+                touched.push(idx);
+            }
+            offset += len;
+            filled += len;
+            if filled == piece_length {
+                pieces.push((std::mem::take(&mut current), std::mem::take(&mut touched)));
+                current.v1 = true;
+                filled = 0;
+            }
+        }
+    }
+    if filled > 0 {
+        pieces.push((current, touched));
+    }
+
+    if hashes.len() != pieces.len() * 20 {
+        return Err(invalid(format!(
+            "expected {} piece hashes, found {}",
+            pieces.len(),
+            hashes.len() / 20
+        )));
+    }
+    Ok(pieces
+        .into_iter()
+        .zip(hashes.chunks(20))
+        .map(|((spec, files), expected)| Piece {
+            spec,
+            files,
+            expected: expected.to_vec(),
+        })
+        .collect())
+}
+
+/// v2 的 piece 只属于一个文件，大于一个 piece 的文件与 piece layers 比较，其它文件与 pieces root 比较
+fn v2_pieces(
+    root: &Path,
+    files: &[LayoutFile],
+    piece_length: u64,
+    layers: &BTreeMap<Vec<u8>, Value>,
+) -> Result<Vec<Piece>> {
+    let mut pieces = Vec::new();
+    for (idx, file) in files.iter().enumerate() {
+        if file.length == 0 {
+            continue;
+        }
+        let pieces_root = file
+            .pieces_root
+            .ok_or_else(|| invalid("file without pieces root"))?;
+        let count = file.length.div_ceil(piece_length);
+        let expected: Vec<Vec<u8>> = if count > 1 {
+            let layer = layers
+                .get(pieces_root.as_slice())
+                .and_then(Value::as_bytes)
+                .filter(|layer| layer.len() as u64 == count * 32)
+                .ok_or_else(|| invalid("missing or incomplete piece layer"))?;
+            layer.chunks(32).map(<[u8]>::to_vec).collect()
+        } else {
+            vec![pieces_root.to_vec()]
+        };
+        let v2_leaves = if count > 1 {
+            piece_length / BLOCK_SIZE
+        } else {
+            file.length.div_ceil(BLOCK_SIZE).next_power_of_two()
+        };
+        for (piece, expected) in expected.into_iter().enumerate() {
+            let offset = piece as u64 * piece_length;
+            pieces.push(Piece {
+                spec: PieceSpec {
+                    chunks: vec![Chunk {
+                        path: local_path(root, file),
+                        offset,
+                        len: (file.length - offset).min(piece_length),
+                    }],
+                    pad: 0,
+                    v1: false,
+                    v2_leaves: Some(v2_leaves),
+                },
+                files: vec![idx],
+                expected,
+            });
+        }
+    }
+    Ok(pieces)
+}
+
+/// 重新计算 `dir` 中数据的 piece 哈希并与种子比较
+///
+/// `dir` 可以是包含种子内容的下载目录，也可以是内容本身（多文件种子的根目录或者单个文件）。
+/// 有 v1 信息时按 v1 校验，纯 v2 种子按 BEP 52 的 merkle 树校验
+pub async fn verify_torrent(
+    data: &[u8],
+    dir: impl AsRef<Path>,
+    jobs: usize,
+) -> Result<VerifyReport> {
+    let (torrent, _) = decode_torrent(data)?;
+    let info = torrent.get("info").expect("checked by decode_torrent");
+    let name = info
+        .get("name")
+        .map(component)
+        .transpose()?
+        .ok_or_else(|| invalid("missing name"))?;
+    let piece_length = info
+        .get("piece length")
+        .and_then(Value::as_int)
+        .and_then(|l| u64::try_from(l).ok())
+        .filter(|&l| l > 0)
+        .ok_or_else(|| invalid("missing piece length"))?;
+
+    let dir = dir.as_ref();
+    let root = if dir.join(&name).exists() {
+        dir.join(&name)
+    } else {
+        dir.to_path_buf()
+    };
+
+    let (files, pieces) = match info.get("pieces").and_then(Value::as_bytes) {
+        Some(hashes) => {
+            let files = v1_layout(info)?;
+            let pieces = v1_pieces(&root, &files, piece_length, hashes)?;
+            (files, pieces)
+        }
+        None => {
+            let tree = info
+                .get("file tree")
+                .ok_or_else(|| invalid("missing pieces and file tree"))?;
+            // merkle 树要求每个 piece 包含 2 的幂个 16 KiB 的块
+            if !piece_length.is_power_of_two() || piece_length < BLOCK_SIZE {
+                return Err(invalid(format!("invalid v2 piece length {}", piece_length)));
+            }
+            let mut files = Vec::new();
+            v2_layout(tree, &mut Vec::new(), &mut files)?;
+            // 单文件的 v2 种子文件树中只有以 name 命名的文件，与 v1 一样直接使用根路径
+            if let [file] = files.as_mut_slice()
+                && info.get("files").is_none()
+                && file.components == [name.as_str()]
+            {
+                file.components.clear();
+            }
+            let empty = BTreeMap::new();
+            let layers = torrent
+                .get("piece layers")
+                .and_then(Value::as_dict)
+                .unwrap_or(&empty);
+            let pieces = v2_pieces(&root, &files, piece_length, layers)?;
+            (files, pieces)
+        }
+    };
+
+    let specs = pieces.iter().map(|p| p.spec.clone()).collect();
+    let results = hash_pieces(specs, jobs).await;
+
+    let mut bad_pieces = vec![0; files.len()];
+    let mut good_pieces = 0;
+    for (piece, result) in pieces.iter().zip(results) {
+        let actual = result.ok().and_then(|h| match h.v1 {
+            Some(v1) => Some(v1.to_vec()),
+            None => h.v2.map(|v2| v2.to_vec()),
+        });
+        if actual.as_ref() == Some(&piece.expected) {
+            good_pieces += 1;
+        } else {
+            for &idx in &piece.files {
+                bad_pieces[idx] += 1;
+            }
+        }
+    }
+
+    let reports = files
+        .iter()
+        .enumerate()
+        .filter(|(_, file)| !file.pad)
+        .map(|(idx, file)| {
+            let path = local_path(&root, file);
+            let status = match std::fs::metadata(&path) {
+                Err(_) => FileStatus::Missing,
+                Ok(meta) if meta.len() < file.length => {
+                    FileStatus::Truncated { actual: meta.len() }
+                }
+                Ok(_) if bad_pieces[idx] > 0 => FileStatus::Corrupt {
+                    bad_pieces: bad_pieces[idx],
+                },
+                Ok(_) => FileStatus::Ok,
+            };
+            FileReport {
+                path: if file.components.is_empty() {
+                    name.clone()
+                } else {
+                    file.components.join("/")
+                },
+                length: file.length,
+                status,
+            }
+        })
+        .collect();
+    Ok(VerifyReport {
+        files: reports,
+        pieces: pieces.len(),
+        good_pieces,
+    })
+}
+
+/// 校验 `dir` 中的数据并打印每个文件的结果，数据不完整时返回错误
+pub async fn verify_torrent_file(
+    file: impl AsRef<Path>,
+    dir: impl AsRef<Path>,
+    jobs: usize,
+) -> Result<()> {
+    let data = tokio::fs::read(file).await.map_err(TorrentError::IoError)?;
+    let report = verify_torrent(&data, dir, jobs).await?;
+    for file in &report.files {
+        match &file.status {
+            FileStatus::Ok => println!("[ok]        {}", file.path),
+            FileStatus::Missing => println!("[missing]   {}", file.path),
+            FileStatus::Truncated { actual } => println!(
+                "[truncated] {} ({} of {})",
+                file.path,
+                format_size(*actual),
+                format_size(file.length)
+            ),
+            FileStatus::Corrupt { bad_pieces } => {
+                println!("[corrupt]   {} ({} bad pieces)", file.path, bad_pieces)
+            }
+        }
+    }
+    println!(
+        "{} of {} pieces verified, {:.2}% complete",
+        report.good_pieces,
+        report.pieces,
+        report.completion()
+    );
+    if report.is_complete() {
+        Ok(())
+    } else {
+        Err(TorrentError::VerifyFailed {
+            bad: report.pieces - report.good_pieces,
+            total: report.pieces,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reject_unsafe_components() {
+        assert_eq!(component(&"en.srt".into()).unwrap(), "en.srt");
+        for name in ["..", ".", "", "a/b", "a\\b"] {
+            assert!(component(&name.into()).is_err(), "{}", name);
+        }
+        let info = crate::torrent::bencode::decode(
+            b"d5:filesld6:lengthi1e4:pathl2:..6:passwdeee4:name1:a12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaae",
+        )
+        .unwrap();
+        assert!(matches!(
+            v1_layout(&info),
+            Err(TorrentError::InvalidMetainfo(_))
+        ));
+    }
+
+    #[test]
+    fn test_v1_pieces_with_padding() {
+        let file = |name: &str, length: u64, pad: bool| LayoutFile {
+            components: vec![name.to_string()],
+            length,
+            pad,
+            pieces_root: None,
+        };
+        let files = [
+            file("a", 20 * 1024, false),
+            file(".pad", 12 * 1024, true),
+            file("b", 8 * 1024, false),
+        ];
+        let pieces = v1_pieces(Path::new("root"), &files, 32 * 1024, &[0; 40]).unwrap();
+        assert_eq!(pieces.len(), 2);
+        assert_eq!(pieces[0].spec.pad, 12 * 1024);
+        assert_eq!(pieces[0].files, vec![0]);
+        assert_eq!(pieces[1].files, vec![2]);
+        assert_eq!(pieces[1].spec.chunks[0].path, Path::new("root").join("b"));
+        assert!(v1_pieces(Path::new("root"), &files, 32 * 1024, &[0; 20]).is_err());
+    }
+}
@@ -0,0 +1,89 @@
+use blowup::torrent::{
+    TorrentError,
+    client::{PushReport, TrackerClient, aria2::Aria2Client},
+};
+use serde_json::json;
+use wiremock::{
+    Mock, MockServer, ResponseTemplate,
+    matchers::{body_partial_json, method, path},
+};
+
+fn trackers() -> Vec<String> {
+    vec![
+        "udp://a.example:1337/announce".to_string(),
+        "http://b.example/announce".to_string(),
+    ]
+}
+
+#[tokio::test]
+async fn test_push_changes_global_bt_tracker() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/jsonrpc"))
+        .and(body_partial_json(json!({
+            "jsonrpc": "2.0",
+            "method": "aria2.changeGlobalOption",
+            "params": [
+                "token:secret",
+                { "bt-tracker": "udp://a.example:1337/announce,http://b.example/announce" }
+            ]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "jsonrpc": "2.0",
+            "id": "blowup",
+            "result": "OK"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let client = Aria2Client::new(
+        format!("{}/jsonrpc", server.uri()),
+        Some("secret".to_string()),
+    )
+    .unwrap();
+    assert_eq!(
+        client.push(&trackers(), true).await.unwrap(),
+        PushReport::default()
+    );
+}
+
+#[tokio::test]
+async fn test_push_reports_rpc_errors() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/jsonrpc"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "jsonrpc": "2.0",
+            "id": "blowup",
+            "error": { "code": 1, "message": "Unauthorized" }
+        })))
+        .mount(&server)
+        .await;
+    let client = Aria2Client::new(
+        format!("{}/jsonrpc", server.uri()),
+        Some("wrong".to_string()),
+    )
+    .unwrap();
+    assert!(matches!(
+        client.push(&trackers(), false).await,
+        Err(TorrentError::LoginFailed(_))
+    ));
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "jsonrpc": "2.0",
+            "id": "blowup",
+            "error": { "code": 1, "message": "No such option: bt-tracker" }
+        })))
+        .mount(&server)
+        .await;
+    let client = Aria2Client::new(server.uri(), None).unwrap();
+    match client.push(&trackers(), false).await {
+        Err(TorrentError::ClientError { message, .. }) => {
+            assert_eq!(message, "No such option: bt-tracker (code 1)")
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
@@ -0,0 +1,49 @@
+use std::fs;
+
+use blowup::sub::{OverlapFixMode, update_srt_time_batch};
+use tempfile::tempdir;
+
+const SRT: &str = "1
+00:00:01,000 --> 00:00:02,000
+first
+
+2
+00:00:03,000 --> 00:00:04,000
+second
+";
+
+#[tokio::test]
+async fn test_incr_directory_keeps_going_after_failures() {
+    let dir = tempdir().expect("failed to create temp dir");
+    let root = dir.path();
+    fs::create_dir_all(root.join("s01")).unwrap();
+    fs::create_dir_all(root.join("s02")).unwrap();
+    fs::write(root.join("s01/e01.srt"), SRT).unwrap();
+    fs::write(root.join("s02/e02.srt"), SRT).unwrap();
+    fs::write(root.join("s02/broken.srt"), "not a subtitle").unwrap();
+    fs::write(root.join("s02/notes.txt"), "ignored").unwrap();
+
+    let result =
+        update_srt_time_batch(root.to_str().unwrap(), 500, OverlapFixMode::Before, 2).await;
+
+    // 损坏的文件使整个批次失败，但不影响其它文件的处理
+    assert!(result.is_err());
+    let e01 = fs::read_to_string(root.join("s01/e01_mod.srt")).unwrap();
+    assert!(e01.contains("00:00:01,500 --> 00:00:02,500"));
+    assert!(root.join("s02/e02_mod.srt").exists());
+    assert!(!root.join("s02/broken_mod.srt").exists());
+
+    // 再次运行时跳过已经生成的 _mod 文件
+    fs::remove_file(root.join("s02/broken.srt")).unwrap();
+    update_srt_time_batch(
+        &format!("{}/*/*.srt", root.display()),
+        -500,
+        OverlapFixMode::Before,
+        2,
+    )
+    .await
+    .unwrap();
+    assert!(!root.join("s01/e01_mod_mod.srt").exists());
+    let e01 = fs::read_to_string(root.join("s01/e01_mod.srt")).unwrap();
+    assert!(e01.contains("00:00:00,500 --> 00:00:01,500"));
+}
@@ -0,0 +1,112 @@
+use std::{
+    fs,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use blowup::{
+    config::Config,
+    daemon::{Daemon, state::STATE_FILE},
+};
+use tempfile::tempdir;
+
+const LIST: &str = "udp://a.example:1337/announce\nhttp://b.example/announce\n";
+
+#[tokio::test]
+async fn test_run_pending_jobs_and_record_state() {
+    let dir = tempdir().unwrap();
+    let list = dir.path().join("trackers.txt");
+    fs::write(&list, LIST).unwrap();
+    let library = dir.path().join("Movies");
+    fs::create_dir_all(&library).unwrap();
+    fs::write(library.join("Blow-Up (1966).mkv"), "").unwrap();
+    fs::write(library.join("blow.up.1966.en.srt"), "").unwrap();
+
+    let config: Config = toml::from_str(&format!(
+        r#"
+        [[tracker.sources]]
+        file = {:?}
+
+        [daemon.tracker]
+        interval = "6h"
+
+        [daemon.push]
+        interval = "6h"
+
+        [daemon.library]
+        interval = "1d"
+        dirs = [{:?}]
+        apply = true
+        "#,
+        list, library
+    ))
+    .unwrap();
+    let data_dir = dir.path().join("data");
+    let cache_file = dir.path().join("cache").join("trackers.json");
+    let mut daemon =
+        Daemon::with_paths(config.clone(), data_dir.clone(), Some(cache_file)).unwrap();
+
+    assert_eq!(daemon.run_pending().await, 3);
+    assert_eq!(fs::read_to_string(daemon.tracker_file()).unwrap(), LIST);
+    assert!(library.join("Blow-Up (1966).en.srt").exists());
+
+    let jobs = &daemon.state().jobs;
+    assert_eq!(jobs["tracker"].last_error, None);
+    assert!(jobs["tracker"].last_success.is_some());
+    assert_eq!(jobs["library"].last_error, None);
+    // 没有配置客户端，推送失败只记录错误
+    assert!(jobs["push"].last_error.is_some());
+    assert_eq!(jobs["push"].last_success, None);
+
+    // 重启后从状态文件恢复，所有任务都还没到时间
+    assert!(data_dir.join(STATE_FILE).is_file());
+    let mut daemon = Daemon::with_paths(config, data_dir, None).unwrap();
+    assert_eq!(daemon.run_pending().await, 0);
+    let (_, next) = daemon.next_run().unwrap();
+    assert!(next > chrono::Local::now() + chrono::Duration::hours(5));
+}
+
+#[test]
+fn test_daemon_logs_to_file_and_stops_on_sigterm() {
+    let dir = tempdir().unwrap();
+    let library = dir.path().join("Movies");
+    fs::create_dir_all(&library).unwrap();
+    let data_dir = dir.path().join("data");
+    let config_file = dir.path().join("config.toml");
+    fs::write(
+        &config_file,
+        format!(
+            "[daemon]\ndata_dir = {:?}\n\n[daemon.library]\ninterval = \"1h\"\ndirs = [{:?}]\n",
+            data_dir, library
+        ),
+    )
+    .unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_blowup"))
+        .arg("daemon")
+        .env("BLOWUP_CONFIG", &config_file)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let log_file = data_dir.join("daemon.log");
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !fs::read_to_string(&log_file).is_ok_and(|log| log.contains("next run")) {
+        assert!(Instant::now() < deadline, "daemon did not start");
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    // SAFETY: 只是向测试启动的子进程发送信号
+    unsafe {
+        libc::kill(child.id() as libc::pid_t, libc::SIGTERM);
+    }
+    assert!(child.wait().unwrap().success());
+
+    let log = fs::read_to_string(&log_file).unwrap();
+    assert!(log.contains("library: finished"), "{}", log);
+    assert!(log.contains("received SIGTERM"), "{}", log);
+    assert!(log.contains("daemon stopped"), "{}", log);
+    assert!(data_dir.join(STATE_FILE).is_file());
+}
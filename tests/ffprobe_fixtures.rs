@@ -0,0 +1,80 @@
+use std::{fs, time::Duration};
+
+use blowup::{
+    ffmpeg::probe::{CodecType, ProbeOutput},
+    sub::SubtitleStreamInfo,
+};
+
+fn load_fixture(name: &str) -> ProbeOutput {
+    let path = format!("./tests/fixtures/ffprobe/{}.json", name);
+    let json = fs::read_to_string(&path).expect("failed to read the ffprobe fixture");
+    serde_json::from_str(&json).expect("failed to deserialize the ffprobe fixture")
+}
+
+fn subtitle_streams(output: &ProbeOutput) -> Vec<SubtitleStreamInfo> {
+    output
+        .streams
+        .iter()
+        .filter(|s| s.codec_type == CodecType::Subtitle)
+        .map(SubtitleStreamInfo::from)
+        .collect()
+}
+
+#[test]
+fn test_mkv_fixture() {
+    let output = load_fixture("mkv");
+    assert_eq!(output.streams.len(), 5);
+    assert_eq!(output.chapters.len(), 2);
+
+    let video = &output.streams[0];
+    assert_eq!(video.hdr_format(), Some("Dolby Vision"));
+    // MKV 的视频流只在标签中记录时长
+    assert_eq!(video.duration(), Some(Duration::from_millis(7_921_914)));
+
+    // MKV 字幕流没有 duration_ts 和 duration 字段
+    let subs = subtitle_streams(&output);
+    assert_eq!(subs.len(), 2);
+    assert_eq!(subs[0].codec_name, "subrip");
+    assert_eq!(subs[0].duration, Some(7_619_968));
+    assert_eq!(subs[0].language.as_deref(), Some("eng"));
+    assert_eq!(subs[1].codec_name, "hdmv_pgs_subtitle");
+    assert_eq!(subs[1].duration, None);
+    assert_eq!(subs[1].title.as_deref(), Some("简体中文"));
+    assert_eq!(output.streams[3].disposition.flags(), vec!["forced"]);
+}
+
+#[test]
+fn test_mp4_fixture() {
+    let output = load_fixture("mp4");
+    let subs = subtitle_streams(&output);
+    assert_eq!(subs.len(), 1);
+    assert_eq!(subs[0].codec_name, "mov_text");
+    // 时间基为 1/1000，duration_ts 恰好是毫秒
+    assert_eq!(subs[0].duration, Some(6_595_229));
+
+    // 视频流的时间基是 1/24000，duration_ts 不能直接当作毫秒
+    let video = &output.streams[0];
+    assert_eq!(video.duration_ts, Some(158_592_434));
+    assert_eq!(video.duration().map(|d| d.as_millis()), Some(6_608_018));
+    assert_eq!(
+        output.format.as_ref().unwrap().format_long_name.as_deref(),
+        Some("QuickTime / MOV")
+    );
+}
+
+#[test]
+fn test_ts_fixture() {
+    let output = load_fixture("ts");
+    let subs = subtitle_streams(&output);
+    // DVB 字幕流既没有 start_time 也没有任何时长信息
+    assert_eq!(subs.len(), 1);
+    assert_eq!(subs[0].codec_name, "dvb_subtitle");
+    assert_eq!(subs[0].duration, None);
+    assert_eq!(subs[0].language.as_deref(), Some("deu"));
+    assert_eq!(output.streams[2].start_time(), None);
+
+    // 时间基为 1/90000 的 270000 个单位是 3 秒
+    assert_eq!(output.streams[0].duration(), Some(Duration::from_secs(3)));
+    assert_eq!(output.streams[3].codec_type, CodecType::Data);
+    assert_eq!(output.streams[3].codec_name, None);
+}
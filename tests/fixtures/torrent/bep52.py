@@ -0,0 +1,94 @@
+#!/usr/bin/env python3
+"""Reference values for tests/torrent_create.rs, computed with hashlib only.
+
+Builds the same sample files as the test and prints the BEP 52 pieces roots,
+piece layers and the BEP 47 pad-file layout of a hybrid torrent as JSON:
+
+    python3 tests/fixtures/torrent/bep52.py > tests/fixtures/torrent/bep52.json
+"""
+import hashlib
+import json
+
+BLOCK = 16 * 1024
+PIECE_LENGTH = 32 * 1024
+
+FILES = [
+    ("Blow-Up.mkv", bytes(i % 251 for i in range(100_000))),
+    ("Subs/en.srt", b"1\n00:00:01,000 --> 00:00:02,000\nHi\n"),
+    ("c.bin", bytes(i * 7 % 256 for i in range(3 * BLOCK))),
+]
+
+
+def sha256(data):
+    return hashlib.sha256(data).digest()
+
+
+def merkle_root(leaves, width):
+    """Root of a tree with `width` leaves, missing leaves are zero hashes."""
+    layer = leaves + [bytes(32)] * (width - len(leaves))
+    while len(layer) > 1:
+        layer = [sha256(layer[i] + layer[i + 1]) for i in range(0, len(layer), 2)]
+    return layer[0]
+
+
+def v2_file(data):
+    blocks = [sha256(data[i:i + BLOCK]) for i in range(0, len(data), BLOCK)]
+    per_piece = PIECE_LENGTH // BLOCK
+    if len(data) <= PIECE_LENGTH:
+        # A file of at most one piece: the tree only covers its own blocks
+        width = 1
+        while width < len(blocks):
+            width *= 2
+        return merkle_root(blocks, width), None
+    layer = [
+        merkle_root(blocks[i:i + per_piece], per_piece)
+        for i in range(0, len(blocks), per_piece)
+    ]
+    # The piece layer is padded with roots of all-zero pieces
+    zero_piece = merkle_root([], per_piece)
+    width = 1
+    while width < len(layer):
+        width *= 2
+    root = layer + [zero_piece] * (width - len(layer))
+    while len(root) > 1:
+        root = [sha256(root[i] + root[i + 1]) for i in range(0, len(root), 2)]
+    return root[0], b"".join(layer)
+
+
+def hybrid_v1():
+    """v1 files list with pad files and the SHA-1 piece hashes over the padded stream."""
+    files, stream = [], b""
+    for idx, (path, data) in enumerate(FILES):
+        files.append({"path": path, "length": len(data)})
+        stream += data
+        pad = -len(data) % PIECE_LENGTH
+        if pad and idx < len(FILES) - 1:
+            files.append({"path": ".pad/%d" % pad, "length": pad, "attr": "p"})
+            stream += bytes(pad)
+    pieces = [
+        hashlib.sha1(stream[i:i + PIECE_LENGTH]).hexdigest()
+        for i in range(0, len(stream), PIECE_LENGTH)
+    ]
+    return files, pieces
+
+
+def main():
+    v2 = []
+    for path, data in FILES:
+        root, layer = v2_file(data)
+        v2.append({
+            "path": path,
+            "pieces_root": root.hex(),
+            "piece_layer": layer.hex() if layer else None,
+        })
+    files, pieces = hybrid_v1()
+    print(json.dumps({
+        "piece_length": PIECE_LENGTH,
+        "v2_files": v2,
+        "hybrid_files": files,
+        "hybrid_pieces": pieces,
+    }, indent=2))
+
+
+if __name__ == "__main__":
+    main()
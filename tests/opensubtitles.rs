@@ -0,0 +1,153 @@
+use std::fs;
+
+use blowup::sub::{
+    FetchOptions, OpenSubtitlesClient, ProviderError, SubtitleProvider, SubtitleQuery,
+    fetch_subtitle_with, format_movie_hash, movie_hash,
+};
+use serde_json::json;
+use tempfile::tempdir;
+use wiremock::{
+    Mock, MockServer, ResponseTemplate,
+    matchers::{body_json, header, method, path, query_param},
+};
+
+const API_KEY: &str = "test-key";
+const SRT: &str = "1\n00:00:01,000 --> 00:00:02,000\nhello\n";
+
+fn search_body() -> serde_json::Value {
+    json!({
+        "total_count": 2,
+        "data": [
+            {
+                "id": "1",
+                "type": "subtitle",
+                "attributes": {
+                    "language": "en",
+                    "release": "Blow-Up.1966.1080p.BluRay",
+                    "download_count": 1200,
+                    "moviehash_match": false,
+                    "files": [{ "file_id": 101, "file_name": "blowup.en.srt" }]
+                }
+            },
+            {
+                "id": "2",
+                "type": "subtitle",
+                "attributes": {
+                    "language": "zh-cn",
+                    "release": "Blow-Up.1966.720p",
+                    "download_count": 30,
+                    "moviehash_match": true,
+                    "files": [{ "file_id": 202, "file_name": "blowup.chs.srt" }]
+                }
+            }
+        ]
+    })
+}
+
+#[tokio::test]
+async fn test_fetch_downloads_best_match_next_to_video() {
+    let server = MockServer::start().await;
+    let dir = tempdir().unwrap();
+    let video = dir.path().join("Blow-Up (1966).mkv");
+    fs::write(&video, vec![7u8; 3 * 64 * 1024]).unwrap();
+    let hash = format_movie_hash(movie_hash(&video).unwrap());
+
+    Mock::given(method("GET"))
+        .and(path("/subtitles"))
+        .and(header("Api-Key", API_KEY))
+        .and(query_param("moviehash", hash.as_str()))
+        .and(query_param("languages", "en,zh-cn"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(search_body()))
+        .expect(3)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/download"))
+        .and(header("Api-Key", API_KEY))
+        .and(body_json(json!({ "file_id": 202 })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "link": format!("{}/files/202", server.uri()),
+            "file_name": "blowup.chs.srt",
+            "remaining": 19
+        })))
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/files/202"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(SRT))
+        .mount(&server)
+        .await;
+
+    let client = OpenSubtitlesClient::new(API_KEY).with_base_url(server.uri());
+    let options = FetchOptions {
+        languages: vec!["zh-cn".to_string(), "en".to_string()],
+        ..Default::default()
+    };
+    let saved = fetch_subtitle_with(&client, &video, &options)
+        .await
+        .unwrap()
+        .unwrap();
+
+    // 哈希匹配的字幕优先于下载次数更多的字幕
+    assert_eq!(saved, dir.path().join("Blow-Up (1966).zh-Hans.srt"));
+    assert_eq!(fs::read_to_string(&saved).unwrap(), SRT);
+
+    // 已有的字幕不会被覆盖，也不会浪费下载次数
+    fs::write(&saved, "edited").unwrap();
+    assert!(matches!(
+        fetch_subtitle_with(&client, &video, &options).await,
+        Err(ProviderError::SubtitleExists(path)) if path == saved
+    ));
+    assert_eq!(fs::read_to_string(&saved).unwrap(), "edited");
+
+    let options = FetchOptions {
+        force: true,
+        ..options
+    };
+    fetch_subtitle_with(&client, &video, &options)
+        .await
+        .unwrap();
+    assert_eq!(fs::read_to_string(&saved).unwrap(), SRT);
+}
+
+#[tokio::test]
+async fn test_search_by_imdb_and_api_errors() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/subtitles"))
+        .and(query_param("imdb_id", "60176"))
+        .and(query_param("query", "blow-up"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(search_body()))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/download"))
+        .respond_with(
+            ResponseTemplate::new(406).set_body_json(
+                json!({ "message": "You have downloaded your allowed 5 subtitles" }),
+            ),
+        )
+        .mount(&server)
+        .await;
+
+    let client = OpenSubtitlesClient::new(API_KEY).with_base_url(format!("{}/", server.uri()));
+    let query = SubtitleQuery {
+        imdb_id: Some(60176),
+        query: Some("Blow-Up".to_string()),
+        ..Default::default()
+    };
+    let candidates = client.search(&query).await.unwrap();
+    assert_eq!(candidates.len(), 2);
+    assert_eq!(candidates[0].file_id, 101);
+    assert_eq!(candidates[0].download_count, 1200);
+    assert!(candidates[1].hash_match);
+
+    match client.download(&candidates[0]).await {
+        Err(ProviderError::ApiError { status, message }) => {
+            assert_eq!(status, 406);
+            assert!(message.contains("allowed 5 subtitles"));
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
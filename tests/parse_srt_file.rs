@@ -11,7 +11,7 @@ fn main() {
 
 #[test]
 fn test_parse_srt() {
-    let mut f = File::open("./test.srt").expect("failed to open the test srt file");
+    let mut f = File::open("./parasite.srt").expect("failed to open the test srt file");
     let mut buf = String::new();
     f.read_to_string(&mut buf)
         .expect("failed to read the original file");
@@ -20,6 +20,7 @@ fn test_parse_srt() {
     let srt = SrtFile::read(f).unwrap();
     let mut nf = OpenOptions::new()
         .create(true)
+        .truncate(true)
         .read(true)
         .write(true)
         .open("./test-copy.srt")
@@ -0,0 +1,135 @@
+use blowup::torrent::{
+    TorrentError,
+    client::{PushReport, TrackerClient, qbittorrent::QbittorrentClient},
+};
+use serde_json::json;
+use wiremock::{
+    Mock, MockServer, ResponseTemplate,
+    matchers::{body_string, body_string_contains, header, method, path, query_param},
+};
+
+fn trackers() -> Vec<String> {
+    vec![
+        "udp://a.example:1337/announce".to_string(),
+        "http://b.example/announce".to_string(),
+    ]
+}
+
+#[tokio::test]
+async fn test_push_sets_preferences_and_updates_public_torrents() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/auth/login"))
+        .and(header("Referer", server.uri().as_str()))
+        .and(body_string("username=admin&password=secret"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header(
+                    "Set-Cookie",
+                    "SID=abc123; HttpOnly; SameSite=Strict; path=/",
+                )
+                .set_body_string("Ok."),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/app/setPreferences"))
+        .and(header("Cookie", "SID=abc123"))
+        .and(body_string_contains("add_trackers_enabled"))
+        .and(body_string_contains(
+            "udp%3A%2F%2Fa.example%3A1337%2Fannounce%5Cnhttp%3A%2F%2Fb.example%2Fannounce",
+        ))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/info"))
+        .and(header("Cookie", "SID=abc123"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            { "hash": "aaaa", "name": "Blow-Up", "private": false },
+            { "hash": "bbbb", "name": "Private", "private": true },
+            { "hash": "cccc", "name": "Old WebUI" },
+            { "hash": "dddd", "name": "Old WebUI, private" }
+        ])))
+        .mount(&server)
+        .await;
+    // 旧版本的 WebUI 不返回 `private`，需要查询种子的属性；属性中也没有时当作私有种子
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/properties"))
+        .and(query_param("hash", "cccc"))
+        .and(header("Cookie", "SID=abc123"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "is_private": false })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/v2/torrents/properties"))
+        .and(query_param("hash", "dddd"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "save_path": "/data" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/torrents/addTrackers"))
+        .and(header("Cookie", "SID=abc123"))
+        .and(body_string_contains("urls=udp%3A%2F%2Fa.example"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let client = QbittorrentClient::new(
+        server.uri(),
+        Some("admin".to_string()),
+        Some("secret".to_string()),
+    )
+    .unwrap();
+    let report = client.push(&trackers(), true).await.unwrap();
+    assert_eq!(
+        report,
+        PushReport {
+            updated: 2,
+            skipped_private: 2
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_push_reports_login_and_api_failures() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/auth/login"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("Fails."))
+        .mount(&server)
+        .await;
+    let client = QbittorrentClient::new(
+        server.uri(),
+        Some("admin".to_string()),
+        Some("wrong".to_string()),
+    )
+    .unwrap();
+    assert!(matches!(
+        client.push(&trackers(), true).await,
+        Err(TorrentError::LoginFailed(_))
+    ));
+
+    // 没有用户名时不登录，只设置默认 tracker
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/v2/app/setPreferences"))
+        .respond_with(ResponseTemplate::new(403).set_body_string("Forbidden"))
+        .mount(&server)
+        .await;
+    let client = QbittorrentClient::new(format!("{}/", server.uri()), None, None).unwrap();
+    match client.push(&trackers(), false).await {
+        Err(TorrentError::ClientError {
+            status, message, ..
+        }) => {
+            assert_eq!(status, 403);
+            assert_eq!(message, "Forbidden");
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
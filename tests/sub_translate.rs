@@ -0,0 +1,120 @@
+use std::fs;
+
+use blowup::{
+    ai::OllamaModel,
+    sub::{TranslateError, TranslateOptions, translate_srt_with, translated_path},
+};
+use serde_json::{Value, json};
+use tempfile::tempdir;
+use wiremock::{
+    Mock, MockServer, Request, Respond, ResponseTemplate,
+    matchers::{body_partial_json, method, path},
+};
+
+const SRT: &str = "1\n00:00:01,000 --> 00:00:02,000\nHello.\n\n\
+2\n00:00:03,000 --> 00:00:04,500\nWho took the photos?\nI did.\n\n\
+3\n00:00:05,000 --> 00:00:06,000\nIn the park.\n\n\
+7\n00:00:08,250 --> 00:00:09,000\nGoodbye.\n\n";
+
+/// 模拟 Ollama 的 `/api/generate`：为提示中的每个条目返回一条译文，分两段以流的形式返回
+struct FakeOllama {
+    /// 少返回的译文数量
+    drop: usize,
+}
+
+impl Respond for FakeOllama {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let body: Value = serde_json::from_slice(&request.body).unwrap();
+        let prompt = body["prompt"].as_str().unwrap();
+        let batch: Value = serde_json::from_str(&prompt[prompt.find('{').unwrap()..]).unwrap();
+        let cues = batch["translate"].as_array().unwrap();
+        let translations: Vec<Value> = cues[..cues.len() - self.drop.min(cues.len())]
+            .iter()
+            .map(|cue| json!({ "id": cue["id"], "text": format!("[zh] {}", cue["text"].as_str().unwrap()) }))
+            .collect();
+        let output = json!({ "translations": translations }).to_string();
+        let (first, second) = output.split_at(output.len() / 2);
+        let lines: Vec<String> = [(first, false), (second, true)]
+            .iter()
+            .map(|(response, done)| {
+                json!({
+                    "model": "test",
+                    "created_at": "2026-10-18T12:00:00Z",
+                    "response": response,
+                    "done": done,
+                })
+                .to_string()
+            })
+            .collect();
+        ResponseTemplate::new(200).set_body_string(lines.join("\n") + "\n")
+    }
+}
+
+async fn ollama(drop: usize) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/generate"))
+        .and(body_partial_json(
+            json!({ "model": "test", "format": "json", "stream": true }),
+        ))
+        .respond_with(FakeOllama { drop })
+        .mount(&server)
+        .await;
+    server
+}
+
+#[tokio::test]
+async fn test_translate_srt_keeps_indices_and_timings() {
+    let server = ollama(0).await;
+    let model = OllamaModel::new(&server.uri(), "test").unwrap();
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("Blow-Up.en.srt");
+    fs::write(&input, SRT).unwrap();
+
+    let options = TranslateOptions {
+        batch_size: 3,
+        ..TranslateOptions::default()
+    };
+    let output = translate_srt_with(&model, &input, None, &options)
+        .await
+        .unwrap();
+    assert_eq!(output, translated_path(&input, "zh"));
+    assert_eq!(
+        fs::read_to_string(&output).unwrap(),
+        "1\n00:00:01,000 --> 00:00:02,000\n[zh] Hello.\n\n\
+2\n00:00:03,000 --> 00:00:04,500\n[zh] Who took the photos?\nI did.\n\n\
+3\n00:00:05,000 --> 00:00:06,000\n[zh] In the park.\n\n\
+7\n00:00:08,250 --> 00:00:09,000\n[zh] Goodbye.\n\n"
+    );
+    assert_eq!(server.received_requests().await.unwrap().len(), 2);
+
+    assert!(matches!(
+        translate_srt_with(&model, &input, None, &options).await,
+        Err(TranslateError::OutputExists(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_translate_srt_rejects_missing_translations() {
+    let server = ollama(1).await;
+    let model = OllamaModel::new(&server.uri(), "test").unwrap();
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("Blow-Up.en.srt");
+    fs::write(&input, SRT).unwrap();
+    let output = dir.path().join("out.srt");
+
+    let options = TranslateOptions {
+        retries: 1,
+        ..TranslateOptions::default()
+    };
+    assert!(matches!(
+        translate_srt_with(&model, &input, Some(&output), &options).await,
+        Err(TranslateError::Mismatch {
+            first: 1,
+            last: 4,
+            ..
+        })
+    ));
+    assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    assert!(!output.exists());
+}
@@ -0,0 +1,143 @@
+use std::fs;
+
+use blowup::{
+    common::{CommandError, CommonError, RecordedCall, ReplayRunner},
+    ffmpeg::FfmpegError,
+    sub::{extract_sub_srt_with, subtitle_streams_with},
+};
+
+#[cfg(target_family = "unix")]
+const FFMPEG: &str = "ffmpeg";
+#[cfg(target_family = "unix")]
+const FFPROBE: &str = "ffprobe";
+#[cfg(target_family = "windows")]
+const FFMPEG: &str = "ffmpeg.exe";
+#[cfg(target_family = "windows")]
+const FFPROBE: &str = "ffprobe.exe";
+
+fn fixture(name: &str) -> String {
+    fs::read_to_string(format!("./tests/fixtures/ffprobe/{}.json", name))
+        .expect("failed to read the ffprobe fixture")
+}
+
+#[tokio::test]
+async fn test_extract_sub_srt_success() {
+    let runner = ReplayRunner::new();
+    runner.push_success("", "");
+
+    extract_sub_srt_with(&runner, "movie.mkv", "movie.srt")
+        .await
+        .unwrap();
+
+    assert_eq!(
+        runner.calls(),
+        vec![RecordedCall {
+            cmd: FFMPEG.into(),
+            args: [
+                "-i",
+                "movie.mkv",
+                "-map",
+                "0:s:0",
+                "-c",
+                "copy",
+                "movie.srt"
+            ]
+            .iter()
+            .map(|a| a.to_string())
+            .collect(),
+        }]
+    );
+}
+
+#[tokio::test]
+async fn test_extract_sub_srt_failure() {
+    let runner = ReplayRunner::new();
+    runner.push_failure(1, "Stream map '0:s:0' matches no streams.");
+
+    let result = extract_sub_srt_with(&runner, "movie.mp4", "movie.srt").await;
+
+    match result {
+        Err(FfmpegError::CmdExecError(CommonError::CommandError(
+            CommandError::CommandFailed { status, stderr, .. },
+        ))) => {
+            assert_eq!(status.code(), Some(1));
+            assert!(stderr.contains("matches no streams"));
+        }
+        other => panic!("Expected CommandFailed error, but got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_extract_sub_srt_without_ffmpeg() {
+    let runner = ReplayRunner::new().with_missing(FFMPEG);
+
+    let result = extract_sub_srt_with(&runner, "movie.mkv", "movie.srt").await;
+
+    assert!(matches!(result, Err(FfmpegError::FfmpegNotFound)));
+    assert!(runner.calls().is_empty());
+}
+
+#[tokio::test]
+async fn test_subtitle_streams_from_fixtures() {
+    let runner = ReplayRunner::new();
+    runner.push_success(fixture("mkv"), "");
+    runner.push_success(fixture("ts"), "");
+
+    let mkv = subtitle_streams_with(&runner, "movie.mkv").await.unwrap();
+    assert_eq!(mkv.len(), 2);
+    assert_eq!(mkv[0].duration, Some(7_619_968));
+    assert_eq!(mkv[1].language.as_deref(), Some("chi"));
+
+    let ts = subtitle_streams_with(&runner, "recording.ts")
+        .await
+        .unwrap();
+    assert_eq!(ts.len(), 1);
+    assert_eq!(ts[0].duration, None);
+
+    let calls = runner.calls();
+    assert_eq!(calls[0].cmd, std::path::PathBuf::from(FFPROBE));
+    assert_eq!(calls[0].args.last().map(String::as_str), Some("movie.mkv"));
+    assert!(calls[0].args.iter().any(|a| a == "-select_streams"));
+}
+
+#[tokio::test]
+async fn test_subtitle_streams_empty_output() {
+    let runner = ReplayRunner::new();
+    runner.push_success("", "");
+    runner.push_success(r#"{ "streams": [] }"#, "");
+
+    assert!(
+        subtitle_streams_with(&runner, "a.mkv")
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert!(
+        subtitle_streams_with(&runner, "b.mkv")
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+async fn test_subtitle_streams_error_paths() {
+    let runner = ReplayRunner::new();
+    runner.push_success("this is not json", "");
+    runner.push_failure(1, "movie.mkv: No such file or directory");
+
+    let invalid = subtitle_streams_with(&runner, "movie.mkv").await;
+    assert!(matches!(invalid, Err(FfmpegError::ProbeParseError(_))));
+
+    let failed = subtitle_streams_with(&runner, "movie.mkv").await;
+    assert!(matches!(
+        failed,
+        Err(FfmpegError::CmdExecError(CommonError::CommandError(
+            CommandError::CommandFailed { .. }
+        )))
+    ));
+
+    let missing =
+        subtitle_streams_with(&ReplayRunner::new().with_missing(FFPROBE), "movie.mkv").await;
+    assert!(matches!(missing, Err(FfmpegError::FfmpegNotFound)));
+}
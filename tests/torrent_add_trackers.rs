@@ -0,0 +1,57 @@
+use std::fs;
+
+use blowup::torrent::{TorrentError, inject::add_trackers, metainfo::Metainfo};
+use tempfile::tempdir;
+
+const INFO: &[u8] =
+    b"d6:lengthi5e4:name5:a.mkv12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaae";
+const PRIVATE_INFO: &[u8] =
+    b"d6:lengthi5e4:name5:a.mkv12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaa7:privatei1ee";
+
+#[tokio::test]
+async fn test_add_trackers_to_torrent_file() {
+    let dir = tempdir().unwrap();
+    let list = dir.path().join("tracker_all.txt");
+    fs::write(
+        &list,
+        "udp://a.example:1337/announce\n\nhttp://b.example/announce\n",
+    )
+    .unwrap();
+
+    let input = dir.path().join("a.torrent");
+    let original = [b"d4:info".as_slice(), INFO, b"e"].concat();
+    fs::write(&input, &original).unwrap();
+    let before = Metainfo::parse(&original).unwrap();
+
+    // 指定输出时不修改原文件
+    let output = dir.path().join("b.torrent");
+    add_trackers(input.to_str().unwrap(), &list, Some(&output))
+        .await
+        .unwrap();
+    assert_eq!(fs::read(&input).unwrap(), original);
+    let after = Metainfo::from_file(&output).await.unwrap();
+    assert_eq!(after.info_hash_v1, before.info_hash_v1);
+    assert_eq!(
+        after.tiers(),
+        vec![
+            vec!["udp://a.example:1337/announce"],
+            vec!["http://b.example/announce"],
+        ]
+    );
+
+    // 默认覆盖原文件
+    add_trackers(input.to_str().unwrap(), &list, None)
+        .await
+        .unwrap();
+    assert_eq!(fs::read(&input).unwrap(), fs::read(&output).unwrap());
+    assert!(!dir.path().join("a.torrent.tmp").exists());
+
+    // 私有种子保持不变
+    let private = [b"d4:info".as_slice(), PRIVATE_INFO, b"e"].concat();
+    fs::write(&input, &private).unwrap();
+    assert!(matches!(
+        add_trackers(input.to_str().unwrap(), &list, None).await,
+        Err(TorrentError::PrivateTorrent(_))
+    ));
+    assert_eq!(fs::read(&input).unwrap(), private);
+}
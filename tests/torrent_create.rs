@@ -0,0 +1,298 @@
+use std::fs;
+
+use blowup::torrent::{
+    TorrentError,
+    bencode::{Value, decode},
+    create::{CreateOptions, TorrentVersion, create_torrent, create_torrent_file},
+    metainfo::Metainfo,
+};
+use serde_json::Value as Json;
+use sha1::{Digest, Sha1};
+use tempfile::tempdir;
+
+fn options(version: TorrentVersion) -> CreateOptions {
+    CreateOptions {
+        version,
+        piece_length: Some(32 * 1024),
+        jobs: 4,
+        ..CreateOptions::default()
+    }
+}
+
+#[tokio::test]
+async fn test_create_torrent_versions() {
+    let dir = tempdir().unwrap();
+    let share = dir.path().join("Blow-Up");
+    fs::create_dir_all(share.join("Subs")).unwrap();
+    let video: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    fs::write(share.join("Blow-Up.mkv"), &video).unwrap();
+    fs::write(
+        share.join("Subs").join("en.srt"),
+        b"1\n00:00:01,000 --> 00:00:02,000\nHi\n",
+    )
+    .unwrap();
+
+    // v1 的 piece 跨越文件，可以直接由连续的数据计算
+    let data = create_torrent(&share, &options(TorrentVersion::V1))
+        .await
+        .unwrap();
+    let info = Metainfo::parse(&data).unwrap();
+    assert_eq!(info.name, "Blow-Up");
+    assert_eq!(info.info_hash_v2, None);
+    assert_eq!(
+        info.files
+            .iter()
+            .map(|f| f.path.as_str())
+            .collect::<Vec<_>>(),
+        vec!["Blow-Up.mkv", "Subs/en.srt"]
+    );
+    let stream = [
+        video.clone(),
+        fs::read(share.join("Subs").join("en.srt")).unwrap(),
+    ]
+    .concat();
+    let pieces: Vec<u8> = stream
+        .chunks(32 * 1024)
+        .flat_map(|piece| Sha1::digest(piece).to_vec())
+        .collect();
+    let torrent = decode(&data).unwrap();
+    assert_eq!(
+        torrent
+            .get("info")
+            .unwrap()
+            .get("pieces")
+            .and_then(Value::as_bytes),
+        Some(pieces.as_slice())
+    );
+
+    let data = create_torrent(&share, &options(TorrentVersion::V2))
+        .await
+        .unwrap();
+    let info = Metainfo::parse(&data).unwrap();
+    assert_eq!(info.info_hash_v1, None);
+    assert!(info.info_hash_v2.is_some());
+    assert_eq!(info.files.len(), 2);
+    // 超过一个 piece 的文件有 piece layer
+    let torrent = decode(&data).unwrap();
+    assert_eq!(
+        torrent
+            .get("piece layers")
+            .and_then(Value::as_dict)
+            .unwrap()
+            .len(),
+        1
+    );
+
+    let data = create_torrent(&share, &options(TorrentVersion::Hybrid))
+        .await
+        .unwrap();
+    let info = Metainfo::parse(&data).unwrap();
+    assert!(info.info_hash_v1.is_some() && info.info_hash_v2.is_some());
+    // 填充文件不计入文件列表，但 v1 的 piece 数量包括填充
+    assert_eq!(info.files.len(), 2);
+    assert_eq!(info.piece_count, 5);
+    let files = decode(&data).unwrap();
+    let files = files
+        .get("info")
+        .unwrap()
+        .get("files")
+        .and_then(Value::as_list)
+        .unwrap();
+    assert_eq!(files[1].get("attr").and_then(Value::as_str), Some("p"));
+    assert_eq!(files[1].get("length").and_then(Value::as_int), Some(31072));
+}
+
+#[tokio::test]
+async fn test_create_torrent_file_options() {
+    let dir = tempdir().unwrap();
+    let file = dir.path().join("Blow-Up.mkv");
+    fs::write(&file, vec![1u8; 50_000]).unwrap();
+    let output = dir.path().join("out.torrent");
+    let list = dir.path().join("tracker_all.txt");
+    fs::write(
+        &list,
+        "udp://c.example:6969/announce\n\nhttp://d.example/announce\n",
+    )
+    .unwrap();
+
+    let options = CreateOptions {
+        private: true,
+        comment: Some("our encode".to_string()),
+        source: Some("HERMES".to_string()),
+        web_seeds: vec!["https://seed.example/Blow-Up.mkv".to_string()],
+        trackers: vec![
+            "udp://a.example:1337/announce".to_string(),
+            "http://b.example/announce".to_string(),
+        ],
+        tracker_file: Some(list.clone()),
+        ..CreateOptions::default()
+    };
+    create_torrent_file(&file, Some(&output), &options)
+        .await
+        .unwrap();
+    let info = Metainfo::from_file(&output).await.unwrap();
+    assert!(!info.multi_file);
+    assert!(info.private);
+    assert_eq!(info.piece_length, 16 * 1024);
+    assert_eq!(info.comment.as_deref(), Some("our encode"));
+    assert_eq!(info.web_seeds, vec!["https://seed.example/Blow-Up.mkv"]);
+    assert_eq!(
+        info.announce.as_deref(),
+        Some("udp://a.example:1337/announce")
+    );
+    // 私有种子不使用 tracker 列表文件
+    assert_eq!(info.tiers().len(), 2);
+    assert!(info.created_by.unwrap().starts_with("blowup "));
+    let torrent = decode(&fs::read(&output).unwrap()).unwrap();
+    assert_eq!(
+        torrent
+            .get("info")
+            .unwrap()
+            .get("source")
+            .and_then(Value::as_str),
+        Some("HERMES")
+    );
+
+    // 不覆盖已有的文件
+    assert!(matches!(
+        create_torrent_file(&file, Some(&output), &options).await,
+        Err(TorrentError::IoError(_))
+    ));
+
+    let public = CreateOptions {
+        private: false,
+        ..options.clone()
+    };
+    let info = Metainfo::parse(&create_torrent(&file, &public).await.unwrap()).unwrap();
+    assert_eq!(
+        info.tiers(),
+        vec![
+            vec!["udp://a.example:1337/announce"],
+            vec!["http://b.example/announce"],
+            vec!["udp://c.example:6969/announce"],
+            vec!["http://d.example/announce"],
+        ]
+    );
+    // 列表文件不存在时只使用指定的 tracker
+    let missing = CreateOptions {
+        tracker_file: Some(dir.path().join("missing.txt")),
+        ..public
+    };
+    let info = Metainfo::parse(&create_torrent(&file, &missing).await.unwrap()).unwrap();
+    assert_eq!(info.tiers().len(), 2);
+
+    let empty = dir.path().join("empty");
+    fs::create_dir(&empty).unwrap();
+    assert!(matches!(
+        create_torrent(&empty, &options).await,
+        Err(TorrentError::NothingToShare(_))
+    ));
+    let bad = CreateOptions {
+        piece_length: Some(1000),
+        ..CreateOptions::default()
+    };
+    assert!(matches!(
+        create_torrent(&file, &bad).await,
+        Err(TorrentError::InvalidPieceLength(1000))
+    ));
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 与 `tests/fixtures/torrent/bep52.py` 生成相同的样本文件
+fn write_reference_share(root: &std::path::Path) {
+    fs::create_dir_all(root.join("Subs")).unwrap();
+    let video: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    fs::write(root.join("Blow-Up.mkv"), video).unwrap();
+    fs::write(
+        root.join("Subs").join("en.srt"),
+        b"1\n00:00:01,000 --> 00:00:02,000\nHi\n",
+    )
+    .unwrap();
+    let bin: Vec<u8> = (0..3 * 16384u32).map(|i| (i * 7 % 256) as u8).collect();
+    fs::write(root.join("c.bin"), bin).unwrap();
+}
+
+/// v2 的 `pieces root`、`piece layers` 以及 hybrid 的填充文件布局，
+/// 期望值由 `tests/fixtures/torrent/bep52.py` 只用 hashlib 按照 BEP 52/47 独立计算
+#[tokio::test]
+async fn test_create_torrent_matches_reference() {
+    let reference: Json =
+        serde_json::from_str(&fs::read_to_string("./tests/fixtures/torrent/bep52.json").unwrap())
+            .unwrap();
+    let dir = tempdir().unwrap();
+    let share = dir.path().join("Blow-Up");
+    write_reference_share(&share);
+    assert_eq!(reference["piece_length"], 32 * 1024);
+
+    for version in [TorrentVersion::V2, TorrentVersion::Hybrid] {
+        let torrent = decode(&create_torrent(&share, &options(version)).await.unwrap()).unwrap();
+        let info = torrent.get("info").unwrap();
+        let layers = torrent
+            .get("piece layers")
+            .and_then(Value::as_dict)
+            .unwrap();
+        for file in reference["v2_files"].as_array().unwrap() {
+            let mut node = info.get("file tree").unwrap();
+            for component in file["path"].as_str().unwrap().split('/') {
+                node = node.get(component).unwrap();
+            }
+            let root = node
+                .get("")
+                .and_then(|leaf| leaf.get("pieces root"))
+                .and_then(Value::as_bytes)
+                .unwrap();
+            assert_eq!(hex(root), file["pieces_root"], "{}", file["path"]);
+            let layer = layers.get(root).and_then(Value::as_bytes).map(hex);
+            assert_eq!(
+                layer.as_deref(),
+                file["piece_layer"].as_str(),
+                "{}",
+                file["path"]
+            );
+        }
+        assert_eq!(layers.len(), 2);
+    }
+
+    let torrent = decode(
+        &create_torrent(&share, &options(TorrentVersion::Hybrid))
+            .await
+            .unwrap(),
+    )
+    .unwrap();
+    let info = torrent.get("info").unwrap();
+    let files: Vec<Json> = info
+        .get("files")
+        .and_then(Value::as_list)
+        .unwrap()
+        .iter()
+        .map(|file| {
+            let path: Vec<&str> = file
+                .get("path")
+                .and_then(Value::as_list)
+                .unwrap()
+                .iter()
+                .filter_map(Value::as_str)
+                .collect();
+            let mut json = serde_json::json!({
+                "path": path.join("/"),
+                "length": file.get("length").and_then(Value::as_int).unwrap(),
+            });
+            if let Some(attr) = file.get("attr").and_then(Value::as_str) {
+                json["attr"] = attr.into();
+            }
+            json
+        })
+        .collect();
+    assert_eq!(Json::Array(files), reference["hybrid_files"]);
+    let pieces: Vec<String> = info
+        .get("pieces")
+        .and_then(Value::as_bytes)
+        .unwrap()
+        .chunks(20)
+        .map(hex)
+        .collect();
+    assert_eq!(serde_json::json!(pieces), reference["hybrid_pieces"]);
+}
@@ -0,0 +1,68 @@
+use std::fs;
+
+use blowup::{
+    common::OutputFormat,
+    torrent::{
+        TorrentError,
+        bencode::Value,
+        metainfo::{Metainfo, show_torrent_info},
+    },
+};
+use tempfile::tempdir;
+
+fn dict(pairs: Vec<(&str, Value)>) -> Value {
+    Value::Dict(
+        pairs
+            .into_iter()
+            .map(|(k, v)| (k.as_bytes().to_vec(), v))
+            .collect(),
+    )
+}
+
+fn torrent() -> Vec<u8> {
+    dict(vec![
+        ("announce", "udp://a.example:1337/announce".into()),
+        ("creation date", Value::Int(0)),
+        (
+            "info",
+            dict(vec![
+                ("length", Value::Int(1 << 20)),
+                ("name", "Blow-Up (1966).mkv".into()),
+                ("piece length", Value::Int(1 << 18)),
+                ("pieces", Value::Bytes(vec![7; 80])),
+            ]),
+        ),
+    ])
+    .encode()
+}
+
+#[tokio::test]
+async fn test_torrent_info_from_file() {
+    let dir = tempdir().unwrap();
+    let file = dir.path().join("blow-up.torrent");
+    fs::write(&file, torrent()).unwrap();
+
+    let info = Metainfo::from_file(&file).await.unwrap();
+    assert_eq!(info.name, "Blow-Up (1966).mkv");
+    assert_eq!(info.piece_count, 4);
+    assert_eq!(info.total_size, 1 << 20);
+    assert_eq!(
+        info.creation_date.as_deref(),
+        Some("1970-01-01T00:00:00+00:00")
+    );
+    assert_eq!(info.tiers(), vec![vec!["udp://a.example:1337/announce"]]);
+
+    for format in [OutputFormat::List, OutputFormat::Table, OutputFormat::Json] {
+        show_torrent_info(&file, format).await.unwrap();
+    }
+
+    fs::write(&file, b"d4:infoi1ee").unwrap();
+    assert!(matches!(
+        Metainfo::from_file(&file).await,
+        Err(TorrentError::InvalidMetainfo(_))
+    ));
+    assert!(matches!(
+        Metainfo::from_file(dir.path().join("missing.torrent")).await,
+        Err(TorrentError::IoError(_))
+    ));
+}
@@ -0,0 +1,56 @@
+use std::fs;
+
+use blowup::torrent::{
+    magnet::{Magnet, torrent_to_magnet},
+    metainfo::Metainfo,
+};
+use tempfile::tempdir;
+
+const INFO: &[u8] =
+    b"d6:lengthi5e4:name9:Blow-Up 112:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaae";
+const PRIVATE_INFO: &[u8] = b"d6:lengthi5e4:name9:Blow-Up 212:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaa7:privatei1ee";
+
+fn torrent(info: &[u8]) -> Vec<u8> {
+    [
+        b"d8:announce29:udp://a.example:1337/announce4:info".as_slice(),
+        info,
+        b"8:url-list21:https://seed.example/e",
+    ]
+    .concat()
+}
+
+#[tokio::test]
+async fn test_torrent_to_magnet() {
+    let dir = tempdir().unwrap();
+    let list = dir.path().join("tracker_all.txt");
+    fs::write(
+        &list,
+        "udp://a.example:1337/announce\nhttp://b.example/announce\n",
+    )
+    .unwrap();
+    let file = dir.path().join("a.torrent");
+    fs::write(&file, torrent(INFO)).unwrap();
+    let info = Metainfo::from_file(&file).await.unwrap();
+
+    let magnet = torrent_to_magnet(&file, Some(&list)).await.unwrap();
+    let uri = magnet.to_string();
+    assert!(uri.starts_with(&format!(
+        "magnet:?xt=urn:btih:{}&dn=Blow-Up%201&xl=5&",
+        info.info_hash_v1.unwrap()
+    )));
+    let parsed = Magnet::parse(&uri).unwrap();
+    assert_eq!(
+        parsed.trackers,
+        vec!["udp://a.example:1337/announce", "http://b.example/announce"]
+    );
+    assert_eq!(parsed.web_seeds, vec!["https://seed.example/"]);
+
+    // 不指定列表时只包含种子自己的 tracker
+    let magnet = torrent_to_magnet(&file, None).await.unwrap();
+    assert_eq!(magnet.trackers, vec!["udp://a.example:1337/announce"]);
+
+    // 私有种子不追加公共 tracker
+    fs::write(&file, torrent(PRIVATE_INFO)).unwrap();
+    let magnet = torrent_to_magnet(&file, Some(&list)).await.unwrap();
+    assert_eq!(magnet.trackers, vec!["udp://a.example:1337/announce"]);
+}
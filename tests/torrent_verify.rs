@@ -0,0 +1,147 @@
+use std::fs;
+
+use blowup::torrent::{
+    TorrentError,
+    create::{CreateOptions, TorrentVersion, create_torrent},
+    verify::{FileStatus, verify_torrent, verify_torrent_file},
+};
+use sha1::{Digest, Sha1};
+use tempfile::tempdir;
+
+#[tokio::test]
+async fn test_verify_detects_missing_truncated_and_corrupt_files() {
+    for version in [
+        TorrentVersion::V1,
+        TorrentVersion::V2,
+        TorrentVersion::Hybrid,
+    ] {
+        let dir = tempdir().unwrap();
+        let share = dir.path().join("Blow-Up");
+        fs::create_dir_all(share.join("Subs")).unwrap();
+        let video: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(share.join("Blow-Up.mkv"), &video).unwrap();
+        fs::write(share.join("Subs").join("en.srt"), vec![b'e'; 20_000]).unwrap();
+        fs::write(share.join("Subs").join("zh.srt"), vec![b'z'; 5_000]).unwrap();
+
+        let options = CreateOptions {
+            version,
+            piece_length: Some(32 * 1024),
+            jobs: 2,
+            ..CreateOptions::default()
+        };
+        let torrent = create_torrent(&share, &options).await.unwrap();
+
+        // 下载目录和内容目录本身都可以
+        for root in [dir.path(), share.as_path()] {
+            let report = verify_torrent(&torrent, root, 2).await.unwrap();
+            assert!(report.is_complete(), "{:?}", version);
+            assert_eq!(report.completion(), 100.0);
+            assert_eq!(report.files.len(), 3);
+        }
+
+        let mut damaged = video.clone();
+        damaged[40_000] ^= 0xff;
+        fs::write(share.join("Blow-Up.mkv"), &damaged).unwrap();
+        fs::write(share.join("Subs").join("en.srt"), vec![b'e'; 10_000]).unwrap();
+        fs::remove_file(share.join("Subs").join("zh.srt")).unwrap();
+
+        let report = verify_torrent(&torrent, dir.path(), 2).await.unwrap();
+        let statuses: Vec<(&str, &FileStatus)> = report
+            .files
+            .iter()
+            .map(|f| (f.path.as_str(), &f.status))
+            .collect();
+        assert!(
+            matches!(statuses[0], ("Blow-Up.mkv", FileStatus::Corrupt { .. })),
+            "{:?}",
+            version
+        );
+        assert_eq!(
+            statuses[1],
+            ("Subs/en.srt", &FileStatus::Truncated { actual: 10_000 })
+        );
+        assert_eq!(statuses[2], ("Subs/zh.srt", &FileStatus::Missing));
+        assert!(!report.is_complete());
+        assert!(report.completion() > 0.0 && report.completion() < 100.0);
+    }
+}
+
+#[tokio::test]
+async fn test_verify_single_file_torrent() {
+    let dir = tempdir().unwrap();
+    let file = dir.path().join("Blow-Up.mkv");
+    fs::write(&file, vec![7u8; 70_000]).unwrap();
+    let torrent_file = dir.path().join("Blow-Up.torrent");
+    let options = CreateOptions {
+        version: TorrentVersion::V2,
+        jobs: 2,
+        ..CreateOptions::default()
+    };
+    fs::write(
+        &torrent_file,
+        create_torrent(&file, &options).await.unwrap(),
+    )
+    .unwrap();
+
+    verify_torrent_file(&torrent_file, dir.path(), 2)
+        .await
+        .unwrap();
+    verify_torrent_file(&torrent_file, &file, 2).await.unwrap();
+
+    fs::write(&file, vec![8u8; 70_000]).unwrap();
+    assert!(matches!(
+        verify_torrent_file(&torrent_file, dir.path(), 2).await,
+        Err(TorrentError::VerifyFailed { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_verify_v1_with_small_pieces() {
+    let dir = tempdir().unwrap();
+    let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+    fs::write(dir.path().join("a.bin"), &data).unwrap();
+
+    // 旧的 v1 种子可能使用小于 16 KiB 的 piece
+    let pieces: Vec<u8> = data.chunks(8192).flat_map(Sha1::digest).collect();
+    let mut torrent =
+        b"d4:infod6:lengthi10000e4:name5:a.bin12:piece lengthi8192e6:pieces40:".to_vec();
+    torrent.extend_from_slice(&pieces);
+    torrent.extend_from_slice(b"ee");
+
+    let report = verify_torrent(&torrent, dir.path(), 2).await.unwrap();
+    assert!(report.is_complete());
+    assert_eq!(report.pieces, 2);
+}
+
+#[tokio::test]
+async fn test_verify_rejects_invalid_v2_piece_length() {
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join("a.bin"), vec![1u8; 100_000]).unwrap();
+
+    // 不是 2 的幂，以及不是 16 KiB 的整数倍；piece layer 完整，只有 piece 大小不合法
+    for piece_length in [48 * 1024, 24 * 1024, 8 * 1024] {
+        let layer_len = 100_000u64.div_ceil(piece_length) * 32;
+        let mut torrent =
+            b"d4:infod9:file treed5:a.bind0:d6:lengthi100000e11:pieces root32:".to_vec();
+        torrent.extend_from_slice(&[0; 32]);
+        torrent.extend_from_slice(
+            format!(
+                "eee12:meta versioni2e4:name5:a.bin12:piece lengthi{}ee12:piece layersd32:",
+                piece_length
+            )
+            .as_bytes(),
+        );
+        torrent.extend_from_slice(&[0; 32]);
+        torrent.extend_from_slice(format!("{}:", layer_len).as_bytes());
+        torrent.extend(vec![0; layer_len as usize]);
+        torrent.extend_from_slice(b"ee");
+        assert!(
+            matches!(
+                verify_torrent(&torrent, dir.path(), 2).await,
+                Err(TorrentError::InvalidMetainfo(_))
+            ),
+            "{}",
+            piece_length
+        );
+    }
+}
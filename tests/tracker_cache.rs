@@ -0,0 +1,120 @@
+use std::fs;
+
+use blowup::torrent::{
+    TorrentError,
+    cache::TrackerCache,
+    download_newest_tracker_with,
+    output::{TrackerListFormat, TrackerOutput},
+    source::TrackerSource,
+    tracker::TrackerPolicy,
+};
+use tempfile::tempdir;
+use wiremock::{
+    Mock, MockServer, ResponseTemplate,
+    matchers::{header, method, path},
+};
+
+const LIST: &str = "udp://a.example:1337/announce\nhttp://b.example/announce\n";
+
+#[tokio::test]
+async fn test_conditional_fetch_and_offline_fallback() {
+    // 不使用共享的服务器池，drop 之后端口真正关闭
+    let server = MockServer::builder().start().await;
+    Mock::given(method("GET"))
+        .and(path("/trackers.txt"))
+        .and(header("If-None-Match", "\"v1\""))
+        .respond_with(ResponseTemplate::new(304))
+        .with_priority(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/trackers.txt"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("ETag", "\"v1\"")
+                .insert_header("Last-Modified", "Sat, 17 Oct 2026 08:00:00 GMT")
+                .set_body_string(LIST),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let dir = tempdir().unwrap();
+    let cache_file = dir.path().join("cache").join("trackers.json");
+    let output_file = dir.path().join("tracker_all.txt");
+    let output = TrackerOutput::File(output_file.clone());
+    let sources: Vec<TrackerSource> =
+        vec![format!("{}/trackers.txt", server.uri()).parse().unwrap()];
+    let policy = TrackerPolicy::new(&[], &[], &[]).unwrap();
+    let expected = "udp://a.example:1337/announce\nhttp://b.example/announce\n";
+
+    // 第一次完整获取并记录校验信息
+    download_newest_tracker_with(
+        &sources,
+        &policy,
+        &output,
+        TrackerListFormat::Lines,
+        Some(&cache_file),
+    )
+    .await
+    .unwrap();
+    assert_eq!(fs::read_to_string(&output_file).unwrap(), expected);
+    let cache = TrackerCache::load(&cache_file);
+    let entry = cache.get(&sources[0].to_string()).unwrap();
+    assert_eq!(entry.etag.as_deref(), Some("\"v1\""));
+    assert_eq!(
+        entry.last_modified.as_deref(),
+        Some("Sat, 17 Oct 2026 08:00:00 GMT")
+    );
+
+    // 第二次发送条件请求，304 时使用缓存的内容
+    fs::remove_file(&output_file).unwrap();
+    download_newest_tracker_with(
+        &sources,
+        &policy,
+        &output,
+        TrackerListFormat::Lines,
+        Some(&cache_file),
+    )
+    .await
+    .unwrap();
+    assert_eq!(fs::read_to_string(&output_file).unwrap(), expected);
+    server.verify().await;
+
+    // 网络不可用时退回到缓存的列表
+    drop(server);
+    fs::remove_file(&output_file).unwrap();
+    download_newest_tracker_with(
+        &sources,
+        &policy,
+        &output,
+        TrackerListFormat::Lines,
+        Some(&cache_file),
+    )
+    .await
+    .unwrap();
+    assert_eq!(fs::read_to_string(&output_file).unwrap(), expected);
+
+    // 没有缓存时无法离线工作，已有的列表保持不变
+    assert!(matches!(
+        download_newest_tracker_with(&sources, &policy, &output, TrackerListFormat::Aria2, None)
+            .await,
+        Err(TorrentError::NoTrackers)
+    ));
+    assert_eq!(fs::read_to_string(&output_file).unwrap(), expected);
+
+    download_newest_tracker_with(
+        &sources,
+        &policy,
+        &output,
+        TrackerListFormat::Aria2,
+        Some(&cache_file),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        fs::read_to_string(&output_file).unwrap(),
+        "udp://a.example:1337/announce,http://b.example/announce\n"
+    );
+}
@@ -0,0 +1,148 @@
+use std::{fs, time::Duration};
+
+use blowup::torrent::{
+    health::{CheckOptions, HealthStatus, check_tracker_file, check_trackers},
+    tracker::Tracker,
+};
+use tempfile::tempdir;
+use tokio::net::UdpSocket;
+use wiremock::{
+    Mock, MockServer, ResponseTemplate,
+    matchers::{method, path, query_param},
+};
+
+const PROTOCOL_ID: u64 = 0x41727101980;
+
+/// 按照 BEP 15 响应 connect 请求的 udp tracker，`error` 不为空时返回错误响应
+async fn fake_udp_tracker(error: Option<&'static str>) -> u16 {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let port = socket.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let mut buf = [0u8; 64];
+        while let Ok((len, peer)) = socket.recv_from(&mut buf).await {
+            if len < 16 || buf[..8] != PROTOCOL_ID.to_be_bytes() || buf[8..12] != [0; 4] {
+                continue;
+            }
+            let mut response = Vec::new();
+            match error {
+                None => {
+                    response.extend(0u32.to_be_bytes());
+                    response.extend(&buf[12..16]);
+                    response.extend(0x1234_5678_9abc_def0u64.to_be_bytes());
+                }
+                Some(message) => {
+                    response.extend(3u32.to_be_bytes());
+                    response.extend(&buf[12..16]);
+                    response.extend(message.as_bytes());
+                }
+            }
+            let _ = socket.send_to(&response, peer).await;
+        }
+    });
+    port
+}
+
+/// 收到请求但从不响应的 udp tracker
+async fn silent_udp_tracker() -> (UdpSocket, u16) {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let port = socket.local_addr().unwrap().port();
+    (socket, port)
+}
+
+async fn fake_http_tracker() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/alive/scrape"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("d5:filesdee"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/tracker.php"))
+        .and(query_param("numwant", "0"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("d14:failure reason7:unknowne"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/html/scrape"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("<html></html>"))
+        .mount(&server)
+        .await;
+    server
+}
+
+#[tokio::test]
+async fn test_check_trackers_against_fake_trackers() {
+    let alive_udp = fake_udp_tracker(None).await;
+    let error_udp = fake_udp_tracker(Some("banned")).await;
+    let (_silent, silent_udp) = silent_udp_tracker().await;
+    let http = fake_http_tracker().await;
+
+    let trackers: Vec<Tracker> = [
+        format!("udp://127.0.0.1:{}/announce", alive_udp),
+        format!("udp://127.0.0.1:{}/announce", error_udp),
+        format!("udp://127.0.0.1:{}/announce", silent_udp),
+        format!("{}/alive/announce", http.uri()),
+        format!("{}/tracker.php", http.uri()),
+        format!("{}/missing/announce", http.uri()),
+        format!("{}/html/announce", http.uri()),
+        "wss://tracker.example.org/announce".to_string(),
+    ]
+    .iter()
+    .map(|t| Tracker::parse(t).unwrap())
+    .collect();
+
+    let options = CheckOptions {
+        timeout: Duration::from_millis(500),
+        jobs: 4,
+    };
+    let reports = check_trackers(trackers, options).await;
+    let statuses: Vec<&HealthStatus> = reports.iter().map(|r| &r.status).collect();
+
+    assert!(statuses[0].is_alive());
+    assert_eq!(
+        statuses[1],
+        &HealthStatus::Dead("Tracker protocol error: banned".to_string())
+    );
+    assert_eq!(
+        statuses[2],
+        &HealthStatus::Dead("Tracker timed out".to_string())
+    );
+    assert!(statuses[3].is_alive());
+    assert!(statuses[4].is_alive());
+    assert!(matches!(statuses[5], HealthStatus::Dead(_)));
+    assert!(matches!(statuses[6], HealthStatus::Dead(_)));
+    assert_eq!(statuses[7], &HealthStatus::Skipped);
+}
+
+#[tokio::test]
+async fn test_check_tracker_file_writes_live_trackers() {
+    let alive_udp = fake_udp_tracker(None).await;
+    let (_silent, silent_udp) = silent_udp_tracker().await;
+    let http = fake_http_tracker().await;
+
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("trackers.txt");
+    let output = dir.path().join("alive.txt");
+    fs::write(
+        &input,
+        format!(
+            "udp://127.0.0.1:{silent_udp}/announce\n\n{uri}/alive/announce\nnot a tracker\nudp://127.0.0.1:{alive_udp}/announce\n",
+            uri = http.uri()
+        ),
+    )
+    .unwrap();
+
+    let options = CheckOptions {
+        timeout: Duration::from_millis(300),
+        jobs: 2,
+    };
+    check_tracker_file(&input, &output, options).await.unwrap();
+    assert_eq!(
+        fs::read_to_string(&output).unwrap(),
+        format!(
+            "{}/alive/announce\nudp://127.0.0.1:{}/announce\n",
+            http.uri(),
+            alive_udp
+        )
+    );
+}
@@ -0,0 +1,60 @@
+use std::fs;
+
+use blowup::torrent::{
+    cache::TrackerCache,
+    source::{TrackerSource, fetch_sources, merge_outcomes},
+};
+use tempfile::tempdir;
+use wiremock::{
+    Mock, MockServer, ResponseTemplate,
+    matchers::{method, path},
+};
+
+#[tokio::test]
+async fn test_fetch_sources_merges_urls_and_files() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/stable"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(
+            "udp://tracker.example.org:1337/announce\n\nhttp://tracker.example.net/announce\n",
+        ))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/broken"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+
+    let dir = tempdir().unwrap();
+    let local = dir.path().join("private.txt");
+    fs::write(
+        &local,
+        "# private trackers\nhttp://tracker.example.net/announce\nudp://private.example:6969/announce\n",
+    )
+    .unwrap();
+
+    let sources = vec![
+        format!("{}/stable", server.uri()).parse().unwrap(),
+        format!("{}/broken", server.uri()).parse().unwrap(),
+        TrackerSource::File(local.display().to_string()),
+    ];
+    let mut cache = TrackerCache::default();
+    let outcomes = fetch_sources(&sources, &mut cache).await;
+
+    // 每个来源的结果单独记录，失败的来源不影响其它来源
+    assert_eq!(outcomes[0].result.as_ref().unwrap().0.len(), 2);
+    assert!(outcomes[1].result.is_err());
+    assert_eq!(outcomes[2].source, sources[2]);
+    // 只缓存成功获取的远程来源
+    assert_eq!(cache.sources.len(), 1);
+    assert!(cache.get(&sources[0].to_string()).is_some());
+    assert_eq!(
+        merge_outcomes(&outcomes),
+        vec![
+            "udp://tracker.example.org:1337/announce",
+            "http://tracker.example.net/announce",
+            "udp://private.example:6969/announce",
+        ]
+    );
+}
@@ -0,0 +1,157 @@
+use blowup::torrent::{
+    TorrentError,
+    client::{PushReport, TrackerClient, transmission::TransmissionClient},
+};
+use serde_json::json;
+use wiremock::{
+    Mock, MockServer, ResponseTemplate,
+    matchers::{body_partial_json, header, header_exists, method, path},
+};
+
+const SESSION: &str = "X-Transmission-Session-Id";
+
+fn trackers() -> Vec<String> {
+    vec![
+        "udp://a.example:1337/announce".to_string(),
+        "http://b.example/announce".to_string(),
+    ]
+}
+
+#[tokio::test]
+async fn test_push_handshakes_and_adds_missing_trackers() {
+    let server = MockServer::start().await;
+    // 没有会话 id 的请求返回 409 和新的会话 id
+    Mock::given(method("POST"))
+        .and(path("/transmission/rpc"))
+        .and(header("Authorization", "Basic YWRtaW46c2VjcmV0"))
+        .respond_with(ResponseTemplate::new(409).insert_header(SESSION, "session-1"))
+        .with_priority(10)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(header(SESSION, "session-1"))
+        .and(body_partial_json(json!({
+            "method": "session-set",
+            "arguments": {
+                "default-trackers": "udp://a.example:1337/announce\n\nhttp://b.example/announce"
+            }
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "result": "success" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(header(SESSION, "session-1"))
+        .and(body_partial_json(json!({ "method": "torrent-get" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "result": "success",
+            "arguments": { "torrents": [
+                { "id": 1, "isPrivate": false, "trackers": [] },
+                { "id": 2, "isPrivate": true, "trackers": [] },
+                { "id": 3, "isPrivate": false, "trackers": [
+                    { "announce": "udp://a.example:1337/announce" }
+                ] },
+                { "id": 4, "isPrivate": false, "trackers": [
+                    { "announce": "UDP://A.example:1337/announce/" },
+                    { "announce": "http://b.example:80/Announce" }
+                ] }
+            ] }
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(header(SESSION, "session-1"))
+        .and(body_partial_json(json!({
+            "method": "torrent-set",
+            "arguments": { "ids": [1], "trackerAdd": trackers() }
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "result": "success" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    // 只添加种子中缺少的 tracker
+    Mock::given(method("POST"))
+        .and(header(SESSION, "session-1"))
+        .and(body_partial_json(json!({
+            "method": "torrent-set",
+            "arguments": { "ids": [3], "trackerAdd": ["http://b.example/announce"] }
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "result": "success" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(header_exists(SESSION))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "result": "unexpected" })))
+        .with_priority(20)
+        .mount(&server)
+        .await;
+
+    let client = TransmissionClient::new(
+        format!("{}/transmission/rpc", server.uri()),
+        Some("admin".to_string()),
+        Some("secret".to_string()),
+    )
+    .unwrap();
+    let report = client.push(&trackers(), true).await.unwrap();
+    assert_eq!(
+        report,
+        PushReport {
+            updated: 2,
+            skipped_private: 1
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_push_reports_auth_and_rpc_failures() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(401))
+        .mount(&server)
+        .await;
+    let client = TransmissionClient::new(server.uri(), Some("admin".to_string()), None).unwrap();
+    assert!(matches!(
+        client.push(&trackers(), true).await,
+        Err(TorrentError::LoginFailed(_))
+    ));
+
+    // 旧版本不认识 default-trackers
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "result": "Invalid argument",
+            "arguments": {}
+        })))
+        .mount(&server)
+        .await;
+    let client = TransmissionClient::new(server.uri(), None, None).unwrap();
+    match client.push(&trackers(), false).await {
+        Err(TorrentError::ClientError { message, .. }) => assert_eq!(message, "Invalid argument"),
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    // 无法解析的种子列表不能当作没有种子
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(json!({ "method": "session-set" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "result": "success" })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(json!({ "method": "torrent-get" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "result": "success",
+            "arguments": { "torrents": [{ "isPrivate": false }] }
+        })))
+        .mount(&server)
+        .await;
+    let client = TransmissionClient::new(server.uri(), None, None).unwrap();
+    match client.push(&trackers(), true).await {
+        Err(TorrentError::ClientError { message, .. }) => {
+            assert!(message.starts_with("invalid torrent-get response"))
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}